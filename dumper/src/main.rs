@@ -1,18 +1,20 @@
 #![no_std]
 #![no_main]
 
-use alloc::collections::vec_deque::VecDeque;
+use alloc::vec;
 use alloc::vec::Vec;
-use alloc::{format, vec};
 use core::arch::global_asm;
 use core::cmp::min;
-use core::ffi::{c_int, CStr};
+use core::ffi::{c_char, c_int, c_long, c_short, c_uint, c_ulong, c_ushort, CStr};
 use core::hint::unreachable_unchecked;
 use core::mem::{zeroed, MaybeUninit};
 use core::panic::PanicInfo;
-use core::ptr::null_mut;
-use obfw::ps4::PartItem;
-use obfw::{DumpItem, MAGIC};
+use core::ptr::{null, null_mut};
+use obfw::ps4::{
+    walk, DecryptedSelf, FileBlock, FsSource, Metadata, NodeInfo, NodeType, SelfError, SelfFile,
+    Timespec,
+};
+use obfw::{DumpSink, DumpWriter, Syscall, SyscallTable, SystemInfo};
 use okf::fd::{openat, write_all, OpenFlags, AT_FDCWD};
 use okf::lock::MtxLock;
 use okf::mount::{Filesystem, FsOps, FsStats, Mount};
@@ -20,11 +22,35 @@ use okf::namei::ComponentName;
 use okf::pcpu::Pcpu;
 use okf::thread::Thread;
 use okf::uio::{IoVec, Uio, UioSeg};
-use okf::vnode::{DirEnt, Vnode, VopLookup, VopRead, VopReadDir};
+use okf::vnode::{DirEnt, Vnode, VopGetAttr, VopLookup, VopRead, VopReadDir, VopReadLink};
 use okf::{kernel, Allocator, Kernel};
 
 extern crate alloc;
 
+/// Devices to dump as-is.
+const RAW_DEVICES: [&CStr; 1] = [c"/dev/sflash0"];
+
+/// `_IOR('d', 129, off_t)` from `sys/disk.h`.
+const DIOCGMEDIASIZE: c_ulong = 0x40086481;
+
+/// Decrypted segments of SELF files are dumped only if this file exists. Otherwise SELF files are
+/// dumped as-is.
+const DECRYPT_SELF: &CStr = c"/mnt/usb0/decrypt-self";
+
+/// Maximum size of SELF headers to read. A file with larger headers is dumped as-is.
+const SELF_HEADERS_MAX: usize = 0x10000;
+
+/// Lowest address of the kernel half of the address space.
+const KERNEL_START: usize = 0xFFFF800000000000;
+
+/// Size of a page on the PS4.
+const PAGE_SIZE: u64 = 0x4000;
+
+const ENOMEM: c_int = 12;
+const VM_PROT_READ: u8 = 1;
+const MAP_SHARED: c_int = 1;
+const OBJT_VNODE: c_int = 2;
+
 // The job of this custom entry point is:
 //
 // - Get address where our payload is loaded.
@@ -93,11 +119,49 @@ fn run<K: Kernel>(k: K) {
 
     // Write magic.
     let fd = fd.as_raw_fd();
+    let mut dump = match DumpWriter::new(DumpFile { k, fd }) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+
+    // Write system information.
+    if dump.system_info(&system_info(k)).is_err() {
+        return;
+    }
+
+    // Write kernel image. The other items are still useful without it so we only stop on a write
+    // error.
+    let mut partial = false;
+
+    match unsafe { dump_kernel(k, &mut dump) } {
+        Ok(v) => partial |= !v,
+        Err(_) => return,
+    }
 
-    if !write_dump(k, fd, MAGIC) {
+    // Write syscall table.
+    if dump.syscalls(&unsafe { syscalls(k) }).is_err() {
         return;
     }
 
+    // Write kernel modules.
+    match unsafe { dump_modules(k, &mut dump) } {
+        Ok(v) => partial |= !v,
+        Err(_) => return,
+    }
+
+    // Write raw devices.
+    for dev in RAW_DEVICES {
+        match unsafe { dump_device(k, &mut dump, dev) } {
+            Ok(v) => partial |= !v,
+            Err(_) => return,
+        }
+    }
+
+    // Check if SELF decryption was requested.
+    let flags = OpenFlags::O_RDONLY;
+    let decrypt =
+        unsafe { openat(k, AT_FDCWD, DECRYPT_SELF.as_ptr(), UioSeg::Kernel, flags, 0).is_ok() };
+
     // Lock mount list.
     let mtx = k.var(K::MOUNTLIST_MTX);
 
@@ -106,7 +170,6 @@ fn run<K: Kernel>(k: K) {
     // Dump all read-only mounts.
     let list = k.var(K::MOUNTLIST);
     let mut mp = unsafe { (*list.ptr()).first };
-    let mut items = 0u32;
     let mut ok = true;
 
     while !mp.is_null() {
@@ -115,19 +178,11 @@ fn run<K: Kernel>(k: K) {
 
         // Check if read-only.
         let lock = unsafe { MtxLock::new(k, (*mp).mtx()) };
-        let r = if unsafe { (*mp).flags() & K::MNT_RDONLY != 0 } {
-            unsafe { dump_mount(k, fd, mp, lock) }
+        ok = if unsafe { (*mp).flags() & K::MNT_RDONLY != 0 } {
+            unsafe { dump_mount(k, &mut dump, mp, lock, decrypt) }
         } else {
             drop(lock);
-            Some(0)
-        };
-
-        ok = match r {
-            Some(v) => {
-                items += v;
-                true
-            }
-            None => false,
+            true
         };
 
         // vfs_busy with MBF_MNTLSTLOCK will unlock before return so we need to re-acquire the lock.
@@ -151,7 +206,7 @@ fn run<K: Kernel>(k: K) {
     }
 
     // Write end entry.
-    if !write_dump(k, fd, &[DumpItem::End.into()]) || !write_dump(k, fd, &items.to_le_bytes()) {
+    if dump.finish().is_err() {
         return;
     }
 
@@ -165,15 +220,334 @@ fn run<K: Kernel>(k: K) {
     }
 
     // Notify the user.
-    notify(k, "Dump completed!");
+    if partial {
+        notify(k, "Dump completed with some items skipped!");
+    } else {
+        notify(k, "Dump completed!");
+    }
+}
+
+/// Collect system information from sysctl. Any sysctl that failed will be left empty.
+fn system_info<K: Kernel>(k: K) -> SystemInfo {
+    let mut time = [0 as c_long; 2];
+
+    unsafe { k.getnanotime(time.as_mut_ptr().cast()) };
+
+    let sdk_version = sysctl(k, c"kern.sdk_version")
+        .and_then(|v| v.try_into().ok())
+        .map(u32::from_le_bytes)
+        .unwrap_or(0);
+
+    // String values are NUL-terminated.
+    let str = |name: &CStr| {
+        let mut v = sysctl(k, name).unwrap_or_default();
+
+        if v.last() == Some(&0) {
+            v.pop();
+        }
+
+        v
+    };
+
+    SystemInfo {
+        sdk_version,
+        kernel: str(c"kern.version"),
+        model: str(c"hw.model"),
+        time: Timespec {
+            sec: time[0],
+            nsec: time[1] as u32,
+        },
+        dumper: concat!("firmware-dumper ", env!("CARGO_PKG_VERSION"))
+            .as_bytes()
+            .to_vec(),
+    }
+}
+
+fn sysctl<K: Kernel>(k: K, name: &CStr) -> Option<Vec<u8>> {
+    let td = K::Pcpu::curthread();
+    let call = |buf: *mut u8, len: &mut usize| unsafe {
+        k.kernel_sysctlbyname(
+            td,
+            name.as_ptr().cast_mut(),
+            buf.cast(),
+            len,
+            null_mut(),
+            0,
+            null_mut(),
+            0,
+        )
+    };
+
+    // Get the size first. The value may grow before we read it so retry with a larger buffer if
+    // it does not fit.
+    let mut len = 0;
+
+    if call(null_mut(), &mut len) != 0 {
+        return None;
+    }
+
+    for _ in 0..4 {
+        let mut buf = vec![0u8; len + 64];
+
+        len = buf.len();
+
+        match call(buf.as_mut_ptr(), &mut len) {
+            0 => {
+                buf.truncate(len);
+                return Some(buf);
+            }
+            ENOMEM => len = buf.len() * 2,
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+/// Write all `PT_LOAD` segments of the running kernel. The kernel was mapped with its ELF header
+/// at the beginning of the first segment so we can find the segments from its program headers.
+/// Returns `false` without writing anything if the headers are not valid.
+unsafe fn dump_kernel<K: Kernel>(k: K, dump: &mut DumpWriter<DumpFile<K>>) -> Result<bool, ()> {
+    let base = k.addr();
+    let segments = match kernel_segments(base) {
+        Some(v) => v,
+        None => {
+            notify(k, "Couldn't parse kernel ELF header");
+            return Ok(false);
+        }
+    };
+
+    let mut kernel = dump.kernel(base as u64)?;
+
+    for (addr, flags, len) in segments {
+        kernel.segment(addr as u64, flags, core::slice::from_raw_parts(addr, len))?;
+    }
+
+    kernel.finish()?;
+
+    Ok(true)
+}
+
+/// Returns address, flags and size of each `PT_LOAD` segment of the kernel mapped at `base`.
+unsafe fn kernel_segments(base: *const u8) -> Option<Vec<(*const u8, u32, usize)>> {
+    // The program headers must be in the same page as the ELF header since that is the only part
+    // we know is mapped.
+    let ehdr = core::slice::from_raw_parts(base, 64);
+    let phoff = u64::from_le_bytes(ehdr[32..40].try_into().unwrap());
+    let phentsize = u16::from_le_bytes(ehdr[54..56].try_into().unwrap());
+    let phnum = u16::from_le_bytes(ehdr[56..58].try_into().unwrap());
+    let end = u64::from(phnum)
+        .checked_mul(56)
+        .and_then(|v| v.checked_add(phoff))?;
+
+    if !ehdr.starts_with(b"\x7FELF") || phentsize != 56 || phoff < 64 || end > PAGE_SIZE {
+        return None;
+    }
+
+    let phdrs =
+        core::slice::from_raw_parts(base.add(phoff as usize).cast::<[u8; 56]>(), phnum.into());
+    let mut segments = Vec::new();
+    let mut first = None;
+    let mut next = 0;
+
+    for ph in phdrs {
+        let field = |off: usize| u64::from_le_bytes(ph[off..(off + 8)].try_into().unwrap());
+
+        // Skip non-PT_LOAD.
+        if ph[..4] != 1u32.to_le_bytes() {
+            continue;
+        }
+
+        // The addresses in the program headers does not include the ASLR slide. The segments must
+        // be sorted without overlapping and must not wrap around the address space.
+        let vaddr = field(16);
+        let first = *first.get_or_insert(vaddr);
+        let off = vaddr.checked_sub(first)?;
+        let len = field(40);
+
+        if off < next || field(32) > len {
+            return None;
+        }
+
+        next = off
+            .checked_add(len)
+            .filter(|&v| (base as u64).checked_add(v).is_some())?;
+
+        let flags = u32::from_le_bytes(ph[4..8].try_into().unwrap());
+
+        segments.push((base.add(off as usize), flags, len as usize));
+    }
+
+    (!segments.is_empty()).then_some(segments)
+}
+
+/// Read `sysent` table of the PS4 processes.
+unsafe fn syscalls<K: Kernel>(k: K) -> SyscallTable {
+    let sv = k.var(K::SELF_ORBIS_SYSVEC).ptr().cast::<SysEntVec>();
+    let len = (*sv).size as usize;
+    let names = (*sv).syscallnames;
+    let mut entries = Vec::with_capacity(len);
+
+    for i in 0..len {
+        let e = &*(*sv).table.add(i);
+        let name = if names.is_null() {
+            null()
+        } else {
+            *names.add(i)
+        };
+
+        entries.push(Syscall {
+            number: i as u32,
+            narg: e.narg as u32,
+            handler: e.call as u64,
+            name: if name.is_null() {
+                Vec::new()
+            } else {
+                CStr::from_ptr(name).to_bytes().to_vec()
+            },
+        });
+    }
+
+    SyscallTable { entries }
+}
+
+/// Write all entries in the kernel linker file list. The image of the kernel itself is not included
+/// since it was already written as the kernel item. Returns `false` if some images were skipped
+/// because they are not readable.
+unsafe fn dump_modules<K: Kernel>(k: K, dump: &mut DumpWriter<DumpFile<K>>) -> Result<bool, ()> {
+    let mut modules = dump.modules()?;
+    let sx = k.var(K::KLD_SX);
+    let list = k.var(K::LINKER_FILES);
+    let mut r = Ok(());
+    let mut complete = true;
+
+    // Lock linker file list.
+    k.sx_xlock(sx.ptr(), 0, c"".as_ptr(), 0);
+
+    let mut lf = *list.ptr().cast::<*const LinkerFile>();
+
+    while !lf.is_null() {
+        let name = match (*lf).filename {
+            v if v.is_null() => &[],
+            v => CStr::from_ptr(v).to_bytes(),
+        };
+
+        // Only the list is required so write the module without its image if we can't read it.
+        let addr = (*lf).address;
+        let size = (*lf).size;
+        let image = if name == b"kernel" {
+            None
+        } else if readable(k, addr, size) {
+            Some(core::slice::from_raw_parts(addr, size))
+        } else {
+            complete = false;
+            None
+        };
+
+        r = modules.module(name, addr as u64, size as u64, image);
+
+        if r.is_err() {
+            break;
+        }
+
+        lf = (*lf).next;
+    }
+
+    k.sx_xunlock(sx.ptr(), c"".as_ptr(), 0);
+
+    r?;
+    modules.finish()?;
+
+    Ok(complete)
+}
+
+/// Returns `true` if `len` bytes at `addr` is mapped kernel memory.
+unsafe fn readable<K: Kernel>(k: K, addr: *const u8, len: usize) -> bool {
+    let len = match c_int::try_from(len) {
+        Ok(v) if v != 0 => v,
+        _ => return false,
+    };
+
+    if (addr as usize) < KERNEL_START || (addr as usize).checked_add(len as usize).is_none() {
+        return false;
+    }
+
+    k.kernacc(addr.cast_mut().cast(), len, VM_PROT_READ.into()) != 0
+}
+
+/// Write content of a raw device. The device will be skipped if it does not exist or its size is
+/// not available. If reading fails in the middle the content will be shorter than the device size.
+/// Returns `false` if the device exists but its content was not fully written.
+unsafe fn dump_device<K: Kernel>(
+    k: K,
+    dump: &mut DumpWriter<DumpFile<K>>,
+    name: &CStr,
+) -> Result<bool, ()> {
+    // Open the device. We need to read through the file descriptor since the read on devfs vnode
+    // is redirected to the device driver by the file operations, not by VOP_READ.
+    let flags = OpenFlags::O_RDONLY;
+    let fd = match openat(k, AT_FDCWD, name.as_ptr(), UioSeg::Kernel, flags, 0) {
+        Ok(v) => v,
+        Err(_) => return Ok(true),
+    };
+
+    // Get device size.
+    let td = K::Pcpu::curthread();
+    let mut size = 0i64;
+
+    if k.kern_ioctl(
+        td,
+        fd.as_raw_fd(),
+        DIOCGMEDIASIZE,
+        (&mut size as *mut i64).cast(),
+    ) != 0
+    {
+        notify(k, "Couldn't get size of a raw device");
+        return Ok(false);
+    }
+
+    // Read the whole device. The chunk size must be a multiple of the sector size.
+    let mut dev = dump.raw_device(name.to_bytes(), size as u64)?;
+    let mut buf = vec![0u8; 0xF000];
+    let mut off = 0;
+
+    while off < size {
+        let mut vec = IoVec {
+            ptr: buf.as_mut_ptr(),
+            len: buf.len(),
+        };
+
+        let mut io = Uio::read(&mut vec, off.try_into().unwrap(), td).unwrap();
+        let errno = k.kern_preadv(td, fd.as_raw_fd(), &mut io, off);
+
+        if errno != 0 {
+            notify(k, "Couldn't read a raw device");
+            break;
+        }
+
+        // Check if EOF.
+        let len = buf.len() - usize::try_from(io.remaining()).unwrap();
+
+        if len == 0 {
+            break;
+        }
+
+        dev.write(&buf[..len])?;
+        off += len as i64;
+    }
+
+    dev.finish()?;
+
+    Ok(off == size)
 }
 
 unsafe fn dump_mount<K: Kernel>(
     k: K,
-    fd: c_int,
+    dump: &mut DumpWriter<DumpFile<K>>,
     mp: *mut K::Mount,
     lock: MtxLock<K>,
-) -> Option<u32> {
+    decrypt: bool,
+) -> bool {
     drop(lock);
 
     // Check filesystem type.
@@ -181,98 +555,178 @@ unsafe fn dump_mount<K: Kernel>(
     let fs = CStr::from_ptr((*fs).name()).to_bytes();
 
     if !matches!(fs, b"exfatfs" | b"ufs") {
-        return Some(0);
+        return true;
     }
 
-    // Write entry type.
-    if !write_dump(k, fd, &[DumpItem::Ps4Part.into()]) {
-        return None;
+    // Write partition header.
+    let stats = (*mp).stats();
+    let dev = CStr::from_ptr((*stats).mounted_from()).to_bytes();
+    let mut part = match dump.ps4_part(fs, dev) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+
+    // Dump all vnodes.
+    if walk(&mut part, &mut Vfs { k, mp, decrypt }, FileBlock::Sparse).is_err() {
+        return false;
     }
 
-    // Write entry version.
-    if !write_dump(k, fd, &[0]) {
-        return None;
+    // Write end entry.
+    part.finish().is_ok()
+}
+
+/// Implementation of [`FsSource`] for a mounted filesystem.
+struct Vfs<K: Kernel> {
+    k: K,
+    mp: *mut K::Mount,
+    /// `true` to dump decrypted segments of SELF files.
+    decrypt: bool,
+}
+
+impl<K: Kernel> FsSource for Vfs<K> {
+    type Node = OwnedVnode<K>;
+    type File = VfsFile<K>;
+    type Error = ();
+
+    fn root(&mut self) -> Result<Self::Node, Self::Error> {
+        match unsafe { (*self.mp).ops().root(self.mp, K::LK_SHARED) } {
+            Ok(vnode) => Ok(OwnedVnode { k: self.k, vnode }),
+            Err(_) => {
+                notify(self.k, "Couldn't get root vnode");
+                Err(())
+            }
+        }
     }
 
-    // Write filesystem type.
-    if !write_dump(k, fd, &fs.len().to_le_bytes()) || !write_dump(k, fd, fs) {
-        return None;
+    fn stat(&mut self, node: &Self::Node) -> Result<NodeInfo, Self::Error> {
+        let k = self.k;
+        let va = unsafe { get_attr(k, node.vnode).ok_or(())? };
+        let ty = unsafe { (*node.vnode).ty() };
+        let ty = if ty == K::VDIR {
+            NodeType::Directory
+        } else if ty == K::VREG {
+            NodeType::File
+        } else if ty == K::VLNK {
+            NodeType::Symlink
+        } else if ty == K::VCHR {
+            NodeType::CharDevice
+        } else if ty == K::VBLK {
+            NodeType::BlockDevice
+        } else if ty == K::VFIFO {
+            NodeType::Fifo
+        } else if ty == K::VSOCK {
+            NodeType::Socket
+        } else {
+            NodeType::Other
+        };
+
+        Ok(NodeInfo {
+            ty,
+            meta: va.metadata(),
+            nlink: va.nlink as u64,
+            id: va.fileid as u64,
+            rdev: va.rdev.into(),
+        })
     }
 
-    // Write mounted from.
-    let stats = (*mp).stats();
-    let dev = CStr::from_ptr((*stats).mounted_from()).to_bytes();
+    fn list(
+        &mut self,
+        dir: &Self::Node,
+        out: &mut Vec<(Vec<u8>, Self::Node)>,
+    ) -> Result<(), Self::Error> {
+        unsafe { list_files(self.k, dir.vnode, out) }
+    }
 
-    if !write_dump(k, fd, &dev.len().to_le_bytes()) || !write_dump(k, fd, dev) {
-        return None;
+    fn open(&mut self, node: &Self::Node) -> Result<Self::File, Self::Error> {
+        Ok(VfsFile {
+            k: self.k,
+            vnode: node.vnode,
+            mapped: Vec::new(),
+        })
     }
 
-    // Get root vnode.
-    let vp = match (*mp).ops().root(mp, K::LK_SHARED) {
-        Ok(v) => v,
-        Err(_) => {
-            notify(k, "Couldn't get root vnode");
-            return None;
+    fn read(
+        &mut self,
+        file: &mut Self::File,
+        off: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        unsafe { read_file(self.k, file.vnode, off, buf) }
+    }
+
+    fn decrypt(&mut self, file: &mut Self::File) -> Result<Option<DecryptedSelf>, Self::Error> {
+        if !self.decrypt {
+            return Ok(None);
         }
-    };
 
-    // Dump all vnodes.
-    let mut items = 1; // Current partition.
-    let mut pending = VecDeque::from([PendingVnode {
-        k,
-        vnode: vp,
-        path: Vec::new(),
-    }]);
-
-    while let Some(p) = pending.pop_front() {
-        // Map type.
-        let ty = (*p.vnode).ty();
-        let ty = if ty == K::VDIR {
-            PartItem::Directory
-        } else if ty == K::VREG {
-            PartItem::File
-        } else {
-            let m = format!("Unknown vnode {ty}");
-            notify(k, &m);
-            return None;
+        let (decrypted, segments) = match unsafe { read_self(self.k, file.vnode)? } {
+            Some(v) => v,
+            None => return Ok(None),
         };
 
-        // Write type and path.
-        if !write_dump(k, fd, &[ty.into()]) {
-            return None;
+        // Map all segments before the SELF is written so we can still dump it as-is if any of
+        // them can't be decrypted.
+        for s in segments {
+            if unsafe { !file.map(&s) } {
+                file.unmap_all();
+                return Ok(None);
+            }
         }
 
-        if !write_dump(k, fd, &p.path.len().to_le_bytes()) || !write_dump(k, fd, &p.path) {
-            return None;
+        Ok(Some(decrypted))
+    }
+
+    fn read_segment(
+        &mut self,
+        file: &mut Self::File,
+        index: u16,
+        off: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let i = file.mapped.iter().position(|m| m.0 == index).ok_or(())?;
+        let (_, addr, size) = file.mapped[i];
+
+        // Release the segment once it was fully read.
+        let off = off as usize;
+
+        if off >= size {
+            file.unmap(i);
+            return Ok(0);
         }
 
-        items += 1;
+        // Copy decrypted data.
+        let len = min(buf.len(), size - off);
+        let src = unsafe { core::slice::from_raw_parts((addr + off) as *const u8, len) };
 
-        // Dump.
-        let ok = match ty {
-            PartItem::End => unreachable_unchecked(),
-            PartItem::Directory => list_files(k, p, &mut pending),
-            PartItem::File => dump_file(k, p, fd),
-        };
+        buf[..len].copy_from_slice(src);
 
-        if !ok {
-            return None;
-        }
+        Ok(len)
     }
 
-    // Write end entry.
-    if write_dump(k, fd, &[PartItem::End.into()]) {
-        Some(items)
-    } else {
-        None
+    fn read_link(&mut self, node: &Self::Node) -> Result<Vec<u8>, Self::Error> {
+        unsafe { read_link(self.k, node.vnode) }
     }
 }
 
+unsafe fn get_attr<K: Kernel>(k: K, vp: *mut K::Vnode) -> Option<Vattr> {
+    let td = K::Pcpu::curthread();
+    let mut va = MaybeUninit::<Vattr>::zeroed();
+    let mut args = VopGetAttr::new(k, vp, va.as_mut_ptr().cast(), (*td).cred());
+    let errno = k.vop_getattr((*vp).ops(), &mut args);
+
+    if errno != 0 {
+        notify(k, "Couldn't get file attributes");
+        return None;
+    }
+
+    Some(va.assume_init())
+}
+
 unsafe fn list_files<K: Kernel>(
     k: K,
-    p: PendingVnode<K>,
-    pending: &mut VecDeque<PendingVnode<K>>,
-) -> bool {
+    vp: *mut K::Vnode,
+    out: &mut Vec<(Vec<u8>, OwnedVnode<K>)>,
+) -> Result<(), ()> {
     let td = K::Pcpu::curthread();
     let mut off = 0;
 
@@ -289,7 +743,7 @@ unsafe fn list_files<K: Kernel>(
         let mut eof = MaybeUninit::uninit();
         let mut args = VopReadDir::new(
             k,
-            p.vnode,
+            vp,
             &mut io,
             (*td).cred(),
             eof.as_mut_ptr(),
@@ -298,11 +752,11 @@ unsafe fn list_files<K: Kernel>(
         );
 
         // Read entry.
-        let errno = k.vop_readdir((*p.vnode).ops(), &mut args);
+        let errno = k.vop_readdir((*vp).ops(), &mut args);
 
         if errno != 0 {
             notify(k, "Couldn't read directory entry");
-            return false;
+            return Err(());
         }
 
         off = io.offset().try_into().unwrap();
@@ -326,30 +780,28 @@ unsafe fn list_files<K: Kernel>(
                 continue;
             }
 
-            // Build path.
-            let mut path = p.path.clone();
-
-            path.push(b'/');
-            path.extend_from_slice(name);
+            let name = name.to_vec();
 
             // Lookup.
             let mut child = MaybeUninit::uninit();
-            let name = (*ent).name.as_mut_ptr();
-            let mut cn = ComponentName::new(k, K::LOOKUP, K::LK_SHARED, name, td);
-            let mut args = VopLookup::new(k, p.vnode, child.as_mut_ptr(), &mut cn);
-            let errno = k.vop_lookup((*p.vnode).ops(), &mut args);
+            let cn = (*ent).name.as_mut_ptr();
+            let mut cn = ComponentName::new(k, K::LOOKUP, K::LK_SHARED, cn, td);
+            let mut args = VopLookup::new(k, vp, child.as_mut_ptr(), &mut cn);
+            let errno = k.vop_lookup((*vp).ops(), &mut args);
 
             if errno != 0 {
                 notify(k, "Couldn't lookup child vnode");
-                return false;
+                return Err(());
             }
 
             // Keep vnode.
-            pending.push_back(PendingVnode {
-                k,
-                vnode: child.assume_init(),
-                path,
-            });
+            out.push((
+                name,
+                OwnedVnode {
+                    k,
+                    vnode: child.assume_init(),
+                },
+            ));
         }
 
         // Stop if no more entries.
@@ -358,70 +810,114 @@ unsafe fn list_files<K: Kernel>(
         }
     }
 
-    true
+    Ok(())
 }
 
-unsafe fn dump_file<K: Kernel>(k: K, p: PendingVnode<K>, fd: c_int) -> bool {
-    // Write block type.
-    if !write_dump(k, fd, &[0]) {
-        return false;
+/// Reads ELF header and program headers of `vp` if it is a SELF. Also returns the segments to map.
+unsafe fn read_self<K: Kernel>(
+    k: K,
+    vp: *mut K::Vnode,
+) -> Result<Option<(DecryptedSelf, Vec<SelfMapping>)>, ()> {
+    // Read until we have all headers. Most SELF have their headers within the first page.
+    let mut buf = vec![0u8; PAGE_SIZE as usize];
+    let file = loop {
+        let len = read_file(k, vp, 0, &mut buf)?;
+
+        match SelfFile::parse(&buf[..len]) {
+            Ok(v) => break v,
+            Err(SelfError::Truncated(v)) if len == buf.len() && v <= SELF_HEADERS_MAX => {
+                buf.resize(v, 0)
+            }
+            Err(_) => return Ok(None),
+        }
+    };
+
+    // We only support program headers that are right after the ELF header.
+    let elf = file.elf_offset();
+    let num = file.programs.len();
+
+    if file.elf.phentsize != 56 || file.elf.phoff != 64 {
+        return Ok(None);
     }
 
-    // Dump data.
-    let td = K::Pcpu::curthread();
-    let mut buf = vec![0; 0xFFFF]; // Maximum block size.
-    let mut off = 0;
+    let headers = buf[elf..(elf + 64 + 56 * num)].to_vec();
 
-    loop {
-        // Setup output buffer.
-        let mut vec = IoVec {
-            ptr: buf.as_mut_ptr(),
-            len: buf.len(),
-        };
+    // Get program headers that have data.
+    let mut segments = Vec::<SelfMapping>::new();
 
-        // Read.
-        let mut io = Uio::read(&mut vec, off, td).unwrap();
-        let mut args = VopRead::new(k, p.vnode, &mut io, 0, (*td).cred());
-        let errno = k.vop_read((*p.vnode).ops(), &mut args);
+    for s in &file.segments {
+        let size = match file.program(s) {
+            Some(p) if p.filesz != 0 => p.filesz,
+            _ => continue,
+        };
 
-        if errno != 0 {
-            notify(k, "Couldn't read a file");
-            return false;
+        if !segments.iter().any(|m| m.index == s.id()) {
+            segments.push(SelfMapping {
+                index: s.id(),
+                size,
+                encrypted: s.is_encrypted().then_some(s.offset),
+            });
         }
+    }
 
-        off = io.offset().try_into().unwrap();
+    segments.sort_unstable_by_key(|m| file.programs[usize::from(m.index)].offset);
 
-        // Check if EOF.
-        let len = buf.len() - usize::try_from(io.remaining()).unwrap();
+    let decrypted = DecryptedSelf {
+        headers,
+        segments: segments.iter().map(|m| m.index).collect(),
+    };
 
-        if len == 0 {
-            break;
-        }
+    Ok(Some((decrypted, segments)))
+}
 
-        // Write dump.
-        let buf = &buf[..len];
-        let len: u16 = len.try_into().unwrap();
+unsafe fn read_link<K: Kernel>(k: K, vp: *mut K::Vnode) -> Result<Vec<u8>, ()> {
+    // Setup output buffer.
+    let td = K::Pcpu::curthread();
+    let mut buf = vec![0; 1024]; // MAXPATHLEN.
+    let mut vec = IoVec {
+        ptr: buf.as_mut_ptr(),
+        len: buf.len(),
+    };
 
-        if !write_dump(k, fd, &len.to_le_bytes()) || !write_dump(k, fd, buf) {
-            return false;
-        }
+    // Read.
+    let mut io = Uio::read(&mut vec, 0, td).unwrap();
+    let mut args = VopReadLink::new(k, vp, &mut io, (*td).cred());
+    let errno = k.vop_readlink((*vp).ops(), &mut args);
+
+    if errno != 0 {
+        notify(k, "Couldn't read a symbolic link");
+        return Err(());
     }
 
-    // Write empty block.
-    write_dump(k, fd, &0u16.to_le_bytes())
+    buf.truncate(buf.len() - usize::try_from(io.remaining()).unwrap());
+
+    Ok(buf)
 }
 
-#[inline(never)]
-fn write_dump<K: Kernel>(k: K, fd: c_int, data: &[u8]) -> bool {
+unsafe fn read_file<K: Kernel>(
+    k: K,
+    vp: *mut K::Vnode,
+    off: u64,
+    buf: &mut [u8],
+) -> Result<usize, ()> {
+    // Setup output buffer.
     let td = K::Pcpu::curthread();
+    let mut vec = IoVec {
+        ptr: buf.as_mut_ptr(),
+        len: buf.len(),
+    };
 
-    match unsafe { write_all(k, fd, data, td) } {
-        Ok(_) => true,
-        Err(_) => {
-            notify(k, "Couldn't write dump file");
-            false
-        }
+    // Read.
+    let mut io = Uio::read(&mut vec, off.try_into().unwrap(), td).unwrap();
+    let mut args = VopRead::new(k, vp, &mut io, 0, (*td).cred());
+    let errno = k.vop_read((*vp).ops(), &mut args);
+
+    if errno != 0 {
+        notify(k, "Couldn't read a file");
+        return Err(());
     }
+
+    Ok(buf.len() - usize::try_from(io.remaining()).unwrap())
 }
 
 #[inline(never)]
@@ -468,18 +964,233 @@ fn panic(_: &PanicInfo) -> ! {
     unsafe { unreachable_unchecked() };
 }
 
-struct PendingVnode<K: Kernel> {
+/// Implementation of [`DumpSink`] for the dump file.
+struct DumpFile<K: Kernel> {
+    k: K,
+    fd: c_int,
+}
+
+impl<K: Kernel> DumpSink for DumpFile<K> {
+    type Error = ();
+
+    #[inline(never)]
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        let td = K::Pcpu::curthread();
+
+        match unsafe { write_all(self.k, self.fd, data, td) } {
+            Ok(_) => Ok(()),
+            Err(_) => {
+                notify(self.k, "Couldn't write dump file");
+                Err(())
+            }
+        }
+    }
+}
+
+/// Vnode that will be released with `vput` when dropped.
+struct OwnedVnode<K: Kernel> {
     k: K,
     vnode: *mut K::Vnode,
-    path: Vec<u8>,
 }
 
-impl<K: Kernel> Drop for PendingVnode<K> {
+impl<K: Kernel> Drop for OwnedVnode<K> {
     fn drop(&mut self) {
         unsafe { self.k.vput(self.vnode) };
     }
 }
 
+/// Implementation of [`FsSource::File`] for [`Vfs`].
+struct VfsFile<K: Kernel> {
+    k: K,
+    vnode: *mut K::Vnode,
+    /// Index, address and size of each decrypted segment that is mapped into the kernel.
+    mapped: Vec<(u16, usize, usize)>,
+}
+
+impl<K: Kernel> VfsFile<K> {
+    /// Maps decrypted segment into the kernel. The SELF pager will decrypt the segment when the
+    /// file is mapped with the segment index on the upper 32-bits of the offset. Returns `false`
+    /// if the segment can't be mapped or was not decrypted.
+    unsafe fn map(&mut self, seg: &SelfMapping) -> bool {
+        let map = *self.k.var(K::KERNEL_MAP).ptr();
+        let index = seg.index;
+        let size = seg.size as usize;
+        let mut addr = 0;
+        let errno = self.k.vm_mmap(
+            map,
+            &mut addr,
+            size,
+            VM_PROT_READ,
+            VM_PROT_READ,
+            MAP_SHARED,
+            OBJT_VNODE,
+            self.vnode.cast(),
+            u64::from(index) << 32,
+        );
+
+        if errno != 0 {
+            return false;
+        }
+
+        self.mapped.push((index, addr, size));
+
+        // The mapped data must not be the same as the encrypted data.
+        let off = match seg.encrypted {
+            Some(v) => v,
+            None => return true,
+        };
+
+        let mut buf = [0u8; 64];
+        let buf = &mut buf[..min(64, size)];
+        let data = core::slice::from_raw_parts(addr as *const u8, buf.len());
+
+        match read_file(self.k, self.vnode, off, buf) {
+            Ok(n) => buf[..n] != data[..n],
+            Err(_) => false,
+        }
+    }
+
+    fn unmap(&mut self, i: usize) {
+        let (_, addr, size) = self.mapped.swap_remove(i);
+        let map = unsafe { *self.k.var(K::KERNEL_MAP).ptr() };
+        let end = (addr + size).next_multiple_of(PAGE_SIZE as usize);
+
+        unsafe { self.k.vm_map_remove(map, addr, end) };
+    }
+
+    fn unmap_all(&mut self) {
+        while !self.mapped.is_empty() {
+            self.unmap(self.mapped.len() - 1);
+        }
+    }
+}
+
+impl<K: Kernel> Drop for VfsFile<K> {
+    fn drop(&mut self) {
+        self.unmap_all();
+    }
+}
+
+/// Decrypted segment of a SELF to map with [`VfsFile::map()`].
+struct SelfMapping {
+    /// Index of the program header.
+    index: u16,
+    size: u64,
+    /// File offset of the segment data if it is encrypted.
+    encrypted: Option<u64>,
+}
+
+/// Implementation of `struct vattr` on the PS4.
+#[repr(C)]
+struct Vattr {
+    ty: c_int,
+    mode: c_ushort,
+    nlink: c_short,
+    uid: c_uint,
+    gid: c_uint,
+    fsid: c_uint,
+    fileid: c_long,
+    size: u64,
+    blocksize: c_long,
+    atime: [c_long; 2],
+    mtime: [c_long; 2],
+    ctime: [c_long; 2],
+    birthtime: [c_long; 2],
+    gen: c_ulong,
+    flags: c_ulong,
+    rdev: c_uint,
+    bytes: u64,
+    filerev: u64,
+    vaflags: c_uint,
+    spare: c_long,
+}
+
+impl Vattr {
+    fn metadata(&self) -> Metadata {
+        let time = |t: [c_long; 2]| Timespec {
+            sec: t[0],
+            nsec: t[1] as u32,
+        };
+
+        Metadata {
+            mode: self.mode,
+            uid: self.uid,
+            gid: self.gid,
+            flags: self.flags,
+            size: self.size,
+            atime: time(self.atime),
+            mtime: time(self.mtime),
+            ctime: time(self.ctime),
+            birthtime: time(self.birthtime),
+        }
+    }
+}
+
+/// Leading fields of `struct linker_file` on the PS4 up to `size`.
+#[repr(C)]
+struct LinkerFile {
+    ops: usize,
+    refs: c_int,
+    userrefs: c_int,
+    flags: c_int,
+    next: *const LinkerFile,
+    prev: *const *const LinkerFile,
+    filename: *const c_char,
+    pathname: *const c_char,
+    id: c_int,
+    address: *const u8,
+    size: usize,
+}
+
+/// Implementation of `struct sysent` on the PS4.
+#[repr(C)]
+struct SysEnt {
+    narg: c_int,
+    call: usize,
+    auevent: c_ushort,
+    systrace_args_func: usize,
+    entry: c_uint,
+    ret: c_uint,
+    flags: c_uint,
+    thrcnt: c_uint,
+}
+
+/// Leading fields of `struct sysentvec` on the PS4 up to `sv_syscallnames`.
+#[repr(C)]
+struct SysEntVec {
+    size: c_int,
+    table: *const SysEnt,
+    mask: c_uint,
+    sigsize: c_int,
+    sigtbl: *const c_int,
+    errsize: c_int,
+    errtbl: *const c_int,
+    transtrap: usize,
+    fixup: usize,
+    sendsig: usize,
+    sigcode: *const u8,
+    szsigcode: *const c_int,
+    prepsyscall: usize,
+    name: *const c_char,
+    coredump: usize,
+    imgact_try: usize,
+    minsigstksz: c_int,
+    pagesize: c_int,
+    minuser: usize,
+    maxuser: usize,
+    usrstack: usize,
+    psstrings: usize,
+    stackprot: c_int,
+    copyout_strings: usize,
+    setregs: usize,
+    fixlimit: usize,
+    maxssiz: *const c_ulong,
+    flags: c_uint,
+    set_syscall_retval: usize,
+    fetch_syscall_args: usize,
+    syscallnames: *const *const c_char,
+}
+
 /// By OSM-Made.
 #[repr(C)]
 struct OrbisNotificationRequest {
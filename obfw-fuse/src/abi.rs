@@ -0,0 +1,212 @@
+//! Linux FUSE kernel ABI. See `include/uapi/linux/fuse.h` in the kernel source.
+use std::mem::size_of;
+
+pub const KERNEL_VERSION: u32 = 7;
+pub const KERNEL_MINOR_VERSION: u32 = 31;
+
+/// Minimum size of the buffer to read a request.
+pub const MIN_READ_BUFFER: usize = 8192;
+
+pub const FUSE_LOOKUP: u32 = 1;
+pub const FUSE_FORGET: u32 = 2;
+pub const FUSE_GETATTR: u32 = 3;
+pub const FUSE_READLINK: u32 = 5;
+pub const FUSE_OPEN: u32 = 14;
+pub const FUSE_READ: u32 = 15;
+pub const FUSE_STATFS: u32 = 17;
+pub const FUSE_RELEASE: u32 = 18;
+pub const FUSE_INIT: u32 = 26;
+pub const FUSE_OPENDIR: u32 = 27;
+pub const FUSE_READDIR: u32 = 28;
+pub const FUSE_RELEASEDIR: u32 = 29;
+pub const FUSE_INTERRUPT: u32 = 36;
+pub const FUSE_DESTROY: u32 = 38;
+pub const FUSE_BATCH_FORGET: u32 = 42;
+
+/// Reply to `FUSE_OPEN` to allow the kernel to cache the file content.
+pub const FOPEN_KEEP_CACHE: u32 = 1 << 1;
+
+pub const ROOT_ID: u64 = 1;
+
+/// Types that can be converted from and to the raw bytes.
+///
+/// # Safety
+/// The type must be `#[repr(C)]` without any padding and all bit patterns must be valid.
+pub unsafe trait Pod: Copy {
+    fn read(data: &[u8]) -> Option<Self> {
+        if data.len() < size_of::<Self>() {
+            return None;
+        }
+
+        Some(unsafe { data.as_ptr().cast::<Self>().read_unaligned() })
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct InHeader {
+    pub len: u32,
+    pub opcode: u32,
+    pub unique: u64,
+    pub nodeid: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: u32,
+    pub total_extlen: u16,
+    pub padding: u16,
+}
+
+unsafe impl Pod for InHeader {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct OutHeader {
+    pub len: u32,
+    pub error: i32,
+    pub unique: u64,
+}
+
+unsafe impl Pod for OutHeader {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct InitIn {
+    pub major: u32,
+    pub minor: u32,
+    pub max_readahead: u32,
+    pub flags: u32,
+}
+
+unsafe impl Pod for InitIn {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct InitOut {
+    pub major: u32,
+    pub minor: u32,
+    pub max_readahead: u32,
+    pub flags: u32,
+    pub max_background: u16,
+    pub congestion_threshold: u16,
+    pub max_write: u32,
+    pub time_gran: u32,
+    pub max_pages: u16,
+    pub map_alignment: u16,
+    pub flags2: u32,
+    pub unused: [u32; 7],
+}
+
+unsafe impl Pod for InitOut {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Attr {
+    pub ino: u64,
+    pub size: u64,
+    pub blocks: u64,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+    pub atimensec: u32,
+    pub mtimensec: u32,
+    pub ctimensec: u32,
+    pub mode: u32,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub rdev: u32,
+    pub blksize: u32,
+    pub flags: u32,
+}
+
+unsafe impl Pod for Attr {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct EntryOut {
+    pub nodeid: u64,
+    pub generation: u64,
+    pub entry_valid: u64,
+    pub attr_valid: u64,
+    pub entry_valid_nsec: u32,
+    pub attr_valid_nsec: u32,
+    pub attr: Attr,
+}
+
+unsafe impl Pod for EntryOut {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AttrOut {
+    pub attr_valid: u64,
+    pub attr_valid_nsec: u32,
+    pub dummy: u32,
+    pub attr: Attr,
+}
+
+unsafe impl Pod for AttrOut {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct OpenIn {
+    pub flags: u32,
+    pub open_flags: u32,
+}
+
+unsafe impl Pod for OpenIn {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct OpenOut {
+    pub fh: u64,
+    pub open_flags: u32,
+    pub padding: u32,
+}
+
+unsafe impl Pod for OpenOut {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ReadIn {
+    pub fh: u64,
+    pub offset: u64,
+    pub size: u32,
+    pub read_flags: u32,
+    pub lock_owner: u64,
+    pub flags: u32,
+    pub padding: u32,
+}
+
+unsafe impl Pod for ReadIn {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Dirent {
+    pub ino: u64,
+    pub off: u64,
+    pub namelen: u32,
+    pub ty: u32,
+}
+
+unsafe impl Pod for Dirent {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct StatfsOut {
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+    pub files: u64,
+    pub ffree: u64,
+    pub bsize: u32,
+    pub namelen: u32,
+    pub frsize: u32,
+    pub padding: u32,
+    pub spare: [u32; 6],
+}
+
+unsafe impl Pod for StatfsOut {}
@@ -0,0 +1,376 @@
+use crate::abi::{Attr, Dirent, StatfsOut, ROOT_ID};
+use obfw::ps4::{part_name, BlockCache, IndexedItem, PartItem};
+use obfw::DumpIndex;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Read-only view of a dump with inode numbers.
+///
+/// Inode 1 is a directory that contains each partition as a directory named after its device.
+/// Other inodes are assigned in the same order as the items in the dump so they never change while
+/// mounted. A hard link shares the inode with its target.
+pub struct Filesystem<F> {
+    dump: DumpIndex<F>,
+    nodes: Vec<Node>,
+    entries: HashMap<(u64, Vec<u8>), u64>,
+    /// Number of handles and the last decompressed block of each opened file.
+    files: HashMap<u64, (u32, BlockCache)>,
+    blocks: u64,
+}
+
+impl<F: Read + Seek> Filesystem<F> {
+    pub fn new(dump: DumpIndex<F>) -> Self {
+        let mut fs = Self {
+            dump,
+            nodes: vec![Node::new(ROOT_ID, PartItem::Directory, None)],
+            entries: HashMap::new(),
+            files: HashMap::new(),
+            blocks: 0,
+        };
+
+        for i in 0..fs.dump.parts().len() {
+            fs.add_part(i);
+        }
+
+        fs
+    }
+
+    pub fn lookup(&self, parent: u64, name: &[u8]) -> Result<(u64, Attr), i32> {
+        self.dir(parent)?;
+
+        match self.entries.get(&(parent, name.to_vec())) {
+            Some(&ino) => Ok((ino, self.attr(ino)?)),
+            None => Err(libc::ENOENT),
+        }
+    }
+
+    pub fn attr(&self, ino: u64) -> Result<Attr, i32> {
+        let node = self.node(ino)?;
+        let item = self.item(node);
+        let (ty, perm) = match node.ty {
+            PartItem::Directory => (libc::S_IFDIR, 0o555),
+            PartItem::File => (libc::S_IFREG, 0o444),
+            PartItem::Symlink => (libc::S_IFLNK, 0o777),
+            PartItem::CharDevice => (libc::S_IFCHR, 0o444),
+            PartItem::BlockDevice => (libc::S_IFBLK, 0o444),
+            PartItem::Fifo => (libc::S_IFIFO, 0o444),
+            PartItem::Socket => (libc::S_IFSOCK, 0o444),
+            PartItem::End | PartItem::HardLink => unreachable!(),
+        };
+
+        // Use the default permissions if the dump was created by an old version.
+        let mut attr = Attr {
+            ino,
+            mode: ty | perm,
+            nlink: node.nlink,
+            blksize: 512,
+            ..Default::default()
+        };
+
+        if let Some(item) = item {
+            attr.size = match item.target() {
+                Some(v) => v.len() as u64,
+                None => item.len(),
+            };
+
+            attr.blocks = item.len().div_ceil(512);
+            attr.rdev = item.rdev().map(linux_dev).unwrap_or(0);
+        }
+
+        if let Some(m) = item.and_then(|v| v.metadata()) {
+            attr.mode = ty | u32::from(m.mode & 0o7777);
+            attr.uid = m.uid;
+            attr.gid = m.gid;
+            attr.atime = m.atime.sec as u64;
+            attr.atimensec = m.atime.nsec;
+            attr.mtime = m.mtime.sec as u64;
+            attr.mtimensec = m.mtime.nsec;
+            attr.ctime = m.ctime.sec as u64;
+            attr.ctimensec = m.ctime.nsec;
+        }
+
+        Ok(attr)
+    }
+
+    pub fn read_link(&self, ino: u64) -> Result<&[u8], i32> {
+        let node = self.node(ino)?;
+
+        match self.item(node) {
+            Some(v) if node.ty == PartItem::Symlink => Ok(v.target().unwrap()),
+            _ => Err(libc::EINVAL),
+        }
+    }
+
+    pub fn open(&mut self, ino: u64) -> Result<(), i32> {
+        match self.node(ino)?.ty {
+            PartItem::File => {
+                self.files.entry(ino).or_default().0 += 1;
+                Ok(())
+            }
+            PartItem::Directory => Err(libc::EISDIR),
+            _ => Err(libc::EACCES),
+        }
+    }
+
+    pub fn release(&mut self, ino: u64) {
+        if let Some(v) = self.files.get_mut(&ino) {
+            v.0 -= 1;
+
+            if v.0 == 0 {
+                self.files.remove(&ino);
+            }
+        }
+    }
+
+    pub fn read(&mut self, ino: u64, off: u64, buf: &mut [u8]) -> Result<usize, i32> {
+        let node = self.node(ino)?;
+        let (part, path) = match (node.ty, node.item) {
+            (PartItem::File, Some((p, i))) => {
+                let part = &self.dump.parts()[p];
+
+                (part.dev().to_vec(), part.items()[i].path().to_vec())
+            }
+            _ => return Err(libc::EBADF),
+        };
+
+        // Reuse the decompressed block from the previous read so a sequential read does not
+        // decompress the same block again.
+        let cache = self.files.get_mut(&ino).ok_or(libc::EBADF)?;
+        let mut file = self
+            .dump
+            .open_file(&part, &path)
+            .map_err(|e| io_error(&path, e))?
+            .with_cache(std::mem::take(&mut cache.1));
+
+        file.seek(SeekFrom::Start(off))
+            .map_err(|e| io_error(&path, e))?;
+
+        // Fill the whole buffer unless EOF since the kernel treats a short read as EOF.
+        let mut len = 0;
+        let r = loop {
+            if len == buf.len() {
+                break Ok(len);
+            }
+
+            match file.read(&mut buf[len..]) {
+                Ok(0) => break Ok(len),
+                Ok(n) => len += n,
+                Err(e) => break Err(io_error(&path, e)),
+            }
+        };
+
+        cache.1 = file.into_cache();
+
+        r
+    }
+
+    pub fn open_dir(&self, ino: u64) -> Result<(), i32> {
+        self.dir(ino).map(|_| ())
+    }
+
+    /// Append entries of `ino` starting at `off` to `out` until it reach `size`.
+    pub fn read_dir(&self, ino: u64, off: u64, size: usize, out: &mut Vec<u8>) -> Result<(), i32> {
+        let node = self.dir(ino)?;
+        let dots = [(&b"."[..], ino), (&b".."[..], node.parent)];
+        let children = node.children.iter().map(|(n, i)| (n.as_slice(), *i));
+        let start = out.len();
+
+        for (i, (name, ino)) in dots
+            .into_iter()
+            .chain(children)
+            .enumerate()
+            .skip(off as usize)
+        {
+            let ent = Dirent {
+                ino,
+                off: i as u64 + 1,
+                namelen: name.len() as u32,
+                ty: self.attr(ino)?.mode >> 12,
+            };
+
+            // Each entry is aligned to 8 bytes.
+            let len = (size_of::<Dirent>() + name.len()).next_multiple_of(8);
+
+            if out.len() - start + len > size {
+                break;
+            }
+
+            out.extend_from_slice(crate::abi::Pod::as_bytes(&ent));
+            out.extend_from_slice(name);
+            out.resize(out.len().next_multiple_of(8), 0);
+        }
+
+        Ok(())
+    }
+
+    pub fn statfs(&self) -> StatfsOut {
+        StatfsOut {
+            blocks: self.blocks,
+            files: self.nodes.len() as u64,
+            bsize: 512,
+            namelen: 255,
+            frsize: 512,
+            ..Default::default()
+        }
+    }
+
+    fn add_part(&mut self, index: usize) {
+        let part = &self.dump.parts()[index];
+        let name = match part_name(part.dev()) {
+            Some(v) if !self.entries.contains_key(&(ROOT_ID, v.to_vec())) => v.to_vec(),
+            _ => {
+                eprintln!(
+                    "Skipping partition {} due to invalid or duplicated name",
+                    part.dev().escape_ascii()
+                );
+                return;
+            }
+        };
+
+        let nodes = &mut self.nodes;
+        let entries = &mut self.entries;
+        let root = add_node(nodes, entries, ROOT_ID, name, PartItem::Directory, None);
+        let mut paths = HashMap::from([(&b""[..], root)]);
+
+        for (i, item) in part.items().iter().enumerate() {
+            let path = item.path();
+
+            // The partition root does not have a name.
+            if path.is_empty() {
+                nodes[(root - 1) as usize].item = Some((index, i));
+                continue;
+            }
+
+            // Items are written breadth-first so the parent always come before its children.
+            let (dir, name) = match path.iter().rposition(|&b| b == b'/') {
+                Some(i) => (&path[..i], &path[(i + 1)..]),
+                None => (&path[..0], path),
+            };
+
+            let parent = match paths.get(dir) {
+                Some(&v) if nodes[(v - 1) as usize].ty == PartItem::Directory => v,
+                _ => {
+                    eprintln!("Skipping {} due to missing parent", path.escape_ascii());
+                    continue;
+                }
+            };
+
+            if name.is_empty()
+                || name == b"."
+                || name == b".."
+                || entries.contains_key(&(parent, name.to_vec()))
+            {
+                eprintln!("Skipping {} due to invalid name", path.escape_ascii());
+                continue;
+            }
+
+            // Hard link shares the inode with its target.
+            let ino = match item.ty() {
+                PartItem::HardLink => {
+                    let target = item.target().unwrap();
+                    let ino = match paths.get(target) {
+                        Some(&v) if nodes[(v - 1) as usize].ty == PartItem::File => v,
+                        _ => {
+                            eprintln!("Skipping {} due to invalid target", path.escape_ascii());
+                            continue;
+                        }
+                    };
+
+                    nodes[(parent - 1) as usize]
+                        .children
+                        .push((name.to_vec(), ino));
+                    entries.insert((parent, name.to_vec()), ino);
+                    nodes[(ino - 1) as usize].nlink += 1;
+
+                    ino
+                }
+                ty => {
+                    self.blocks += item.len().div_ceil(512);
+
+                    add_node(nodes, entries, parent, name.to_vec(), ty, Some((index, i)))
+                }
+            };
+
+            paths.insert(path, ino);
+        }
+    }
+
+    fn node(&self, ino: u64) -> Result<&Node, i32> {
+        ino.checked_sub(1)
+            .and_then(|i| self.nodes.get(i as usize))
+            .ok_or(libc::ENOENT)
+    }
+
+    fn item(&self, node: &Node) -> Option<&IndexedItem> {
+        node.item.map(|(p, i)| &self.dump.parts()[p].items()[i])
+    }
+
+    fn dir(&self, ino: u64) -> Result<&Node, i32> {
+        let node = self.node(ino)?;
+
+        if node.ty == PartItem::Directory {
+            Ok(node)
+        } else {
+            Err(libc::ENOTDIR)
+        }
+    }
+}
+
+/// Adds a node to `nodes` as a child of `parent`. Returns inode number of the new node.
+fn add_node(
+    nodes: &mut Vec<Node>,
+    entries: &mut HashMap<(u64, Vec<u8>), u64>,
+    parent: u64,
+    name: Vec<u8>,
+    ty: PartItem,
+    item: Option<(usize, usize)>,
+) -> u64 {
+    let ino = nodes.len() as u64 + 1;
+    let p = &mut nodes[(parent - 1) as usize];
+
+    if ty == PartItem::Directory {
+        p.nlink += 1;
+    }
+
+    p.children.push((name.clone(), ino));
+    entries.insert((parent, name), ino);
+    nodes.push(Node::new(parent, ty, item));
+
+    ino
+}
+
+/// Node in [`Filesystem`].
+struct Node {
+    parent: u64,
+    /// Type of the node. Never be [`PartItem::HardLink`].
+    ty: PartItem,
+    /// Partition and item index. [`None`] for a directory that is not in the dump.
+    item: Option<(usize, usize)>,
+    nlink: u32,
+    children: Vec<(Vec<u8>, u64)>,
+}
+
+impl Node {
+    fn new(parent: u64, ty: PartItem, item: Option<(usize, usize)>) -> Self {
+        Self {
+            parent,
+            ty,
+            item,
+            nlink: if ty == PartItem::Directory { 2 } else { 1 },
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Converts FreeBSD device number to the one used by FUSE.
+fn linux_dev(rdev: u64) -> u32 {
+    let major = (rdev >> 8) & 0xff;
+    let minor = rdev & 0xffff00ff;
+
+    ((minor & 0xff) | (major << 8) | ((minor & !0xff) << 12)) as u32
+}
+
+fn io_error(path: &[u8], e: impl std::error::Error) -> i32 {
+    eprintln!("Couldn't read {}: {e}", path.escape_ascii());
+    libc::EIO
+}
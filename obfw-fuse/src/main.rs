@@ -0,0 +1,122 @@
+use clap::Parser;
+use std::error::Error;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[cfg(target_os = "linux")]
+mod abi;
+#[cfg(target_os = "linux")]
+mod fs;
+#[cfg(target_os = "linux")]
+mod mount;
+#[cfg(target_os = "linux")]
+mod session;
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    match run(args) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            report(e.as_ref());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    use self::fs::Filesystem;
+    use obfw::DumpIndex;
+    use std::fs::File;
+    use std::io::BufReader;
+
+    // Load the index. This only reads the table of contents if the dump has it.
+    let path = &args.dump;
+    let file = File::open(path).map_err(|e| FuseError::Open(path.clone(), e))?;
+    let index =
+        DumpIndex::new(BufReader::new(file)).map_err(|e| FuseError::Index(path.clone(), e))?;
+    let mut fs = Filesystem::new(index);
+
+    // Unmount when interrupted. The session will stop once the kernel tells us it was unmounted.
+    let signals = unsafe {
+        let mut set = std::mem::zeroed();
+
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        libc::sigaddset(&mut set, libc::SIGTERM);
+        libc::sigaddset(&mut set, libc::SIGHUP);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+
+        set
+    };
+
+    let (mut dev, unmount) = self::mount::mount(&args.mountpoint)
+        .map_err(|e| FuseError::Mount(args.mountpoint.clone(), e))?;
+
+    std::thread::spawn(move || {
+        let mut sig = 0;
+
+        unsafe { libc::sigwait(&signals, &mut sig) };
+
+        if let Err(e) = unmount.run() {
+            report(&e);
+        }
+    });
+
+    eprintln!(
+        "Mounted {} on {}. Press Ctrl+C to unmount.",
+        path.display(),
+        args.mountpoint.display()
+    );
+
+    self::session::run(&mut dev, &mut fs)?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run(_: Args) -> Result<(), Box<dyn Error>> {
+    Err("FUSE is supported only on Linux".into())
+}
+
+fn report(e: &dyn Error) {
+    let mut msg = e.to_string();
+    let mut src = e.source();
+
+    while let Some(e) = src {
+        write!(msg, " -> {e}").unwrap();
+        src = e.source();
+    }
+
+    eprintln!("{msg}");
+}
+
+/// Represents an error when [`run()`] fails.
+#[cfg(target_os = "linux")]
+#[derive(Debug, thiserror::Error)]
+enum FuseError {
+    #[error("couldn't open {}", .0.display())]
+    Open(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't load index of {}", .0.display())]
+    Index(PathBuf, #[source] obfw::IndexError),
+
+    #[error("couldn't mount on {}", .0.display())]
+    Mount(PathBuf, #[source] self::mount::MountError),
+}
+
+/// Mount a firmware dump as a read-only filesystem.
+///
+/// Each partition is a directory named after its device (e.g. `da0x4.crypt`). File content is
+/// read from the dump only when requested.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+
+    /// Directory to mount on.
+    mountpoint: PathBuf,
+}
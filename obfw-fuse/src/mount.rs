@@ -0,0 +1,201 @@
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::Error;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use thiserror::Error;
+
+/// Mount options for `fusermount`.
+const OPTIONS: &str = "ro,nosuid,nodev,fsname=obfw,subtype=obfw";
+
+/// Mount a FUSE filesystem on `path`. Returns the FUSE device to serve the requests.
+///
+/// This requires `CAP_SYS_ADMIN` to mount directly otherwise `fusermount3` or `fusermount` will
+/// be used.
+pub fn mount(path: &Path) -> Result<(File, Unmount), MountError> {
+    let target = CString::new(path.as_os_str().as_bytes()).map_err(|_| MountError::InvalidPath)?;
+    let dev = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/fuse")
+        .map_err(MountError::OpenDevice)?;
+    let data = format!(
+        "fd={},rootmode=40000,user_id={},group_id={}",
+        dev.as_raw_fd(),
+        unsafe { libc::getuid() },
+        unsafe { libc::getgid() }
+    );
+    let data = CString::new(data).unwrap();
+    let flags = libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV;
+    let r = unsafe {
+        libc::mount(
+            c"obfw".as_ptr(),
+            target.as_ptr(),
+            c"fuse.obfw".as_ptr(),
+            flags,
+            data.as_ptr().cast(),
+        )
+    };
+
+    if r == 0 {
+        return Ok((
+            dev,
+            Unmount {
+                target,
+                helper: None,
+            },
+        ));
+    }
+
+    // Fallback to the helper if we don't have a permission.
+    let e = Error::last_os_error();
+
+    if e.raw_os_error() != Some(libc::EPERM) {
+        return Err(MountError::Mount(e));
+    }
+
+    drop(dev);
+
+    for helper in ["fusermount3", "fusermount"] {
+        match fusermount(helper, path) {
+            Ok(dev) => {
+                return Ok((
+                    dev,
+                    Unmount {
+                        target,
+                        helper: Some(helper),
+                    },
+                ))
+            }
+            Err(MountError::RunHelper(_, e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(MountError::Mount(e))
+}
+
+/// Mount with `fusermount`, which will send the opened FUSE device back to us via a socket.
+fn fusermount(helper: &'static str, path: &Path) -> Result<File, MountError> {
+    let (sock, remote) = UnixStream::pair().map_err(|e| MountError::RunHelper(helper, e))?;
+
+    // Allow the helper to inherit the socket.
+    if unsafe { libc::fcntl(remote.as_raw_fd(), libc::F_SETFD, 0) } < 0 {
+        return Err(MountError::RunHelper(helper, Error::last_os_error()));
+    }
+
+    let status = Command::new(helper)
+        .args(["-o", OPTIONS, "--"])
+        .arg(path)
+        .env("_FUSE_COMMFD", remote.as_raw_fd().to_string())
+        .status()
+        .map_err(|e| MountError::RunHelper(helper, e))?;
+
+    if !status.success() {
+        return Err(MountError::HelperFailed(helper, status));
+    }
+
+    drop(remote);
+
+    // Receive the device.
+    let mut byte = 0u8;
+    let mut iov = libc::iovec {
+        iov_base: (&raw mut byte).cast(),
+        iov_len: 1,
+    };
+    let mut control = [0u64; 8];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+
+    msg.msg_iov = &raw mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = size_of_val(&control) as _;
+
+    if unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) } < 0 {
+        return Err(MountError::ReceiveDevice(Error::last_os_error()));
+    }
+
+    let cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+
+    if cmsg.is_null() || unsafe { (*cmsg).cmsg_type } != libc::SCM_RIGHTS {
+        return Err(MountError::ReceiveDevice(Error::other(
+            "no file descriptor was received",
+        )));
+    }
+
+    let fd = unsafe { libc::CMSG_DATA(cmsg).cast::<libc::c_int>().read_unaligned() };
+
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Unmount the filesystem mounted by [`mount()`].
+pub struct Unmount {
+    target: CString,
+    helper: Option<&'static str>,
+}
+
+impl Unmount {
+    /// This does a lazy unmount so it does not fail if the filesystem is busy.
+    pub fn run(&self) -> Result<(), UnmountError> {
+        let helper = match self.helper {
+            Some(v) => v,
+            None => {
+                return match unsafe { libc::umount2(self.target.as_ptr(), libc::MNT_DETACH) } {
+                    0 => Ok(()),
+                    _ => Err(UnmountError::Unmount(Error::last_os_error())),
+                };
+            }
+        };
+
+        let path = PathBuf::from(std::ffi::OsStr::from_bytes(self.target.as_bytes()));
+        let status = Command::new(helper)
+            .args(["-u", "-z", "--"])
+            .arg(path)
+            .status()
+            .map_err(|e| UnmountError::RunHelper(helper, e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(UnmountError::HelperFailed(helper, status))
+        }
+    }
+}
+
+/// Represents an error when [`mount()`] fails.
+#[derive(Debug, Error)]
+pub enum MountError {
+    #[error("mount point contains a NUL character")]
+    InvalidPath,
+
+    #[error("couldn't open /dev/fuse")]
+    OpenDevice(#[source] Error),
+
+    #[error("couldn't mount the filesystem")]
+    Mount(#[source] Error),
+
+    #[error("couldn't run {0}")]
+    RunHelper(&'static str, #[source] Error),
+
+    #[error("{0} exited with {1}")]
+    HelperFailed(&'static str, ExitStatus),
+
+    #[error("couldn't receive FUSE device from fusermount")]
+    ReceiveDevice(#[source] Error),
+}
+
+/// Represents an error when [`Unmount::run()`] fails.
+#[derive(Debug, Error)]
+pub enum UnmountError {
+    #[error("couldn't unmount the filesystem")]
+    Unmount(#[source] Error),
+
+    #[error("couldn't run {0}")]
+    RunHelper(&'static str, #[source] Error),
+
+    #[error("{0} exited with {1}")]
+    HelperFailed(&'static str, ExitStatus),
+}
@@ -0,0 +1,218 @@
+use crate::abi::*;
+use crate::fs::Filesystem;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::mem::size_of;
+use thiserror::Error;
+
+/// How long the kernel can cache the attributes and the entries. The dump never changes while
+/// mounted so we can use a long duration.
+const TTL: u64 = 24 * 60 * 60;
+
+/// Serve requests from `dev` until the filesystem is unmounted.
+pub fn run<F: Read + Seek>(dev: &mut File, fs: &mut Filesystem<F>) -> Result<(), SessionError> {
+    let mut buf = vec![0; MIN_READ_BUFFER + 0x20000];
+    let mut out = Vec::new();
+
+    loop {
+        // Read the next request.
+        let len = match dev.read(&mut buf) {
+            Ok(v) => v,
+            Err(e) => match e.raw_os_error() {
+                // The request was interrupted before we read it.
+                Some(libc::ENOENT | libc::EINTR | libc::EAGAIN) => continue,
+                // The filesystem has been unmounted.
+                Some(libc::ENODEV) => return Ok(()),
+                _ => return Err(SessionError::Read(e)),
+            },
+        };
+
+        let req = &buf[..len];
+        let hdr = InHeader::read(req).ok_or(SessionError::InvalidRequest)?;
+        let data = req
+            .get(size_of::<InHeader>()..(hdr.len as usize))
+            .ok_or(SessionError::InvalidRequest)?;
+
+        // Process the request.
+        out.clear();
+        out.resize(size_of::<OutHeader>(), 0);
+
+        let r = match hdr.opcode {
+            FUSE_INIT => init(data, &mut out),
+            FUSE_DESTROY => Ok(()),
+            FUSE_FORGET | FUSE_BATCH_FORGET | FUSE_INTERRUPT => continue,
+            FUSE_LOOKUP => lookup(fs, hdr.nodeid, data, &mut out),
+            FUSE_GETATTR => fs.attr(hdr.nodeid).map(|attr| {
+                let v = AttrOut {
+                    attr_valid: TTL,
+                    attr_valid_nsec: 0,
+                    dummy: 0,
+                    attr,
+                };
+
+                out.extend_from_slice(v.as_bytes());
+            }),
+            FUSE_READLINK => fs.read_link(hdr.nodeid).map(|v| out.extend_from_slice(v)),
+            FUSE_OPEN => open(fs, hdr.nodeid, data, &mut out),
+            FUSE_READ => read(fs, hdr.nodeid, data, &mut out),
+            FUSE_RELEASE => {
+                fs.release(hdr.nodeid);
+                Ok(())
+            }
+            FUSE_RELEASEDIR => Ok(()),
+            FUSE_OPENDIR => fs.open_dir(hdr.nodeid).map(|_| {
+                let v = OpenOut {
+                    fh: 0,
+                    open_flags: 0,
+                    padding: 0,
+                };
+
+                out.extend_from_slice(v.as_bytes());
+            }),
+            FUSE_READDIR => match ReadIn::read(data) {
+                Some(v) => fs.read_dir(hdr.nodeid, v.offset, v.size as usize, &mut out),
+                None => Err(libc::EINVAL),
+            },
+            FUSE_STATFS => {
+                out.extend_from_slice(fs.statfs().as_bytes());
+                Ok(())
+            }
+            _ => Err(libc::ENOSYS),
+        };
+
+        // Send the reply.
+        let err = match r {
+            Ok(_) => 0,
+            Err(e) => {
+                out.truncate(size_of::<OutHeader>());
+                -e
+            }
+        };
+
+        let res = OutHeader {
+            len: out.len() as u32,
+            error: err,
+            unique: hdr.unique,
+        };
+
+        out[..size_of::<OutHeader>()].copy_from_slice(res.as_bytes());
+
+        if let Err(e) = dev.write_all(&out) {
+            // The request was interrupted.
+            if e.raw_os_error() != Some(libc::ENOENT) {
+                return Err(SessionError::Write(e));
+            }
+        }
+
+        if hdr.opcode == FUSE_DESTROY {
+            return Ok(());
+        }
+    }
+}
+
+fn init(data: &[u8], out: &mut Vec<u8>) -> Result<(), i32> {
+    let req = InitIn::read(data).ok_or(libc::EINVAL)?;
+
+    // We don't support compatibility structures of the old kernels.
+    if req.major != KERNEL_VERSION || req.minor < 23 {
+        return Err(libc::EPROTO);
+    }
+
+    let res = InitOut {
+        major: KERNEL_VERSION,
+        minor: req.minor.min(KERNEL_MINOR_VERSION),
+        max_readahead: req.max_readahead,
+        max_write: 4096,
+        time_gran: 1,
+        ..Default::default()
+    };
+
+    out.extend_from_slice(res.as_bytes());
+
+    Ok(())
+}
+
+fn lookup<F: Read + Seek>(
+    fs: &Filesystem<F>,
+    parent: u64,
+    data: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), i32> {
+    // Name is null-terminated.
+    let name = match data.iter().position(|&b| b == 0) {
+        Some(i) => &data[..i],
+        None => return Err(libc::EINVAL),
+    };
+
+    let (ino, attr) = fs.lookup(parent, name)?;
+    let res = EntryOut {
+        nodeid: ino,
+        generation: 0,
+        entry_valid: TTL,
+        attr_valid: TTL,
+        entry_valid_nsec: 0,
+        attr_valid_nsec: 0,
+        attr,
+    };
+
+    out.extend_from_slice(res.as_bytes());
+
+    Ok(())
+}
+
+fn open<F: Read + Seek>(
+    fs: &mut Filesystem<F>,
+    ino: u64,
+    data: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), i32> {
+    let req = OpenIn::read(data).ok_or(libc::EINVAL)?;
+
+    if req.flags & libc::O_ACCMODE as u32 != libc::O_RDONLY as u32 {
+        return Err(libc::EROFS);
+    }
+
+    fs.open(ino)?;
+
+    // File content never change so the kernel does not need to invalidate the cache when opened.
+    let res = OpenOut {
+        fh: 0,
+        open_flags: FOPEN_KEEP_CACHE,
+        padding: 0,
+    };
+
+    out.extend_from_slice(res.as_bytes());
+
+    Ok(())
+}
+
+fn read<F: Read + Seek>(
+    fs: &mut Filesystem<F>,
+    ino: u64,
+    data: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), i32> {
+    let req = ReadIn::read(data).ok_or(libc::EINVAL)?;
+    let start = out.len();
+
+    out.resize(start + req.size as usize, 0);
+
+    let len = fs.read(ino, req.offset, &mut out[start..])?;
+
+    out.truncate(start + len);
+
+    Ok(())
+}
+
+/// Represents an error when [`run()`] fails.
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("couldn't read a request from the kernel")]
+    Read(#[source] std::io::Error),
+
+    #[error("couldn't write a reply to the kernel")]
+    Write(#[source] std::io::Error),
+
+    #[error("the kernel sent an invalid request")]
+    InvalidRequest,
+}
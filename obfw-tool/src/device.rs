@@ -0,0 +1,70 @@
+use crate::dump::{part_matches, OpenError};
+use obfw::{DumpIndex, IndexError};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), DeviceError> {
+    // Open the dump.
+    let file = File::open(&args.dump).map_err(|e| OpenError::Open(args.dump.clone(), e))?;
+    let mut dump = DumpIndex::new(BufReader::new(file)).map_err(DeviceError::ReadDump)?;
+    let name = dump
+        .raw_devices()
+        .iter()
+        .find(|d| part_matches(d.name(), &args.device))
+        .map(|d| d.name().to_vec())
+        .ok_or_else(|| DeviceError::NotFound(args.device.clone()))?;
+
+    // Check the content before writing it since the output can be a large file.
+    dump.check_raw_device(&name)
+        .map_err(DeviceError::ReadDump)?;
+
+    // Write content.
+    let mut r = dump.open_raw_device(&name).map_err(DeviceError::ReadDump)?;
+    let file = File::create(&args.output).map_err(DeviceError::Create)?;
+    let mut file = BufWriter::new(file);
+    let len = std::io::copy(&mut r, &mut file)
+        .and_then(|v| file.flush().map(|_| v))
+        .map_err(DeviceError::Write)?;
+
+    eprintln!(
+        "Wrote {} bytes from {} to {}",
+        len,
+        name.escape_ascii(),
+        args.output.display()
+    );
+
+    Ok(())
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+
+    /// Name of the device (e.g. `sflash0` or `/dev/sflash0`).
+    device: String,
+
+    /// Path to write the content.
+    output: PathBuf,
+}
+
+/// Represents an error when `device` command fails.
+#[derive(Debug, Error)]
+pub enum DeviceError {
+    #[error(transparent)]
+    Open(#[from] OpenError),
+
+    #[error("couldn't read the dump")]
+    ReadDump(#[source] IndexError),
+
+    #[error("the dump doesn't contain {0}")]
+    NotFound(String),
+
+    #[error("couldn't create the output file")]
+    Create(#[source] std::io::Error),
+
+    #[error("couldn't write the output file")]
+    Write(#[source] std::io::Error),
+}
@@ -0,0 +1,351 @@
+use crate::dump::{display_path, major, minor, OpenError};
+use obfw::ps4::{PartData, PartError};
+use obfw::{ItemReader, ReaderError};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Size of each block when comparing file content with `--bytes`.
+const BLOCK: usize = 0x1000;
+
+/// Maximum number of ranges to show for each file with `--bytes`.
+const MAX_RANGES: usize = 8;
+
+/// Returns `true` if both dumps have the same partitions and items.
+pub fn run(args: Args) -> Result<bool, DiffError> {
+    let old = load(&args.old, args.bytes)?;
+    let new = load(&args.new, args.bytes)?;
+    let mut same = true;
+
+    // Compare partitions. Their content is not listed if the whole partition was added or removed.
+    for (dev, p) in &old {
+        if !new.contains_key(dev) {
+            println!("D {} ({})", dev.escape_ascii(), p.fs.escape_ascii());
+            same = false;
+        }
+    }
+
+    for (dev, p) in &new {
+        if !old.contains_key(dev) {
+            println!("A {} ({})", dev.escape_ascii(), p.fs.escape_ascii());
+            same = false;
+        }
+    }
+
+    // Compare items in the same partition.
+    for (dev, o) in &old {
+        let n = match new.get(dev) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let mut lines = Vec::new();
+
+        if o.fs != n.fs {
+            lines.push(format!(
+                "T {} -> {}",
+                o.fs.escape_ascii(),
+                n.fs.escape_ascii()
+            ));
+        }
+
+        for (path, item) in &o.items {
+            if !n.items.contains_key(path) {
+                lines.push(format!("D {} ({})", display_path(path), item));
+            }
+        }
+
+        for (path, item) in &n.items {
+            let old = match o.items.get(path) {
+                Some(v) => v,
+                None => {
+                    lines.push(format!("A {} ({})", display_path(path), item));
+                    continue;
+                }
+            };
+
+            match (old, item) {
+                (Item::File(a), Item::File(b)) => {
+                    if a.len == b.len && a.hash == b.hash {
+                        continue;
+                    }
+
+                    lines.push(format!(
+                        "M {} ({} -> {} bytes, sha256 {} -> {})",
+                        display_path(path),
+                        a.len,
+                        b.len,
+                        short_hash(&a.hash),
+                        short_hash(&b.hash)
+                    ));
+
+                    if args.bytes {
+                        lines.push(format!("    {}", changed_ranges(a, b)));
+                    }
+                }
+                (a, b) if std::mem::discriminant(a) != std::mem::discriminant(b) => {
+                    lines.push(format!("T {} ({} -> {})", display_path(path), a, b));
+                }
+                (a, b) if a != b => {
+                    lines.push(format!(
+                        "M {} ({} -> {})",
+                        display_path(path),
+                        a.value(),
+                        b.value()
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        if !lines.is_empty() {
+            println!("{}:", dev.escape_ascii());
+
+            for l in lines {
+                println!("  {l}");
+            }
+
+            same = false;
+        }
+    }
+
+    if same {
+        eprintln!("No differences");
+    }
+
+    Ok(same)
+}
+
+/// Reads all items in the dump at `path`. Each partition is keyed by its device.
+fn load(path: &Path, blocks: bool) -> Result<BTreeMap<Vec<u8>, Part>, DiffError> {
+    let mut dump = crate::dump::open(path)?;
+    let mut parts = BTreeMap::new();
+    let mut buf = vec![0; BLOCK];
+
+    eprintln!("Reading {}", path.display());
+
+    while let Some(item) = dump.next_item().map_err(DiffError::ReadDump)? {
+        let mut part = match item {
+            ItemReader::Ps4Part(v) => v,
+            _ => continue,
+        };
+        let mut items = BTreeMap::new();
+
+        while let Some(data) = part.next_item().map_err(DiffError::ReadPart)? {
+            let (path, item) = match data {
+                PartData::Directory(path, _) => (path, Item::Directory),
+                PartData::File(path, _, mut r) => {
+                    let file = hash_file(&mut r, &mut buf, blocks)
+                        .map_err(|e| DiffError::ReadFile(display_path(&path), e))?;
+
+                    (path, Item::File(file))
+                }
+                PartData::Symlink(path, _, target) => (path, Item::Symlink(target)),
+                PartData::HardLink(path, _, target) => (path, Item::HardLink(target)),
+                PartData::CharDevice(path, _, rdev) => (path, Item::CharDevice(rdev)),
+                PartData::BlockDevice(path, _, rdev) => (path, Item::BlockDevice(rdev)),
+                PartData::Fifo(path, _) => (path, Item::Fifo),
+                PartData::Socket(path, _) => (path, Item::Socket),
+            };
+
+            items.insert(path, item);
+        }
+
+        let p = Part {
+            fs: part.fs().to_vec(),
+            items,
+        };
+
+        if parts.insert(part.dev().to_vec(), p).is_some() {
+            return Err(DiffError::DuplicatedPartition(
+                path.to_path_buf(),
+                part.dev().escape_ascii().to_string(),
+            ));
+        }
+    }
+
+    Ok(parts)
+}
+
+/// Reads the whole file and calculate its hash. The hash of each block is also calculated if
+/// `blocks` is `true`.
+fn hash_file(r: &mut impl Read, buf: &mut [u8], blocks: bool) -> std::io::Result<File> {
+    let mut file = File {
+        len: 0,
+        hash: [0; 32],
+        blocks: Vec::new(),
+    };
+
+    let mut hash = Sha256::new();
+
+    loop {
+        // Fill the whole block so the block hashes does not depend on how the data was read.
+        let mut n = 0;
+
+        while n < buf.len() {
+            match r.read(&mut buf[n..])? {
+                0 => break,
+                v => n += v,
+            }
+        }
+
+        if n == 0 {
+            break;
+        }
+
+        let data = &buf[..n];
+
+        hash.update(data);
+        file.len += n as u64;
+
+        if blocks {
+            let h = Sha256::digest(data);
+
+            file.blocks
+                .push(u64::from_le_bytes(h[..8].try_into().unwrap()));
+        }
+    }
+
+    file.hash = hash.finalize().into();
+
+    Ok(file)
+}
+
+/// Returns a summary of the blocks that are different between `a` and `b`.
+fn changed_ranges(a: &File, b: &File) -> String {
+    let len = a.len.max(b.len);
+    let total = a.blocks.len().max(b.blocks.len());
+    let mut ranges = Vec::new();
+    let mut start = None;
+
+    for i in 0..=total {
+        let changed = i < total && a.blocks.get(i) != b.blocks.get(i);
+
+        match (changed, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                ranges.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    let blocks: usize = ranges.iter().map(|(s, e)| e - s).sum();
+    let mut out = String::from("changed");
+
+    for (i, &(s, e)) in ranges.iter().enumerate() {
+        if i == MAX_RANGES {
+            write!(out, " and {} more,", ranges.len() - i).unwrap();
+            break;
+        }
+
+        let s = (s * BLOCK) as u64;
+        let e = ((e * BLOCK) as u64).min(len);
+
+        write!(out, " {s:#x}-{e:#x},").unwrap();
+    }
+
+    out.pop();
+
+    write!(out, " ({blocks} of {total} blocks)").unwrap();
+
+    out
+}
+
+fn short_hash(hash: &[u8; 32]) -> String {
+    hash[..6].iter().fold(String::new(), |mut s, b| {
+        write!(s, "{b:02x}").unwrap();
+        s
+    })
+}
+
+/// Partition in the dump.
+struct Part {
+    fs: Vec<u8>,
+    items: BTreeMap<Vec<u8>, Item>,
+}
+
+/// Item in the partition.
+#[derive(PartialEq)]
+enum Item {
+    Directory,
+    File(File),
+    Symlink(Vec<u8>),
+    HardLink(Vec<u8>),
+    CharDevice(u64),
+    BlockDevice(u64),
+    Fifo,
+    Socket,
+}
+
+impl Item {
+    /// Returns the value to show when the item was modified without changing its type.
+    fn value(&self) -> String {
+        match self {
+            Self::Symlink(v) => v.escape_ascii().to_string(),
+            Self::HardLink(v) => display_path(v),
+            Self::CharDevice(v) | Self::BlockDevice(v) => format!("{}, {}", major(*v), minor(*v)),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Item {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Directory => f.write_str("directory"),
+            Self::File(v) => write!(f, "file, {} bytes", v.len),
+            Self::Symlink(v) => write!(f, "symlink -> {}", v.escape_ascii()),
+            Self::HardLink(v) => write!(f, "hard link => {}", display_path(v)),
+            Self::CharDevice(v) => write!(f, "character device {}, {}", major(*v), minor(*v)),
+            Self::BlockDevice(v) => write!(f, "block device {}, {}", major(*v), minor(*v)),
+            Self::Fifo => f.write_str("fifo"),
+            Self::Socket => f.write_str("socket"),
+        }
+    }
+}
+
+/// Summary of a regular file.
+#[derive(PartialEq)]
+struct File {
+    len: u64,
+    hash: [u8; 32],
+    /// Truncated SHA-256 of each block. Only available with `--bytes`.
+    blocks: Vec<u64>,
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the old dump.
+    old: PathBuf,
+
+    /// Path to the new dump.
+    new: PathBuf,
+
+    /// Show which ranges of each modified file are different, in 4 KB blocks.
+    #[arg(short, long)]
+    bytes: bool,
+}
+
+/// Represents an error when `diff` command fails.
+#[derive(Debug, Error)]
+pub enum DiffError {
+    #[error(transparent)]
+    Open(#[from] OpenError),
+
+    #[error("couldn't read the next item")]
+    ReadDump(#[source] ReaderError),
+
+    #[error("couldn't read the next partition item")]
+    ReadPart(#[source] PartError),
+
+    #[error("couldn't read {0}")]
+    ReadFile(String, #[source] std::io::Error),
+
+    #[error("{} contains multiple partitions from {}", .0.display(), .1)]
+    DuplicatedPartition(PathBuf, String),
+}
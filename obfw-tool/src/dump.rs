@@ -0,0 +1,154 @@
+use obfw::{DumpIndex, DumpReader, IndexError, ReaderError};
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Opens a dump at `path` for reading.
+pub fn open(path: &Path) -> Result<DumpReader<BufReader<File>>, OpenError> {
+    let file = File::open(path).map_err(|e| OpenError::Open(path.into(), e))?;
+
+    DumpReader::new(BufReader::new(file)).map_err(|e| OpenError::Read(path.into(), e))
+}
+
+/// Reads an item that is not a partition from the dump at `path` with `f` (e.g.
+/// [`DumpIndex::kernel()`]). The item is located with the table of contents if the dump has one
+/// otherwise all items are scanned.
+pub fn read_item<T>(
+    path: &Path,
+    f: impl FnOnce(&mut DumpIndex<BufReader<File>>) -> Result<Option<T>, IndexError>,
+) -> Result<Option<T>, ItemError> {
+    let file = File::open(path).map_err(|e| ItemError::Open(path.into(), e))?;
+    let mut dump =
+        DumpIndex::new(BufReader::new(file)).map_err(|e| ItemError::Read(path.into(), e))?;
+
+    f(&mut dump).map_err(|e| ItemError::Read(path.into(), e))
+}
+
+/// Returns a name of the partition that is suitable for a directory name (e.g. `da0x4.crypt` for
+/// `/dev/da0x4.crypt`). Returns [`None`] if the device does not have a valid name.
+pub fn part_name(dev: &[u8]) -> Option<Cow<'_, str>> {
+    obfw::ps4::part_name(dev).map(String::from_utf8_lossy)
+}
+
+/// Returns a directory under `root` for the partition with `dev`. Returns [`None`] if the device
+/// does not have a valid name or it cannot be represented on the host.
+pub fn part_dir(root: &Path, dev: &[u8]) -> Option<PathBuf> {
+    obfw::ps4::part_name(dev)
+        .and_then(host_name)
+        .map(|n| root.join(n))
+}
+
+/// Returns `true` if the partition with `dev` was selected by `name`, which can be either a full
+/// device path or the value returned from [`part_name()`].
+pub fn part_matches(dev: &[u8], name: &str) -> bool {
+    dev == name.as_bytes() || part_name(dev).is_some_and(|n| n == name)
+}
+
+/// Returns a human-readable form of `path` in the partition.
+pub fn display_path(path: &[u8]) -> String {
+    if path.is_empty() {
+        String::from("/")
+    } else {
+        path.escape_ascii().to_string()
+    }
+}
+
+/// Returns `mode` in the same form as `ls -l` (e.g. `drwxr-xr-x`).
+pub fn display_mode(mode: u16) -> String {
+    let mut out = String::with_capacity(10);
+    let ty = match mode & 0o170000 {
+        0o010000 => 'p',
+        0o020000 => 'c',
+        0o040000 => 'd',
+        0o060000 => 'b',
+        0o100000 => '-',
+        0o120000 => 'l',
+        0o140000 => 's',
+        _ => '?',
+    };
+
+    out.push(ty);
+
+    for (i, special) in [(6, 0o4000), (3, 0o2000), (0, 0o1000)] {
+        let bits = (mode >> i) & 7;
+        let exec = match (bits & 1 != 0, mode & special != 0) {
+            (true, false) => 'x',
+            (false, false) => '-',
+            (true, true) if i == 0 => 't',
+            (false, true) if i == 0 => 'T',
+            (true, true) => 's',
+            (false, true) => 'S',
+        };
+
+        out.push(if bits & 4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 2 != 0 { 'w' } else { '-' });
+        out.push(exec);
+    }
+
+    out
+}
+
+/// Returns major number of `rdev`.
+pub fn major(rdev: u64) -> u64 {
+    (rdev >> 8) & 0xff
+}
+
+/// Returns minor number of `rdev`.
+pub fn minor(rdev: u64) -> u64 {
+    rdev & 0xffff00ff
+}
+
+/// Maps `path` in the partition to a path under `root`. Returns [`None`] if `path` contains
+/// components that can escape from `root` or cannot be represented on the host.
+pub fn host_path(root: &Path, path: &[u8]) -> Option<PathBuf> {
+    let mut out = root.to_path_buf();
+
+    if path.is_empty() {
+        return Some(out);
+    }
+
+    for c in path.strip_prefix(b"/")?.split(|&b| b == b'/') {
+        if c.is_empty() || c == b"." || c == b".." || c.contains(&0) {
+            return None;
+        }
+
+        out.push(host_name(c)?);
+    }
+
+    Some(out)
+}
+
+#[cfg(unix)]
+fn host_name(name: &[u8]) -> Option<&OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(OsStr::from_bytes(name))
+}
+
+#[cfg(not(unix))]
+fn host_name(name: &[u8]) -> Option<&OsStr> {
+    std::str::from_utf8(name).ok().map(OsStr::new)
+}
+
+/// Represents an error when [`open()`] fails.
+#[derive(Debug, Error)]
+pub enum OpenError {
+    #[error("couldn't open {}", .0.display())]
+    Open(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't read {}", .0.display())]
+    Read(PathBuf, #[source] ReaderError),
+}
+
+/// Represents an error when [`read_item()`] fails.
+#[derive(Debug, Error)]
+pub enum ItemError {
+    #[error("couldn't open {}", .0.display())]
+    Open(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't read {}", .0.display())]
+    Read(PathBuf, #[source] IndexError),
+}
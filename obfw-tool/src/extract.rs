@@ -0,0 +1,302 @@
+use crate::dump::{display_path, host_path, part_dir, part_matches, OpenError};
+use obfw::ps4::{Metadata, PartData, PartError};
+use obfw::{ItemReader, ReaderError};
+use std::collections::HashSet;
+use std::fs::{create_dir_all, hard_link, File};
+use std::io::{sink, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), ExtractError> {
+    let mut dump = crate::dump::open(&args.dump)?;
+    let mut found = false;
+    let mut roots = HashSet::new();
+    let mut skipped = 0;
+
+    while let Some(item) = dump.next_item().map_err(ExtractError::ReadDump)? {
+        let mut part = match item {
+            ItemReader::Ps4Part(v) => v,
+            _ => continue,
+        };
+
+        // Get output directory. We still need to read the whole partition to reach the next one
+        // even if it was not selected.
+        let root = match &args.partition {
+            Some(v) if part_matches(part.dev(), v) => Some(args.output.clone()),
+            Some(_) => None,
+            None => match part_dir(&args.output, part.dev()) {
+                Some(v) => Some(v),
+                None => {
+                    return Err(ExtractError::InvalidPartition(
+                        part.dev().escape_ascii().to_string(),
+                    ))
+                }
+            },
+        };
+
+        if let Some(root) = &root {
+            // Never extract two partitions to the same directory so one cannot write through the
+            // symbolic links of the other.
+            if !roots.insert(root.clone()) {
+                return Err(ExtractError::DuplicatePartition(
+                    part.dev().escape_ascii().to_string(),
+                ));
+            }
+
+            eprintln!(
+                "Extracting {} to {}",
+                part.dev().escape_ascii(),
+                root.display()
+            );
+            found = true;
+        }
+
+        part.set_elf(args.elf);
+
+        // Symbolic links are created after all other items so we never write through them.
+        let mut links = Vec::new();
+
+        while let Some(data) = part.next_item().map_err(ExtractError::ReadPart)? {
+            match data {
+                PartData::Symlink(path, _, target) => {
+                    let root = match &root {
+                        Some(v) => v,
+                        None => continue,
+                    };
+
+                    let link = host_path(root, &path)
+                        .ok_or_else(|| ExtractError::InvalidPath(display_path(&path)))?;
+
+                    links.push((link, target));
+                }
+                PartData::HardLink(path, _, target) => {
+                    let root = match &root {
+                        Some(v) => v,
+                        None => continue,
+                    };
+
+                    // The target always come before the link so it was already extracted.
+                    let link = host_path(root, &path)
+                        .ok_or_else(|| ExtractError::InvalidPath(display_path(&path)))?;
+                    let target = host_path(root, &target)
+                        .ok_or_else(|| ExtractError::InvalidPath(display_path(&target)))?;
+
+                    hard_link(&target, &link).map_err(|e| ExtractError::CreateHardLink(link, e))?;
+                }
+                PartData::CharDevice(path, meta, rdev) => {
+                    if let Some(root) = &root {
+                        skipped += create_special(root, &path, 0o020000, meta, rdev)?;
+                    }
+                }
+                PartData::BlockDevice(path, meta, rdev) => {
+                    if let Some(root) = &root {
+                        skipped += create_special(root, &path, 0o060000, meta, rdev)?;
+                    }
+                }
+                PartData::Fifo(path, meta) => {
+                    if let Some(root) = &root {
+                        skipped += create_special(root, &path, 0o010000, meta, 0)?;
+                    }
+                }
+                PartData::Socket(path, meta) => {
+                    if let Some(root) = &root {
+                        skipped += create_special(root, &path, 0o140000, meta, 0)?;
+                    }
+                }
+                PartData::Directory(path, _) => {
+                    let root = match &root {
+                        Some(v) => v,
+                        None => continue,
+                    };
+
+                    let dir = host_path(root, &path)
+                        .ok_or_else(|| ExtractError::InvalidPath(display_path(&path)))?;
+
+                    create_dir_all(&dir).map_err(|e| ExtractError::CreateDirectory(dir, e))?;
+                }
+                PartData::File(path, _, mut r) => {
+                    let root = match &root {
+                        Some(v) => v,
+                        None => {
+                            std::io::copy(&mut r, &mut sink())
+                                .map_err(|e| ExtractError::ReadFile(display_path(&path), e))?;
+                            continue;
+                        }
+                    };
+
+                    // Create the file.
+                    let file = host_path(root, &path)
+                        .ok_or_else(|| ExtractError::InvalidPath(display_path(&path)))?;
+                    let mut w = match File::create(&file) {
+                        Ok(v) => BufWriter::new(v),
+                        Err(e) => return Err(ExtractError::CreateFile(file, e)),
+                    };
+
+                    // Copy data. We don't use std::io::copy here so we can tell which side failed.
+                    let mut buf = vec![0; 0x10000];
+
+                    loop {
+                        let n = r
+                            .read(&mut buf)
+                            .map_err(|e| ExtractError::ReadFile(display_path(&path), e))?;
+
+                        if n == 0 {
+                            break;
+                        }
+
+                        w.write_all(&buf[..n])
+                            .map_err(|e| ExtractError::WriteFile(file.clone(), e))?;
+                    }
+
+                    w.flush().map_err(|e| ExtractError::WriteFile(file, e))?;
+                }
+            }
+        }
+
+        for (link, target) in links {
+            create_symlink(&link, &target)?;
+        }
+    }
+
+    if skipped != 0 {
+        eprintln!("Skipped {skipped} special files that couldn't be created");
+    }
+
+    match args.partition {
+        Some(v) if !found => Err(ExtractError::PartitionNotFound(v)),
+        _ => Ok(()),
+    }
+}
+
+/// Creates a device node, FIFO or socket at `path` in `root`. `ty` is the file type bits of
+/// `st_mode`. Returns the number of skipped files, which is one if the file couldn't be created
+/// (e.g. creating a device node requires root).
+fn create_special(
+    root: &Path,
+    path: &[u8],
+    ty: u16,
+    meta: Option<Metadata>,
+    rdev: u64,
+) -> Result<u32, ExtractError> {
+    let file =
+        host_path(root, path).ok_or_else(|| ExtractError::InvalidPath(display_path(path)))?;
+    let mode = ty | meta.map_or(0o644, |m| m.mode & 0o7777);
+
+    match mknod(&file, mode, rdev) {
+        Ok(_) => Ok(0),
+        Err(e) => {
+            eprintln!("Skipping special file {}: {e}", display_path(path));
+            Ok(1)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn mknod(path: &Path, mode: u16, rdev: u64) -> std::io::Result<()> {
+    use crate::dump::{major, minor};
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let perm = libc::mode_t::from(mode & 0o7777);
+    let ret = if mode & 0o170000 == 0o010000 {
+        unsafe { libc::mkfifo(path.as_ptr(), perm) }
+    } else {
+        let dev = libc::makedev(major(rdev) as _, minor(rdev) as _);
+
+        unsafe { libc::mknod(path.as_ptr(), libc::mode_t::from(mode), dev) }
+    };
+
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn mknod(_: &Path, _: u16, _: u64) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(unix)]
+fn create_symlink(link: &Path, target: &[u8]) -> Result<(), ExtractError> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    std::os::unix::fs::symlink(OsStr::from_bytes(target), link)
+        .map_err(|e| ExtractError::CreateSymlink(link.to_path_buf(), e))
+}
+
+#[cfg(not(unix))]
+fn create_symlink(link: &Path, target: &[u8]) -> Result<(), ExtractError> {
+    eprintln!(
+        "Skipping symbolic link {} -> {}",
+        link.display(),
+        target.escape_ascii()
+    );
+
+    Ok(())
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+
+    /// Directory to extract to. Each partition will be extracted to a sub-directory named after
+    /// its device unless --partition is specified.
+    output: PathBuf,
+
+    /// Extract only the partition mounted from this device (e.g. `/dev/da0x4.crypt` or
+    /// `da0x4.crypt`) directly into the output directory.
+    #[arg(short, long)]
+    partition: Option<String>,
+
+    /// Extract SELF files that have decrypted segments as ELF files.
+    #[arg(long)]
+    elf: bool,
+}
+
+/// Represents an error when `extract` command fails.
+#[derive(Debug, Error)]
+pub enum ExtractError {
+    #[error(transparent)]
+    Open(#[from] OpenError),
+
+    #[error("couldn't read the next item")]
+    ReadDump(#[source] ReaderError),
+
+    #[error("couldn't read the next partition item")]
+    ReadPart(#[source] PartError),
+
+    #[error("invalid path {0}")]
+    InvalidPath(String),
+
+    #[error("partition {0} doesn't have a valid name")]
+    InvalidPartition(String),
+
+    #[error("partition {0} would be extracted to the same directory as another one")]
+    DuplicatePartition(String),
+
+    #[error("couldn't create {}", .0.display())]
+    CreateDirectory(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't create {}", .0.display())]
+    CreateFile(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't create {}", .0.display())]
+    CreateSymlink(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't create {}", .0.display())]
+    CreateHardLink(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't read {0}")]
+    ReadFile(String, #[source] std::io::Error),
+
+    #[error("couldn't write {}", .0.display())]
+    WriteFile(PathBuf, #[source] std::io::Error),
+
+    #[error("no partition matched {0}")]
+    PartitionNotFound(String),
+}
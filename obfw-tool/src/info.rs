@@ -0,0 +1,142 @@
+use crate::dump::{display_path, OpenError};
+use obfw::ps4::{PartData, PartError};
+use obfw::{ItemReader, ReaderError, MAGIC};
+use std::io::sink;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), InfoError> {
+    let mut dump = crate::dump::open(&args.dump)?;
+
+    println!("Magic     : {}", MAGIC.escape_ascii());
+    println!("Items     : {}", dump.items());
+
+    match dump.toc() {
+        Some(v) => println!("TOC       : {v:#x}"),
+        None => println!("TOC       : none"),
+    }
+
+    let mut parts = 0;
+
+    while let Some(item) = dump.next_item().map_err(InfoError::ReadDump)? {
+        let mut part = match item {
+            ItemReader::Ps4Part(v) => v,
+            ItemReader::SystemInfo(v) => {
+                let (major, minor) = v.firmware();
+
+                println!("Firmware  : {major}.{minor:02} ({:#010x})", v.sdk_version);
+                println!("Model     : {}", v.model.escape_ascii());
+                println!("Kernel    : {}", v.kernel.trim_ascii().escape_ascii());
+                println!("Time      : {}", v.time.sec);
+                println!("Dumper    : {}", v.dumper.escape_ascii());
+                continue;
+            }
+            ItemReader::Kernel(v) => {
+                let size: usize = v.segments.iter().map(|s| s.data.len()).sum();
+
+                println!(
+                    "Kernel    : {:#x} ({} segments, {} bytes)",
+                    v.base,
+                    v.segments.len(),
+                    size
+                );
+                continue;
+            }
+            ItemReader::Syscalls(v) => {
+                println!("Syscalls  : {}", v.entries.len());
+                continue;
+            }
+            ItemReader::Modules(v) => {
+                println!("Modules   : {}", v.len());
+                continue;
+            }
+            ItemReader::RawDevice(v) => {
+                println!(
+                    "Device    : {} ({} of {} bytes)",
+                    v.name().escape_ascii(),
+                    v.len(),
+                    v.size()
+                );
+
+                if v.len() != v.size() {
+                    eprintln!(
+                        "Warning: {} is incomplete since the dumper couldn't read the whole device",
+                        v.name().escape_ascii()
+                    );
+                }
+
+                continue;
+            }
+        };
+
+        if parts == 0 {
+            println!("Partitions:");
+        }
+
+        parts += 1;
+
+        // Count items.
+        let mut dirs = 0;
+        let mut files = 0;
+        let mut links = 0;
+        let mut hard_links = 0;
+        let mut specials = 0;
+        let mut size = 0;
+
+        while let Some(data) = part.next_item().map_err(InfoError::ReadPart)? {
+            match data {
+                PartData::Directory(..) => dirs += 1,
+                PartData::Symlink(..) => links += 1,
+                PartData::HardLink(..) => hard_links += 1,
+                PartData::CharDevice(..)
+                | PartData::BlockDevice(..)
+                | PartData::Fifo(..)
+                | PartData::Socket(..) => specials += 1,
+                PartData::File(path, _, mut r) => {
+                    files += 1;
+                    size += std::io::copy(&mut r, &mut sink())
+                        .map_err(|e| InfoError::ReadFile(display_path(&path), e))?;
+                }
+            }
+        }
+
+        println!(
+            concat!(
+                "  {} on {} ({} directories, {} files, {} symlinks, {} hard links, ",
+                "{} special files, {} bytes)"
+            ),
+            part.fs().escape_ascii(),
+            part.dev().escape_ascii(),
+            dirs,
+            files,
+            links,
+            hard_links,
+            specials,
+            size
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+}
+
+/// Represents an error when `info` command fails.
+#[derive(Debug, Error)]
+pub enum InfoError {
+    #[error(transparent)]
+    Open(#[from] OpenError),
+
+    #[error("couldn't read the next item")]
+    ReadDump(#[source] ReaderError),
+
+    #[error("couldn't read the next partition item")]
+    ReadPart(#[source] PartError),
+
+    #[error("couldn't read {0}")]
+    ReadFile(String, #[source] std::io::Error),
+}
@@ -0,0 +1,136 @@
+use crate::dump::ItemError;
+use obfw::kernel::Kernel;
+use obfw::DumpIndex;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Alignment of each segment in the output file, which is the page size of the PS4.
+const ALIGN: u64 = 0x4000;
+
+/// Size of ELF header.
+const EHDR_LEN: u64 = 64;
+
+/// Size of each program header.
+const PHDR_LEN: u64 = 56;
+
+pub fn run(args: Args) -> Result<(), KernelError> {
+    // Find the kernel.
+    let kernel =
+        crate::dump::read_item(&args.dump, DumpIndex::kernel)?.ok_or(KernelError::NoKernel)?;
+
+    // Write ELF.
+    let file = File::create(&args.output).map_err(KernelError::Create)?;
+    let mut file = BufWriter::new(file);
+
+    write_elf(&mut file, &kernel)
+        .and_then(|_| file.flush())
+        .map_err(KernelError::Write)?;
+
+    eprintln!(
+        "Wrote {} segments from {:#x} to {}",
+        kernel.segments.len(),
+        kernel.base,
+        args.output.display()
+    );
+
+    Ok(())
+}
+
+/// Write `kernel` as an ELF executable with one `PT_LOAD` for each segment.
+fn write_elf(w: &mut impl Write, kernel: &Kernel) -> std::io::Result<()> {
+    // Use the entry point from the original header if it is in the dump.
+    let entry = kernel
+        .segments
+        .iter()
+        .find(|s| s.addr == kernel.base)
+        .map(|s| s.data.as_slice())
+        .filter(|d| d.len() >= EHDR_LEN as usize && d.starts_with(b"\x7FELF"))
+        .map(|d| u64::from_le_bytes(d[24..32].try_into().unwrap()))
+        .unwrap_or(0);
+
+    // The offset of each segment must be congruent to its address modulo the alignment.
+    let phnum = kernel.segments.len() as u64;
+    let mut offset = EHDR_LEN + PHDR_LEN * phnum;
+    let mut offsets = Vec::with_capacity(kernel.segments.len());
+
+    for s in &kernel.segments {
+        offset = offset.next_multiple_of(ALIGN) + s.addr % ALIGN;
+        offsets.push(offset);
+        offset += s.data.len() as u64;
+    }
+
+    // Write ELF header.
+    let mut hdr = Vec::with_capacity(EHDR_LEN as usize);
+
+    hdr.extend_from_slice(b"\x7FELF");
+    hdr.extend_from_slice(&[2, 1, 1, 9]); // ELFCLASS64, ELFDATA2LSB, EV_CURRENT, ELFOSABI_FREEBSD.
+    hdr.extend_from_slice(&[0; 8]);
+    hdr.extend_from_slice(&2u16.to_le_bytes()); // ET_EXEC.
+    hdr.extend_from_slice(&62u16.to_le_bytes()); // EM_X86_64.
+    hdr.extend_from_slice(&1u32.to_le_bytes());
+    hdr.extend_from_slice(&entry.to_le_bytes());
+    hdr.extend_from_slice(&EHDR_LEN.to_le_bytes());
+    hdr.extend_from_slice(&0u64.to_le_bytes());
+    hdr.extend_from_slice(&0u32.to_le_bytes());
+    hdr.extend_from_slice(&(EHDR_LEN as u16).to_le_bytes());
+    hdr.extend_from_slice(&(PHDR_LEN as u16).to_le_bytes());
+    hdr.extend_from_slice(&u16::try_from(phnum).unwrap().to_le_bytes());
+    hdr.extend_from_slice(&[0; 6]);
+
+    // Write program headers.
+    for (s, &off) in kernel.segments.iter().zip(&offsets) {
+        let len = s.data.len() as u64;
+
+        hdr.extend_from_slice(&1u32.to_le_bytes()); // PT_LOAD.
+        hdr.extend_from_slice(&s.flags.to_le_bytes());
+        hdr.extend_from_slice(&off.to_le_bytes());
+        hdr.extend_from_slice(&s.addr.to_le_bytes());
+        hdr.extend_from_slice(&s.addr.to_le_bytes());
+        hdr.extend_from_slice(&len.to_le_bytes());
+        hdr.extend_from_slice(&len.to_le_bytes());
+        hdr.extend_from_slice(&ALIGN.to_le_bytes());
+    }
+
+    w.write_all(&hdr)?;
+
+    // Write segments.
+    let mut pos = hdr.len() as u64;
+
+    for (s, &off) in kernel.segments.iter().zip(&offsets) {
+        let pad = vec![0; (off - pos) as usize];
+
+        w.write_all(&pad)?;
+        w.write_all(&s.data)?;
+
+        pos = off + s.data.len() as u64;
+    }
+
+    Ok(())
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+
+    /// Path to write the ELF file.
+    output: PathBuf,
+}
+
+/// Represents an error when `kernel` command fails.
+#[derive(Debug, Error)]
+pub enum KernelError {
+    #[error(transparent)]
+    Read(#[from] ItemError),
+
+    #[error("the dump doesn't contain the kernel")]
+    NoKernel,
+
+    #[error("couldn't create the output file")]
+    Create(#[source] std::io::Error),
+
+    #[error("couldn't write the output file")]
+    Write(#[source] std::io::Error),
+}
@@ -0,0 +1,162 @@
+use crate::dump::{display_mode, display_path, major, minor, part_matches, OpenError};
+use obfw::ps4::{Metadata, PartData, PartError};
+use obfw::{ItemReader, ReaderError};
+use std::io::sink;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), LsError> {
+    let mut dump = crate::dump::open(&args.dump)?;
+    let mut found = false;
+
+    while let Some(item) = dump.next_item().map_err(LsError::ReadDump)? {
+        let mut part = match item {
+            ItemReader::Ps4Part(v) => v,
+            _ => continue,
+        };
+
+        // We still need to read the whole partition to reach the next one.
+        let list = match &args.partition {
+            Some(v) => part_matches(part.dev(), v),
+            None => true,
+        };
+
+        if list {
+            println!(
+                "{} ({}):",
+                part.dev().escape_ascii(),
+                part.fs().escape_ascii()
+            );
+            found = true;
+        }
+
+        while let Some(data) = part.next_item().map_err(LsError::ReadPart)? {
+            match data {
+                PartData::Directory(path, meta) => {
+                    if list {
+                        let attrs = attrs(args.long, meta.as_ref());
+
+                        println!("{attrs}{:>12} {}", "<DIR>", display_path(&path));
+                    }
+                }
+                PartData::Symlink(path, meta, target) => {
+                    if list {
+                        let attrs = attrs(args.long, meta.as_ref());
+
+                        println!(
+                            "{attrs}{:>12} {} -> {}",
+                            "<LNK>",
+                            display_path(&path),
+                            target.escape_ascii()
+                        );
+                    }
+                }
+                PartData::HardLink(path, meta, target) => {
+                    if list {
+                        let attrs = attrs(args.long, meta.as_ref());
+
+                        println!(
+                            "{attrs}{:>12} {} => {}",
+                            "<HLNK>",
+                            display_path(&path),
+                            display_path(&target)
+                        );
+                    }
+                }
+                PartData::CharDevice(path, meta, rdev)
+                | PartData::BlockDevice(path, meta, rdev) => {
+                    if list {
+                        let attrs = attrs(args.long, meta.as_ref());
+
+                        println!(
+                            "{attrs}{:>12} {} ({}, {})",
+                            "<DEV>",
+                            display_path(&path),
+                            major(rdev),
+                            minor(rdev)
+                        );
+                    }
+                }
+                PartData::Fifo(path, meta) => {
+                    if list {
+                        let attrs = attrs(args.long, meta.as_ref());
+
+                        println!("{attrs}{:>12} {}", "<FIFO>", display_path(&path));
+                    }
+                }
+                PartData::Socket(path, meta) => {
+                    if list {
+                        let attrs = attrs(args.long, meta.as_ref());
+
+                        println!("{attrs}{:>12} {}", "<SOCK>", display_path(&path));
+                    }
+                }
+                PartData::File(path, meta, mut r) => {
+                    let size = std::io::copy(&mut r, &mut sink())
+                        .map_err(|e| LsError::ReadFile(display_path(&path), e))?;
+
+                    if list {
+                        let attrs = attrs(args.long, meta.as_ref());
+
+                        println!("{attrs}{size:>12} {}", display_path(&path));
+                    }
+                }
+            }
+        }
+    }
+
+    match args.partition {
+        Some(v) if !found => Err(LsError::PartitionNotFound(v)),
+        _ => Ok(()),
+    }
+}
+
+/// Returns mode, owner, group and modification time for long listing. Returns an empty string if
+/// `long` is `false`.
+fn attrs(long: bool, meta: Option<&Metadata>) -> String {
+    match meta {
+        _ if !long => String::new(),
+        Some(m) => format!(
+            "{} {:>5} {:>5} {:>11} ",
+            display_mode(m.mode),
+            m.uid,
+            m.gid,
+            m.mtime.sec
+        ),
+        None => format!("{:10} {:>5} {:>5} {:>11} ", "?", "?", "?", "?"),
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+
+    /// List only the partition mounted from this device (e.g. `/dev/da0x4.crypt` or
+    /// `da0x4.crypt`).
+    #[arg(short, long)]
+    partition: Option<String>,
+
+    /// Show mode, owner, group and modification time (in seconds since Unix epoch) of each item.
+    #[arg(short, long)]
+    long: bool,
+}
+
+/// Represents an error when `ls` command fails.
+#[derive(Debug, Error)]
+pub enum LsError {
+    #[error(transparent)]
+    Open(#[from] OpenError),
+
+    #[error("couldn't read the next item")]
+    ReadDump(#[source] ReaderError),
+
+    #[error("couldn't read the next partition item")]
+    ReadPart(#[source] PartError),
+
+    #[error("couldn't read {0}")]
+    ReadFile(String, #[source] std::io::Error),
+
+    #[error("no partition matched {0}")]
+    PartitionNotFound(String),
+}
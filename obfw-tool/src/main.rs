@@ -0,0 +1,110 @@
+use clap::{Parser, Subcommand};
+use std::error::Error;
+use std::fmt::Write;
+use std::process::ExitCode;
+
+mod device;
+mod diff;
+mod dump;
+mod extract;
+mod info;
+mod kernel;
+mod ls;
+mod modules;
+mod pack;
+mod syscalls;
+mod tar;
+mod verify;
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let r = match args.command {
+        Command::Info(v) => self::info::run(v).map_err(Box::<dyn Error>::from),
+        Command::Ls(v) => self::ls::run(v).map_err(Box::<dyn Error>::from),
+        Command::Extract(v) => self::extract::run(v).map_err(Box::<dyn Error>::from),
+        Command::Verify(v) => self::verify::run(v).map_err(Box::<dyn Error>::from),
+        Command::Pack(v) => self::pack::run(v).map_err(Box::<dyn Error>::from),
+        Command::Tar(v) => self::tar::run(v).map_err(Box::<dyn Error>::from),
+        Command::Diff(v) => {
+            // Same exit status as diff(1) so scripts can tell a difference from an error.
+            return match self::diff::run(v) {
+                Ok(true) => ExitCode::SUCCESS,
+                Ok(false) => ExitCode::from(1),
+                Err(e) => {
+                    report(&e);
+                    ExitCode::from(2)
+                }
+            };
+        }
+        Command::Kernel(v) => self::kernel::run(v).map_err(Box::<dyn Error>::from),
+        Command::Syscalls(v) => self::syscalls::run(v).map_err(Box::<dyn Error>::from),
+        Command::Modules(v) => self::modules::run(v).map_err(Box::<dyn Error>::from),
+        Command::Device(v) => self::device::run(v).map_err(Box::<dyn Error>::from),
+    };
+
+    match r {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            report(e.as_ref());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn report(e: &dyn Error) {
+    let mut msg = e.to_string();
+    let mut src = e.source();
+
+    while let Some(e) = src {
+        write!(msg, " -> {e}").unwrap();
+        src = e.source();
+    }
+
+    eprintln!("{msg}");
+}
+
+/// Inspect, extract and create firmware dumps.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show summary of a dump.
+    Info(self::info::Args),
+
+    /// List all items in a dump.
+    Ls(self::ls::Args),
+
+    /// Extract partitions from a dump.
+    Extract(self::extract::Args),
+
+    /// Check integrity of a dump.
+    Verify(self::verify::Args),
+
+    /// Create a dump from local directories.
+    Pack(self::pack::Args),
+
+    /// Write a dump as a tar archive to standard output.
+    Tar(self::tar::Args),
+
+    /// Show differences between two dumps.
+    ///
+    /// Exits with 0 if the dumps are the same, 1 if they differ or 2 on error.
+    Diff(self::diff::Args),
+
+    /// Write the kernel image in a dump as an ELF file.
+    Kernel(self::kernel::Args),
+
+    /// Write the syscall table in a dump as JSON to standard output.
+    Syscalls(self::syscalls::Args),
+
+    /// List kernel modules in a dump and optionally extract their images.
+    Modules(self::modules::Args),
+
+    /// Write content of a raw device (e.g. sflash) in a dump to a file.
+    Device(self::device::Args),
+}
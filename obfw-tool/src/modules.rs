@@ -0,0 +1,89 @@
+use crate::dump::ItemError;
+use obfw::kernel::Module;
+use obfw::DumpIndex;
+use std::fs::{create_dir_all, write};
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), ModulesError> {
+    // Find the list.
+    let modules =
+        crate::dump::read_item(&args.dump, DumpIndex::modules)?.ok_or(ModulesError::NoModules)?;
+
+    // List modules.
+    for m in &modules {
+        let image = match m.image {
+            Some(_) => "",
+            None => " (no image)",
+        };
+
+        println!(
+            "{:#018x} {:>10} {}{}",
+            m.addr,
+            m.size,
+            m.name.escape_ascii(),
+            image
+        );
+    }
+
+    // Extract images.
+    let output = match args.output {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    create_dir_all(&output).map_err(|e| ModulesError::CreateDirectory(output.clone(), e))?;
+
+    for m in &modules {
+        let image = match &m.image {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let path = output.join(file_name(m));
+
+        write(&path, image).map_err(|e| ModulesError::WriteImage(path, e))?;
+    }
+
+    Ok(())
+}
+
+/// Returns a file name for the image of `m`. The name of the module is not trusted since it is
+/// from the dump.
+fn file_name(m: &Module) -> String {
+    let name = match m.name.iter().rposition(|&b| b == b'/') {
+        Some(i) => &m.name[(i + 1)..],
+        None => m.name.as_slice(),
+    };
+
+    match String::from_utf8_lossy(name) {
+        n if n.is_empty() || n == "." || n == ".." => format!("{:x}", m.addr),
+        n => n.into_owned(),
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+
+    /// Extract the image of each module to this directory.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Represents an error when `modules` command fails.
+#[derive(Debug, Error)]
+pub enum ModulesError {
+    #[error(transparent)]
+    Read(#[from] ItemError),
+
+    #[error("the dump doesn't contain the kernel modules")]
+    NoModules,
+
+    #[error("couldn't create {0}")]
+    CreateDirectory(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't write {0}")]
+    WriteImage(PathBuf, #[source] std::io::Error),
+}
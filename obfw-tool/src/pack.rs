@@ -0,0 +1,89 @@
+use obfw::ps4::{walk, FileBlock, HostError, HostFs, WalkError};
+use obfw::DumpWriter;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), PackError> {
+    // Create the dump.
+    let file = File::create(&args.output).map_err(|e| PackError::Create(args.output.clone(), e))?;
+    let mut dump = DumpWriter::new(BufWriter::new(file))
+        .map_err(|e| PackError::Write(args.output.clone(), e))?;
+
+    for p in &args.parts {
+        eprintln!("Packing {} as {}", p.root.display(), p.dev);
+
+        let mut part = dump
+            .ps4_part(p.fs.as_bytes(), p.dev.as_bytes())
+            .map_err(|e| PackError::Write(args.output.clone(), e))?;
+
+        walk(&mut part, &mut HostFs::new(&p.root), FileBlock::Sparse).map_err(|e| match e {
+            WalkError::Source(e) => PackError::Read(e),
+            WalkError::Write(e) => PackError::Write(args.output.clone(), e),
+        })?;
+
+        part.finish()
+            .map_err(|e| PackError::Write(args.output.clone(), e))?;
+    }
+
+    // Write table of contents.
+    let mut w = dump
+        .finish()
+        .map_err(|e| PackError::Write(args.output.clone(), e))?;
+
+    w.flush().map_err(|e| PackError::Write(args.output, e))
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path of the dump to create.
+    output: PathBuf,
+
+    /// Add a partition from a local directory in the form of `FS:DEV:DIR` (e.g.
+    /// `ufs:/dev/da0x4.crypt:system`). Can be specified multiple times.
+    #[arg(short, long = "part", value_name = "FS:DEV:DIR", required = true)]
+    parts: Vec<PartArg>,
+}
+
+/// Value of `--part`.
+#[derive(Clone)]
+struct PartArg {
+    fs: String,
+    dev: String,
+    root: PathBuf,
+}
+
+impl FromStr for PartArg {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut iter = s.splitn(3, ':');
+        let (fs, dev, root) = match (iter.next(), iter.next(), iter.next()) {
+            (Some(fs), Some(dev), Some(root)) if !fs.is_empty() && !root.is_empty() => {
+                (fs, dev, root)
+            }
+            _ => return Err("expect FS:DEV:DIR"),
+        };
+
+        Ok(Self {
+            fs: fs.into(),
+            dev: dev.into(),
+            root: root.into(),
+        })
+    }
+}
+
+/// Represents an error when `pack` command fails.
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("couldn't create {}", .0.display())]
+    Create(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't write {}", .0.display())]
+    Write(PathBuf, #[source] std::io::Error),
+
+    #[error(transparent)]
+    Read(HostError),
+}
@@ -0,0 +1,38 @@
+use crate::dump::ItemError;
+use obfw::DumpIndex;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), SyscallsError> {
+    // Find the table.
+    let table =
+        crate::dump::read_item(&args.dump, DumpIndex::syscalls)?.ok_or(SyscallsError::NoTable)?;
+
+    // Write JSON.
+    let mut out = BufWriter::new(std::io::stdout().lock());
+
+    table
+        .write_json(&mut out)
+        .and_then(|_| out.flush())
+        .map_err(SyscallsError::Write)
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+}
+
+/// Represents an error when `syscalls` command fails.
+#[derive(Debug, Error)]
+pub enum SyscallsError {
+    #[error(transparent)]
+    Read(#[from] ItemError),
+
+    #[error("the dump doesn't contain the syscall table")]
+    NoTable,
+
+    #[error("couldn't write the output")]
+    Write(#[source] std::io::Error),
+}
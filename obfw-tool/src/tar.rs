@@ -0,0 +1,362 @@
+use crate::dump::{display_path, major, minor, part_matches, part_name, OpenError};
+use obfw::ps4::{Metadata, PartData, PartError};
+use obfw::{ItemReader, ReaderError};
+use std::io::{sink, BufWriter, Read, Write};
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), TarError> {
+    let mut dump = crate::dump::open(&args.dump)?;
+    let mut tar = Archive::new(BufWriter::new(std::io::stdout().lock()));
+    let mut data = Vec::new();
+    let mut found = false;
+
+    while let Some(item) = dump.next_item().map_err(TarError::ReadDump)? {
+        let mut part = match item {
+            ItemReader::Ps4Part(v) => v,
+            _ => continue,
+        };
+
+        // We still need to read the whole partition to reach the next one.
+        let export = match &args.partition {
+            Some(v) => part_matches(part.dev(), v),
+            None => true,
+        };
+
+        if export {
+            eprintln!("Exporting {}", part.dev().escape_ascii());
+            found = true;
+        }
+
+        // Each partition is a top-level directory.
+        let root = match part_name(part.dev()) {
+            Some(v) => v.into_owned().into_bytes(),
+            None if !export => Vec::new(),
+            None => {
+                return Err(TarError::InvalidPartition(
+                    part.dev().escape_ascii().to_string(),
+                ))
+            }
+        };
+        let name = |path: &[u8]| {
+            let mut name = root.clone();
+
+            name.extend_from_slice(path);
+            name
+        };
+
+        while let Some(item) = part.next_item().map_err(TarError::ReadPart)? {
+            let (path, meta, entry) = match item {
+                PartData::Directory(path, meta) => {
+                    let mut n = name(&path);
+
+                    n.push(b'/');
+
+                    (n, meta, Entry::Directory)
+                }
+                PartData::File(path, meta, mut r) => {
+                    // We need the size before writing the header so read the whole file first.
+                    let r = if export {
+                        data.clear();
+                        r.read_to_end(&mut data).map(|_| ())
+                    } else {
+                        std::io::copy(&mut r, &mut sink()).map(|_| ())
+                    };
+
+                    r.map_err(|e| TarError::ReadFile(display_path(&path), e))?;
+
+                    (name(&path), meta, Entry::File(&data))
+                }
+                PartData::Symlink(path, meta, target) => {
+                    (name(&path), meta, Entry::Symlink(target))
+                }
+                PartData::HardLink(path, meta, target) => {
+                    (name(&path), meta, Entry::HardLink(name(&target)))
+                }
+                PartData::CharDevice(path, meta, rdev) => {
+                    (name(&path), meta, Entry::CharDevice(rdev))
+                }
+                PartData::BlockDevice(path, meta, rdev) => {
+                    (name(&path), meta, Entry::BlockDevice(rdev))
+                }
+                PartData::Fifo(path, meta) => (name(&path), meta, Entry::Fifo),
+                PartData::Socket(path, _) => {
+                    if export {
+                        eprintln!("Skipping socket {}", display_path(&path));
+                    }
+
+                    continue;
+                }
+            };
+
+            if export {
+                tar.append(&path, meta.as_ref(), entry)
+                    .map_err(TarError::Write)?;
+            }
+        }
+    }
+
+    if let Some(v) = args.partition {
+        if !found {
+            return Err(TarError::PartitionNotFound(v));
+        }
+    }
+
+    tar.finish().map_err(TarError::Write)
+}
+
+/// Size of a block in tar archive.
+const BLOCK: usize = 512;
+
+/// Maximum value of 12 bytes octal field.
+const MAX_SIZE: u64 = 0o77777777777;
+
+/// Maximum value of 8 bytes octal field.
+const MAX_ID: u64 = 0o7777777;
+
+/// Writer for POSIX tar archive in pax format.
+///
+/// A pax extended header is written before the entry only when the entry cannot be represented by
+/// ustar header (e.g. long path or timestamp with fractional seconds).
+struct Archive<W> {
+    w: W,
+}
+
+impl<W: Write> Archive<W> {
+    fn new(w: W) -> Self {
+        Self { w }
+    }
+
+    fn append(
+        &mut self,
+        path: &[u8],
+        meta: Option<&Metadata>,
+        entry: Entry,
+    ) -> std::io::Result<()> {
+        // Use the default metadata if the dump was created by an old version.
+        let (mode, uid, gid, mtime) = match meta {
+            Some(m) => (m.mode & 0o7777, m.uid, m.gid, m.mtime),
+            None => match entry {
+                Entry::Directory => (0o755, 0, 0, Default::default()),
+                Entry::Symlink(_) => (0o777, 0, 0, Default::default()),
+                _ => (0o644, 0, 0, Default::default()),
+            },
+        };
+
+        let (ty, size, link, rdev) = match &entry {
+            Entry::Directory => (b'5', 0, None, None),
+            Entry::File(d) => (b'0', d.len() as u64, None, None),
+            Entry::Symlink(t) => (b'2', 0, Some(t.as_slice()), None),
+            Entry::HardLink(t) => (b'1', 0, Some(t.as_slice()), None),
+            Entry::CharDevice(v) => (b'3', 0, None, Some(*v)),
+            Entry::BlockDevice(v) => (b'4', 0, None, Some(*v)),
+            Entry::Fifo => (b'6', 0, None, None),
+        };
+
+        // Build extended header.
+        let mut pax = Vec::new();
+
+        if path.len() > 100 || !path.is_ascii() {
+            record(&mut pax, "path", path);
+        }
+
+        if let Some(v) = link.filter(|v| v.len() > 100 || !v.is_ascii()) {
+            record(&mut pax, "linkpath", v);
+        }
+
+        if size > MAX_SIZE {
+            record(&mut pax, "size", size.to_string().as_bytes());
+        }
+
+        if mtime.sec < 0 || mtime.nsec != 0 {
+            let v = match (mtime.sec, mtime.nsec) {
+                (s, 0) => format!("{s}"),
+                (s, n) if s < 0 => format!("-{}.{:09}", -(s + 1), 1_000_000_000 - n),
+                (s, n) => format!("{s}.{n:09}"),
+            };
+
+            record(&mut pax, "mtime", v.as_bytes());
+        }
+
+        for (k, v) in [("uid", uid), ("gid", gid)] {
+            if u64::from(v) > MAX_ID {
+                record(&mut pax, k, v.to_string().as_bytes());
+            }
+        }
+
+        // Path in the dump is not guaranteed to be UTF-8.
+        if std::str::from_utf8(path).is_err()
+            || link.is_some_and(|v| std::str::from_utf8(v).is_err())
+        {
+            record(&mut pax, "hdrcharset", b"BINARY");
+        }
+
+        if !pax.is_empty() {
+            let h = Header {
+                path: b"././@PaxHeader",
+                mode: 0o644,
+                uid: 0,
+                gid: 0,
+                mtime: 0,
+                ty: b'x',
+                size: pax.len() as u64,
+                link: b"",
+                rdev: None,
+            };
+
+            self.w.write_all(&h.encode())?;
+            self.write_data(&pax)?;
+        }
+
+        // Write the entry.
+        let h = Header {
+            path,
+            mode,
+            uid,
+            gid,
+            mtime: mtime.sec.clamp(0, MAX_SIZE as i64) as u64,
+            ty,
+            size,
+            link: link.unwrap_or(b""),
+            rdev,
+        };
+
+        self.w.write_all(&h.encode())?;
+
+        if let Entry::File(d) = entry {
+            self.write_data(d)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        // The archive ends with two zero blocks.
+        self.w.write_all(&[0; BLOCK * 2])?;
+        self.w.flush()
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let pad = data.len().next_multiple_of(BLOCK) - data.len();
+
+        self.w.write_all(data)?;
+        self.w.write_all(&[0; BLOCK][..pad])
+    }
+}
+
+/// Header of an entry in [`Archive`]. Values that are too large are truncated so they must also be
+/// written to the extended header.
+struct Header<'a> {
+    path: &'a [u8],
+    mode: u16,
+    uid: u32,
+    gid: u32,
+    mtime: u64,
+    ty: u8,
+    size: u64,
+    link: &'a [u8],
+    rdev: Option<u64>,
+}
+
+impl Header<'_> {
+    fn encode(&self) -> [u8; BLOCK] {
+        let mut h = [0; BLOCK];
+        let field = |h: &mut [u8; BLOCK], off: usize, len: usize, v: &[u8]| {
+            let n = v.len().min(len);
+
+            h[off..(off + n)].copy_from_slice(&v[..n]);
+        };
+
+        // Octal fields are terminated with NUL.
+        let octal = |h: &mut [u8; BLOCK], off: usize, len: usize, v: u64| {
+            field(h, off, len - 1, format!("{:01$o}", v, len - 1).as_bytes());
+        };
+
+        field(&mut h, 0, 100, self.path);
+        octal(&mut h, 100, 8, self.mode.into());
+        octal(&mut h, 108, 8, u64::from(self.uid).min(MAX_ID));
+        octal(&mut h, 116, 8, u64::from(self.gid).min(MAX_ID));
+        octal(&mut h, 124, 12, self.size.min(MAX_SIZE));
+        octal(&mut h, 136, 12, self.mtime);
+        h[156] = self.ty;
+        field(&mut h, 157, 100, self.link);
+        field(&mut h, 257, 8, b"ustar\x0000");
+
+        if let Some(v) = self.rdev {
+            octal(&mut h, 329, 8, major(v));
+            octal(&mut h, 337, 8, minor(v).min(MAX_ID));
+        }
+
+        // Checksum is calculated with the checksum field filled with spaces.
+        h[148..156].fill(b' ');
+
+        let sum: u32 = h.iter().map(|&b| u32::from(b)).sum();
+
+        octal(&mut h, 148, 7, sum.into());
+        h[154] = 0;
+
+        h
+    }
+}
+
+/// Append a record to the extended header. Each record is `LEN KEY=VALUE\n` where `LEN` is the
+/// length of the whole record, including itself.
+fn record(pax: &mut Vec<u8>, key: &str, value: &[u8]) {
+    let rest = 1 + key.len() + 1 + value.len() + 1;
+    let mut len = rest + 1;
+
+    while len != rest + len.to_string().len() {
+        len = rest + len.to_string().len();
+    }
+
+    pax.extend_from_slice(format!("{len} {key}=").as_bytes());
+    pax.extend_from_slice(value);
+    pax.push(b'\n');
+}
+
+/// Type-specific data of an entry in [`Archive`].
+enum Entry<'a> {
+    Directory,
+    File(&'a [u8]),
+    Symlink(Vec<u8>),
+    HardLink(Vec<u8>),
+    CharDevice(u64),
+    BlockDevice(u64),
+    Fifo,
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+
+    /// Export only the partition mounted from this device (e.g. `/dev/da0x4.crypt` or
+    /// `da0x4.crypt`).
+    #[arg(short, long)]
+    partition: Option<String>,
+}
+
+/// Represents an error when `tar` command fails.
+#[derive(Debug, Error)]
+pub enum TarError {
+    #[error(transparent)]
+    Open(#[from] OpenError),
+
+    #[error("couldn't read the next item")]
+    ReadDump(#[source] ReaderError),
+
+    #[error("couldn't read the next partition item")]
+    ReadPart(#[source] PartError),
+
+    #[error("couldn't read {0}")]
+    ReadFile(String, #[source] std::io::Error),
+
+    #[error("partition {0} doesn't have a valid name")]
+    InvalidPartition(String),
+
+    #[error("couldn't write the archive")]
+    Write(#[source] std::io::Error),
+
+    #[error("no partition matched {0}")]
+    PartitionNotFound(String),
+}
@@ -0,0 +1,296 @@
+use crate::dump::{display_path, OpenError};
+use obfw::device::RawDeviceError;
+use obfw::ps4::{PartData, PartError};
+use obfw::{DumpIndex, DumpItem, DumpReader, IndexError, ItemReader, ReaderError};
+use std::cell::Cell;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::rc::Rc;
+use thiserror::Error;
+
+pub fn run(args: Args) -> Result<(), VerifyError> {
+    // Open the dump.
+    let file = File::open(&args.dump).map_err(|e| OpenError::Open(args.dump.clone(), e))?;
+    let len = file
+        .metadata()
+        .map_err(|e| OpenError::Open(args.dump.clone(), e))?
+        .len();
+    let pos = Rc::new(Cell::new(0));
+    let file = Tracked {
+        inner: BufReader::new(file),
+        pos: pos.clone(),
+    };
+
+    let mut dump = DumpReader::new(file).map_err(|e| match e {
+        e if is_eof(&e) => VerifyError::Truncated(pos.get()),
+        e => VerifyError::Header(e),
+    })?;
+
+    // Walk the whole dump.
+    let mut info = None;
+    let mut parts = Vec::new();
+    let mut devices = Vec::new();
+    let mut others = Vec::new();
+    let mut items = 0;
+    let mut files = 0;
+    let mut size = 0;
+    let mut buf = vec![0; 0x10000];
+    let end = loop {
+        let off = pos.get();
+        let item = match dump.next_item() {
+            Ok(Some(v)) => v,
+            Ok(None) => break off,
+            Err(e) if is_eof(&e) => return Err(VerifyError::Truncated(pos.get())),
+            Err(e) => return Err(VerifyError::Item(off, e)),
+        };
+
+        let mut part = match item {
+            ItemReader::Ps4Part(v) => v,
+            ItemReader::SystemInfo(v) => {
+                info = Some(v);
+                others.push((DumpItem::SystemInfo, off));
+                items += 1;
+                continue;
+            }
+            ItemReader::Syscalls(_) => {
+                others.push((DumpItem::Syscalls, off));
+                items += 1;
+                continue;
+            }
+            ItemReader::Kernel(v) => {
+                others.push((DumpItem::Kernel, off));
+                items += 1 + v.segments.len() as u32;
+                continue;
+            }
+            ItemReader::Modules(v) => {
+                others.push((DumpItem::Modules, off));
+                items += 1 + v.len() as u32;
+                continue;
+            }
+            ItemReader::RawDevice(mut v) => {
+                match v.check_digest() {
+                    Ok(_) => {}
+                    Err(e) if is_eof(&e) => return Err(VerifyError::Truncated(pos.get())),
+                    Err(e) => return Err(VerifyError::RawDevice(off, e)),
+                }
+
+                // The dumper stops at the first read error so the content can be shorter.
+                if v.len() != v.size() {
+                    return Err(VerifyError::IncompleteDevice(off, v.len(), v.size()));
+                }
+
+                devices.push((off, v.len()));
+                items += 1;
+                size += v.len();
+                continue;
+            }
+        };
+
+        let mut offsets = Vec::new();
+
+        items += 1;
+
+        loop {
+            let off = pos.get();
+            let (path, mut r) = match part.next_item() {
+                Ok(Some(PartData::File(path, _, r))) => (path, r),
+                Ok(Some(_)) => {
+                    offsets.push((off, 0));
+                    items += 1;
+                    continue;
+                }
+                Ok(None) => break,
+                Err(e) if is_eof(&e) => return Err(VerifyError::Truncated(pos.get())),
+                Err(e) => return Err(VerifyError::PartItem(off, e)),
+            };
+
+            // Read the whole file so the digest is checked.
+            let mut len = 0;
+
+            loop {
+                let off = pos.get();
+                let n = match r.read(&mut buf) {
+                    Ok(v) => v,
+                    Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                        return Err(VerifyError::Truncated(pos.get()));
+                    }
+                    Err(e) => return Err(VerifyError::File(off, display_path(&path), e)),
+                };
+
+                if n == 0 {
+                    break;
+                }
+
+                len += u64::try_from(n).unwrap();
+            }
+
+            offsets.push((off, len));
+            items += 1;
+            files += 1;
+            size += len;
+        }
+
+        parts.push(offsets);
+    };
+
+    // The dump must end with the table of contents or the item count right after the end entry.
+    match dump.toc() {
+        Some(v) if v != end + 1 => return Err(VerifyError::TocOffset(v)),
+        Some(_) => {}
+        None if end + 1 + 4 != len => return Err(VerifyError::TrailingData(end + 1)),
+        None => {}
+    }
+
+    if items != dump.items() {
+        return Err(VerifyError::ItemCount(items, dump.items()));
+    }
+
+    // Check if table of contents matches with the actual items.
+    if dump.toc().is_some() {
+        let file = File::open(&args.dump).map_err(|e| OpenError::Open(args.dump.clone(), e))?;
+        let index = DumpIndex::new(BufReader::new(file)).map_err(VerifyError::Toc)?;
+
+        if index.system_info() != info.as_ref() {
+            return Err(VerifyError::TocSystemInfo);
+        }
+
+        if index.parts().len() != parts.len() {
+            return Err(VerifyError::TocPartitions(index.parts().len(), parts.len()));
+        }
+
+        let actual = index.raw_devices().iter().map(|d| (d.offset(), d.len()));
+
+        if !actual.eq(devices.iter().copied()) {
+            return Err(VerifyError::TocDevices);
+        }
+
+        for i in 0..index.others().len().max(others.len()) {
+            match (index.others().get(i), others.get(i)) {
+                (a, Some(b)) if a != Some(b) => return Err(VerifyError::TocItem(b.1)),
+                (Some(a), None) => return Err(VerifyError::TocItem(a.1)),
+                _ => {}
+            }
+        }
+
+        for (p, offsets) in index.parts().iter().zip(&parts) {
+            let actual = p.items().iter().map(|i| (i.offset(), i.len()));
+
+            if !actual.eq(offsets.iter().copied()) {
+                return Err(VerifyError::TocMismatch(p.offset()));
+            }
+        }
+    }
+
+    println!(
+        "{} is valid ({items} items, {files} files, {size} bytes)",
+        args.dump.display()
+    );
+
+    Ok(())
+}
+
+/// Returns `true` if `e` was caused by an unexpected end of file.
+fn is_eof(e: &(dyn Error + 'static)) -> bool {
+    let mut src = Some(e);
+
+    while let Some(e) = src {
+        if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            if e.kind() == ErrorKind::UnexpectedEof {
+                return true;
+            }
+        }
+
+        src = e.source();
+    }
+
+    false
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Path to the dump.
+    dump: PathBuf,
+}
+
+/// Wraps a reader to keep track of the current offset while it is owned by [`DumpReader`].
+struct Tracked<R> {
+    inner: R,
+    pos: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        self.pos.set(self.pos.get() + u64::try_from(n).unwrap());
+
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Tracked<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = self.inner.seek(pos)?;
+
+        self.pos.set(pos);
+
+        Ok(pos)
+    }
+}
+
+/// Represents an error when `verify` command fails.
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error(transparent)]
+    Open(#[from] OpenError),
+
+    #[error("invalid header at offset 0x0")]
+    Header(#[source] ReaderError),
+
+    #[error("unexpected end of dump at offset {0:#x}")]
+    Truncated(u64),
+
+    #[error("invalid item at offset {0:#x}")]
+    Item(u64, #[source] ReaderError),
+
+    #[error("invalid partition item at offset {0:#x}")]
+    PartItem(u64, #[source] PartError),
+
+    #[error("invalid raw device at offset {0:#x}")]
+    RawDevice(u64, #[source] RawDeviceError),
+
+    #[error("raw device at offset {0:#x} has {1} of {2} bytes")]
+    IncompleteDevice(u64, u64, u64),
+
+    #[error("invalid content of {1} at offset {0:#x}")]
+    File(u64, String, #[source] std::io::Error),
+
+    #[error("unexpected data at offset {0:#x}")]
+    TrailingData(u64),
+
+    #[error("table of contents at offset {0:#x} is not right after the end entry")]
+    TocOffset(u64),
+
+    #[error("found {0} items but the dump says {1}")]
+    ItemCount(u32, u32),
+
+    #[error("couldn't load table of contents")]
+    Toc(#[source] IndexError),
+
+    #[error("table of contents doesn't match the system information")]
+    TocSystemInfo,
+
+    #[error("table of contents has {0} partitions but found {1}")]
+    TocPartitions(usize, usize),
+
+    #[error("table of contents doesn't match the raw devices")]
+    TocDevices,
+
+    #[error("table of contents doesn't match the item at offset {0:#x}")]
+    TocItem(u64),
+
+    #[error("table of contents doesn't match the partition at offset {0:#x}")]
+    TocMismatch(u64),
+}
@@ -0,0 +1,54 @@
+//! CRC-32 (IEEE 802.3) with a 4-bit lookup table.
+//!
+//! The table is 64 bytes instead of 1 KiB of the usual byte-wise table to fit in the payload.
+
+/// Calculates CRC-32 of the data that was passed to [`Crc32::update()`].
+#[derive(Clone, Copy)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    const TABLE: [u32; 16] = {
+        let mut table = [0; 16];
+        let mut i = 0;
+
+        while i < 16 {
+            let mut c = i as u32;
+            let mut j = 0;
+
+            while j < 4 {
+                c = if c & 1 != 0 {
+                    (c >> 1) ^ 0xEDB88320
+                } else {
+                    c >> 1
+                };
+
+                j += 1;
+            }
+
+            table[i] = c;
+            i += 1;
+        }
+
+        table
+    };
+
+    pub fn new() -> Self {
+        Self(!0)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut c = self.0;
+
+        for &b in data {
+            c ^= u32::from(b);
+            c = (c >> 4) ^ Self::TABLE[(c & 0xF) as usize];
+            c = (c >> 4) ^ Self::TABLE[(c & 0xF) as usize];
+        }
+
+        self.0 = c;
+    }
+
+    pub fn finish(self) -> u32 {
+        !self.0
+    }
+}
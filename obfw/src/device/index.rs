@@ -0,0 +1,187 @@
+use super::RawDeviceError;
+use crate::crc32::Crc32;
+use crate::ps4::{IndexedBlock, IndexedFile, IndexedItem, PartIndex};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use std::vec;
+use std::vec::Vec;
+
+/// Offsets of the content of a raw device in the dump.
+#[derive(Debug)]
+pub struct RawDeviceIndex {
+    offset: u64,
+    name: Vec<u8>,
+    size: u64,
+    item: IndexedItem,
+}
+
+impl RawDeviceIndex {
+    /// `offset` is the offset of the item in the dump and `ver` is its version. The `dump` must be
+    /// at the item data. On success the `dump` will be at the next item.
+    pub(crate) fn new<F: Read + Seek>(
+        offset: u64,
+        ver: u8,
+        dump: &mut F,
+    ) -> Result<Self, RawDeviceError> {
+        if ver != 1 {
+            return Err(RawDeviceError::UnknownVersion(ver));
+        }
+
+        // Read header.
+        let name = PartIndex::load_str(dump).map_err(RawDeviceError::Read)?;
+        let size = PartIndex::load_u64(dump).map_err(RawDeviceError::Read)?;
+
+        // Read blocks.
+        let mut blocks = Vec::new();
+        let mut pos = 0;
+
+        loop {
+            let offset = dump.stream_position().map_err(RawDeviceError::Seek)?;
+            let mut len = [0u8; 2];
+
+            dump.read_exact(&mut len).map_err(RawDeviceError::Read)?;
+
+            // Check if end.
+            let len = u16::from_le_bytes(len);
+
+            if len == 0 {
+                break;
+            }
+
+            dump.seek(SeekFrom::Current(len.into()))
+                .map_err(RawDeviceError::Seek)?;
+
+            blocks.push(IndexedBlock {
+                offset,
+                pos,
+                len: len.into(),
+            });
+
+            pos += u64::from(len);
+        }
+
+        // Skip digest.
+        dump.seek(SeekFrom::Current(4))
+            .map_err(RawDeviceError::Seek)?;
+
+        Ok(Self {
+            offset,
+            name,
+            size,
+            item: IndexedItem::raw(offset, pos, blocks),
+        })
+    }
+
+    /// Load the index from the table of contents. See [`RawDeviceWriter`] for the format.
+    ///
+    /// [`RawDeviceWriter`]: super::RawDeviceWriter
+    pub(crate) fn load<R: Read>(offset: u64, toc: &mut R) -> Result<Self, Error> {
+        let name = PartIndex::load_str(toc)?;
+        let size = PartIndex::load_u64(toc)?;
+        let len = PartIndex::load_u64(toc)?;
+        let count = PartIndex::load_u32(toc)?;
+        let mut blocks = Vec::new();
+        let mut pos = 0;
+
+        for _ in 0..count {
+            let offset = PartIndex::load_u64(toc)?;
+            let len = PartIndex::load_u32(toc)?;
+
+            blocks.push(IndexedBlock { offset, pos, len });
+            pos += u64::from(len);
+        }
+
+        if pos != len {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid device length"));
+        }
+
+        Ok(Self {
+            offset,
+            name,
+            size,
+            item: IndexedItem::raw(offset, len, blocks),
+        })
+    }
+
+    /// Returns the offset of the item in the dump.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the name of the device (e.g. `/dev/sflash0`).
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// Returns the size of the device reported by the kernel. This can be different from
+    /// [`Self::len()`] if the dumper could not read the whole device.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the size of the content in the dump.
+    pub fn len(&self) -> u64 {
+        self.item.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.item.is_empty()
+    }
+
+    pub fn blocks(&self) -> &[IndexedBlock] {
+        self.item.blocks()
+    }
+
+    pub(crate) fn open<'a, F>(&'a self, dump: &'a mut F) -> IndexedFile<'a, F> {
+        IndexedFile::new(dump, &self.item)
+    }
+
+    /// Returns the offset right after this item.
+    pub(crate) fn end(&self) -> u64 {
+        self.digest_offset() + 4
+    }
+
+    /// Reads the whole content and compare its CRC-32 with the digest in the dump.
+    pub(crate) fn check_digest<F: Read + Seek>(&self, dump: &mut F) -> Result<(), RawDeviceError> {
+        let mut file = self.open(dump);
+        let mut crc = Crc32::new();
+        let mut buf = vec![0; 0x10000];
+
+        loop {
+            let n = file.read(&mut buf).map_err(RawDeviceError::Read)?;
+
+            if n == 0 {
+                break;
+            }
+
+            crc.update(&buf[..n]);
+        }
+
+        // Read digest.
+        let mut digest = [0u8; 4];
+
+        dump.seek(SeekFrom::Start(self.digest_offset()))
+            .map_err(RawDeviceError::Seek)?;
+        dump.read_exact(&mut digest).map_err(RawDeviceError::Read)?;
+
+        if u32::from_le_bytes(digest) != crc.finish() {
+            Err(RawDeviceError::DigestMismatch)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the offset of the digest, which is right after the end block.
+    fn digest_offset(&self) -> u64 {
+        let end = match self.item.blocks().last() {
+            Some(b) => b.offset + 2 + u64::from(b.len),
+            None => {
+                // Type, version, name and size.
+                let name: u64 = self.name.len().try_into().unwrap();
+
+                self.offset + 2 + 8 + name + 8
+            }
+        };
+
+        end + 2
+    }
+}
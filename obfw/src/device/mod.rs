@@ -0,0 +1,13 @@
+#[cfg(feature = "read")]
+pub use self::index::*;
+#[cfg(feature = "read")]
+pub use self::reader::*;
+#[cfg(feature = "write")]
+pub use self::writer::*;
+
+#[cfg(feature = "read")]
+mod index;
+#[cfg(feature = "read")]
+mod reader;
+#[cfg(feature = "write")]
+mod writer;
@@ -0,0 +1,74 @@
+use super::RawDeviceIndex;
+use crate::ps4::IndexedFile;
+use std::io::{Read, Seek};
+use thiserror::Error;
+
+/// Provides [`Read`] and [`Seek`] implementation for a raw device in the dump.
+///
+/// The blocks are indexed when constructing so the content can be read in any order.
+#[derive(Debug)]
+pub struct RawDeviceReader<'a, F> {
+    dump: &'a mut F,
+    index: RawDeviceIndex,
+}
+
+impl<'a, F: Read + Seek> RawDeviceReader<'a, F> {
+    /// `offset` is the offset of the item in the dump.
+    pub(crate) fn new(dump: &'a mut F, offset: u64, ver: u8) -> Result<Self, RawDeviceError> {
+        let index = RawDeviceIndex::new(offset, ver, dump)?;
+
+        Ok(Self { dump, index })
+    }
+
+    pub fn index(&self) -> &RawDeviceIndex {
+        &self.index
+    }
+
+    /// Returns the name of the device (e.g. `/dev/sflash0`).
+    pub fn name(&self) -> &[u8] {
+        self.index.name()
+    }
+
+    /// Returns the size of the device reported by the kernel.
+    pub fn size(&self) -> u64 {
+        self.index.size()
+    }
+
+    /// Returns the size of the content in the dump.
+    pub fn len(&self) -> u64 {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn open(&mut self) -> IndexedFile<'_, F> {
+        self.index.open(self.dump)
+    }
+
+    pub fn into_index(self) -> RawDeviceIndex {
+        self.index
+    }
+
+    /// Reads the whole content and compare its CRC-32 with the digest in the dump.
+    pub fn check_digest(&mut self) -> Result<(), RawDeviceError> {
+        self.index.check_digest(self.dump)
+    }
+}
+
+/// Represents an error when reading a raw device fails.
+#[derive(Debug, Error)]
+pub enum RawDeviceError {
+    #[error("unknown version {0}")]
+    UnknownVersion(u8),
+
+    #[error("couldn't read the specified file")]
+    Read(#[source] std::io::Error),
+
+    #[error("couldn't seek the specified file")]
+    Seek(#[source] std::io::Error),
+
+    #[error("digest mismatch")]
+    DigestMismatch,
+}
@@ -0,0 +1,95 @@
+use crate::crc32::Crc32;
+use crate::{DumpItem, DumpSink, DumpWriter};
+
+/// Provides methods to write content of a raw device.
+///
+/// The content is stored with the same blocks as [`FileBlock::Uncompressed`] followed by CRC-32
+/// of the content. The entry in the table of contents contains the device name, its size, the
+/// length of the content and the list of blocks in the same format as a file in [`PartWriter`].
+///
+/// [`FileBlock::Uncompressed`]: crate::ps4::FileBlock::Uncompressed
+/// [`PartWriter`]: crate::ps4::PartWriter
+#[must_use]
+pub struct RawDeviceWriter<'a, S> {
+    dump: &'a mut DumpWriter<S>,
+    crc: Crc32,
+    entry: usize,
+    toc: usize,
+    len: u64,
+    blocks: u32,
+}
+
+impl<'a, S: DumpSink> RawDeviceWriter<'a, S> {
+    /// Maximum size of data in each block.
+    pub const BLOCK_SIZE: usize = 0xFFFF;
+
+    pub(crate) fn new(
+        dump: &'a mut DumpWriter<S>,
+        offset: u64,
+        name: &[u8],
+        size: u64,
+    ) -> Result<Self, S::Error> {
+        dump.write_str(name)?;
+        dump.write(&size.to_le_bytes())?;
+
+        // Add to table of contents.
+        let toc = dump.toc();
+        let entry = toc.begin(DumpItem::RawDevice, offset);
+
+        toc.push_str(name);
+        toc.push(&size.to_le_bytes());
+
+        let pos = toc.len();
+
+        toc.push(&0u64.to_le_bytes());
+        toc.push(&0u32.to_le_bytes());
+
+        Ok(Self {
+            dump,
+            crc: Crc32::new(),
+            entry,
+            toc: pos,
+            len: 0,
+            blocks: 0,
+        })
+    }
+
+    /// Append `data` to the content. Data larger than [`Self::BLOCK_SIZE`] will be split into
+    /// multiple blocks.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), S::Error> {
+        for b in data.chunks(Self::BLOCK_SIZE) {
+            let offset = self.dump.offset();
+            let len: u16 = b.len().try_into().unwrap();
+
+            self.crc.update(b);
+            self.dump.write(&len.to_le_bytes())?;
+            self.dump.write(b)?;
+
+            // Add to table of contents.
+            let toc = self.dump.toc();
+
+            toc.push(&offset.to_le_bytes());
+            toc.push(&u32::from(len).to_le_bytes());
+
+            self.len += u64::from(len);
+            self.blocks += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Write empty block to mark the end of content followed by CRC-32 of the content.
+    pub fn finish(self) -> Result<(), S::Error> {
+        self.dump.write(&0u16.to_le_bytes())?;
+        self.dump.write(&self.crc.finish().to_le_bytes())?;
+
+        // Update table of contents.
+        let toc = self.dump.toc();
+
+        toc.patch(self.toc, &self.len.to_le_bytes());
+        toc.patch(self.toc + 8, &self.blocks.to_le_bytes());
+        toc.end(self.entry);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+use crate::device::{RawDeviceError, RawDeviceIndex};
+use crate::kernel::{Kernel, Module};
+use crate::ps4::{IndexedFile, PartError, PartIndex, PartItem};
+use crate::{DumpItem, DumpReader, ItemReader, ReaderError, SyscallTable, SystemInfo};
+use std::io::{Read, Seek, SeekFrom};
+use std::vec::Vec;
+use thiserror::Error;
+
+/// Provides random access to a firmware dump.
+///
+/// The index is loaded from the table of contents if the dump has it. Otherwise the dump is
+/// scanned once when constructing. File content is not read during the scan so this is fast even
+/// on a large dump.
+pub struct DumpIndex<F> {
+    file: F,
+    items: u32,
+    info: Option<SystemInfo>,
+    parts: Vec<PartIndex>,
+    devices: Vec<RawDeviceIndex>,
+    others: Vec<(DumpItem, u64)>,
+}
+
+impl<F: Read + Seek> DumpIndex<F> {
+    pub fn new(mut file: F) -> Result<Self, IndexError> {
+        let mut dump = DumpReader::new(&mut file).map_err(IndexError::Reader)?;
+        let items = dump.items();
+        let i = match dump.toc() {
+            Some(v) => Self::load(dump.file(), v).map_err(IndexError::ReadToc)?,
+            None => Self::scan_items(dump)?,
+        };
+
+        Ok(Self {
+            file,
+            items,
+            info: i.info,
+            parts: i.parts,
+            devices: i.devices,
+            others: i.others,
+        })
+    }
+
+    /// Same as [`DumpIndex::new()`] but always scan the dump even if it has table of contents.
+    pub fn scan(mut file: F) -> Result<Self, IndexError> {
+        let dump = DumpReader::new(&mut file).map_err(IndexError::Reader)?;
+        let items = dump.items();
+        let i = Self::scan_items(dump)?;
+
+        Ok(Self {
+            file,
+            items,
+            info: i.info,
+            parts: i.parts,
+            devices: i.devices,
+            others: i.others,
+        })
+    }
+
+    /// Returns total items in this dump, including nested items.
+    pub fn items(&self) -> u32 {
+        self.items
+    }
+
+    /// Returns information of the system that produced the dump. Returns [`None`] if the dump was
+    /// created by an old version.
+    pub fn system_info(&self) -> Option<&SystemInfo> {
+        self.info.as_ref()
+    }
+
+    pub fn parts(&self) -> &[PartIndex] {
+        &self.parts
+    }
+
+    /// Returns partition that was mounted from `dev`.
+    pub fn part(&self, dev: &[u8]) -> Option<&PartIndex> {
+        self.parts.iter().find(|p| p.dev() == dev)
+    }
+
+    pub fn raw_devices(&self) -> &[RawDeviceIndex] {
+        &self.devices
+    }
+
+    /// Returns raw device with `name` (e.g. `/dev/sflash0`).
+    pub fn raw_device(&self, name: &[u8]) -> Option<&RawDeviceIndex> {
+        self.devices.iter().find(|d| d.name() == name)
+    }
+
+    /// Returns type and offset of the top-level items other than partitions and raw devices in the
+    /// same order as the dump.
+    pub fn others(&self) -> &[(DumpItem, u64)] {
+        &self.others
+    }
+
+    /// Reads the kernel image. Returns [`None`] if the dump does not have it.
+    pub fn kernel(&mut self) -> Result<Option<Kernel>, IndexError> {
+        self.read_item(DumpItem::Kernel, |i| match i {
+            ItemReader::Kernel(v) => Some(v),
+            _ => None,
+        })
+    }
+
+    /// Reads the syscall table. Returns [`None`] if the dump does not have it.
+    pub fn syscalls(&mut self) -> Result<Option<SyscallTable>, IndexError> {
+        self.read_item(DumpItem::Syscalls, |i| match i {
+            ItemReader::Syscalls(v) => Some(v),
+            _ => None,
+        })
+    }
+
+    /// Reads the list of kernel modules. Returns [`None`] if the dump does not have it.
+    pub fn modules(&mut self) -> Result<Option<Vec<Module>>, IndexError> {
+        self.read_item(DumpItem::Modules, |i| match i {
+            ItemReader::Modules(v) => Some(v),
+            _ => None,
+        })
+    }
+
+    /// Open content of the raw device with `name`.
+    pub fn open_raw_device(&mut self, name: &[u8]) -> Result<IndexedFile<'_, F>, IndexError> {
+        let dev = self
+            .devices
+            .iter()
+            .find(|d| d.name() == name)
+            .ok_or(IndexError::DeviceNotFound)?;
+
+        Ok(dev.open(&mut self.file))
+    }
+
+    /// Reads the whole content of the raw device with `name` and compare its CRC-32 with the
+    /// digest in the dump.
+    pub fn check_raw_device(&mut self, name: &[u8]) -> Result<(), IndexError> {
+        let dev = self
+            .devices
+            .iter()
+            .find(|d| d.name() == name)
+            .ok_or(IndexError::DeviceNotFound)?;
+
+        dev.check_digest(&mut self.file)
+            .map_err(|e| IndexError::RawDevice(dev.offset(), e))
+    }
+
+    /// Open a file at `path` in the partition that was mounted from `part`. A hard link will be
+    /// resolved to its target.
+    pub fn open_file(
+        &mut self,
+        part: &[u8],
+        path: &[u8],
+    ) -> Result<IndexedFile<'_, F>, IndexError> {
+        let part = self
+            .parts
+            .iter()
+            .find(|p| p.dev() == part)
+            .ok_or(IndexError::PartitionNotFound)?;
+        let mut item = part.get(path).ok_or(IndexError::ItemNotFound)?;
+
+        // The target of a hard link is always the file that was written before it.
+        if item.ty() == PartItem::HardLink {
+            item = item
+                .target()
+                .and_then(|t| part.get(t))
+                .ok_or(IndexError::ItemNotFound)?;
+        }
+
+        if item.ty() != PartItem::File {
+            return Err(IndexError::NotFile);
+        }
+
+        Ok(IndexedFile::new(&mut self.file, item))
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
+    /// Reads the first item with type `ty` and pass it to `f`, which must return [`None`] if the
+    /// item has unexpected type.
+    fn read_item<T>(
+        &mut self,
+        ty: DumpItem,
+        f: impl FnOnce(ItemReader<'_, &mut F>) -> Option<T>,
+    ) -> Result<Option<T>, IndexError> {
+        let offset = match self.others.iter().find(|i| i.0 == ty) {
+            Some(v) => v.1,
+            None => return Ok(None),
+        };
+
+        let mut dump =
+            DumpReader::at(&mut self.file, offset).map_err(|e| IndexError::Item(offset, e))?;
+        let item = dump
+            .next_item()
+            .map_err(|e| IndexError::Item(offset, e))?
+            .ok_or(IndexError::UnexpectedItem(offset))?;
+
+        f(item).map(Some).ok_or(IndexError::UnexpectedItem(offset))
+    }
+
+    fn scan_items(mut dump: DumpReader<&mut F>) -> Result<Items, IndexError> {
+        let mut items = Items::default();
+
+        loop {
+            let offset = dump
+                .file()
+                .stream_position()
+                .map_err(|e| IndexError::Reader(ReaderError::Read(e)))?;
+            let item = match dump.next_item().map_err(IndexError::Reader)? {
+                Some(v) => v,
+                None => break,
+            };
+
+            match item {
+                ItemReader::Ps4Part(r) => {
+                    let i = PartIndex::new(offset, r).map_err(|e| IndexError::Part(offset, e))?;
+
+                    items.parts.push(i);
+                }
+                ItemReader::SystemInfo(v) => {
+                    items.info = Some(v);
+                    items.others.push((DumpItem::SystemInfo, offset));
+                }
+                ItemReader::RawDevice(r) => items.devices.push(r.into_index()),
+                ItemReader::Kernel(_) => items.others.push((DumpItem::Kernel, offset)),
+                ItemReader::Syscalls(_) => items.others.push((DumpItem::Syscalls, offset)),
+                ItemReader::Modules(_) => items.others.push((DumpItem::Modules, offset)),
+            }
+        }
+
+        Ok(items)
+    }
+
+    fn load(file: &mut F, offset: u64) -> Result<Items, std::io::Error> {
+        let mut items = Items::default();
+
+        file.seek(SeekFrom::Start(offset))?;
+
+        loop {
+            // Read entry type.
+            let mut ty = 0u8;
+
+            file.read_exact(std::slice::from_mut(&mut ty))?;
+
+            if ty == DumpItem::End.into() {
+                break;
+            }
+
+            // Read entry header.
+            let mut offset = [0u8; 8];
+            let mut len = [0u8; 8];
+
+            file.read_exact(&mut offset)?;
+            file.read_exact(&mut len)?;
+
+            // Read entry data.
+            let offset = u64::from_le_bytes(offset);
+            let len = u64::from_le_bytes(len);
+            let mut data = Vec::new();
+
+            if file.by_ref().take(len).read_to_end(&mut data)? as u64 != len {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+
+            // Load entry. Skip unknown entry for forward compatibility.
+            if ty == DumpItem::Ps4Part.into() {
+                let ver = Self::item_version(file, offset)?;
+
+                items
+                    .parts
+                    .push(PartIndex::load(offset, ver, &mut data.as_slice())?);
+            } else if ty == DumpItem::SystemInfo.into() {
+                let ver = Self::item_version(file, offset)?;
+
+                items.info = Some(SystemInfo::read(&mut data.as_slice(), ver)?);
+                items.others.push((DumpItem::SystemInfo, offset));
+            } else if ty == DumpItem::RawDevice.into() {
+                items
+                    .devices
+                    .push(RawDeviceIndex::load(offset, &mut data.as_slice())?);
+            } else if let Ok(ty @ (DumpItem::Kernel | DumpItem::Syscalls | DumpItem::Modules)) =
+                DumpItem::try_from(ty)
+            {
+                items.others.push((ty, offset));
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Returns version of the item at `offset`. The format of the entry depends on this version.
+    fn item_version(file: &mut F, offset: u64) -> Result<u8, std::io::Error> {
+        let next = file.stream_position()?;
+        let mut ver = 0u8;
+
+        file.seek(SeekFrom::Start(offset + 1))?;
+        file.read_exact(std::slice::from_mut(&mut ver))?;
+        file.seek(SeekFrom::Start(next))?;
+
+        Ok(ver)
+    }
+}
+
+/// Top-level items that was indexed by [`DumpIndex`].
+#[derive(Default)]
+struct Items {
+    info: Option<SystemInfo>,
+    parts: Vec<PartIndex>,
+    devices: Vec<RawDeviceIndex>,
+    others: Vec<(DumpItem, u64)>,
+}
+
+/// Represents an error when [`DumpIndex`] fails.
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error("couldn't read the dump")]
+    Reader(#[source] ReaderError),
+
+    #[error("couldn't index partition at {0:#x}")]
+    Part(u64, #[source] PartError),
+
+    #[error("couldn't read raw device at {0:#x}")]
+    RawDevice(u64, #[source] RawDeviceError),
+
+    #[error("couldn't read table of contents")]
+    ReadToc(#[source] std::io::Error),
+
+    #[error("couldn't read item at {0:#x}")]
+    Item(u64, #[source] ReaderError),
+
+    #[error("unexpected item at {0:#x}")]
+    UnexpectedItem(u64),
+
+    #[error("partition not found")]
+    PartitionNotFound,
+
+    #[error("raw device not found")]
+    DeviceNotFound,
+
+    #[error("item not found")]
+    ItemNotFound,
+
+    #[error("the specified item is not a file")]
+    NotFile,
+}
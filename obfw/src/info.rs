@@ -0,0 +1,124 @@
+use crate::ps4::Timespec;
+use alloc::vec::Vec;
+
+/// Information of the system that produced the dump.
+///
+/// The item data is prefixed with its length so new fields can be appended without breaking the
+/// old readers.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SystemInfo {
+    /// Value of `kern.sdk_version` (e.g. `0x11008001` for 11.00).
+    pub sdk_version: u32,
+    /// Value of `kern.version`.
+    pub kernel: Vec<u8>,
+    /// Value of `hw.model`.
+    pub model: Vec<u8>,
+    /// Time when the dump was started.
+    pub time: Timespec,
+    /// Name and version of the program that created the dump.
+    pub dumper: Vec<u8>,
+}
+
+impl SystemInfo {
+    /// Returns major and minor version of the firmware (e.g. `(11, 0)` for 11.00).
+    pub fn firmware(&self) -> (u8, u8) {
+        let bcd = |v: u32| ((v >> 4) * 10 + (v & 0xf)) as u8;
+
+        (
+            bcd(self.sdk_version >> 24),
+            bcd((self.sdk_version >> 16) & 0xff),
+        )
+    }
+
+    #[cfg(feature = "write")]
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let str = |data: &mut Vec<u8>, v: &[u8]| {
+            data.extend_from_slice(&(v.len() as u64).to_le_bytes());
+            data.extend_from_slice(v);
+        };
+
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&self.sdk_version.to_le_bytes());
+        str(&mut data, &self.kernel);
+        str(&mut data, &self.model);
+        data.extend_from_slice(&self.time.sec.to_le_bytes());
+        data.extend_from_slice(&self.time.nsec.to_le_bytes());
+        str(&mut data, &self.dumper);
+
+        // Write length.
+        let len = (data.len() - 8) as u64;
+
+        data[..8].copy_from_slice(&len.to_le_bytes());
+
+        data
+    }
+
+    /// Reads the item data from `r`. `ver` is the version of the item.
+    #[cfg(feature = "read")]
+    pub(crate) fn read<R: std::io::Read>(r: &mut R, ver: u8) -> std::io::Result<Self> {
+        use std::io::{Error, ErrorKind, Read};
+
+        if ver != 1 {
+            return Err(Error::new(ErrorKind::InvalidData, "unknown item version"));
+        }
+
+        // Read the whole data.
+        let mut len = [0u8; 8];
+        let mut data = Vec::new();
+
+        r.read_exact(&mut len)?;
+
+        let len = u64::from_le_bytes(len);
+
+        if r.take(len).read_to_end(&mut data)? as u64 != len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        // Parse fields. Any data after the known fields is from the newer version.
+        let mut data = data.as_slice();
+        let mut sdk_version = [0u8; 4];
+
+        data.read_exact(&mut sdk_version)?;
+
+        let kernel = Self::read_str(&mut data)?;
+        let model = Self::read_str(&mut data)?;
+        let mut time = [0u8; 12];
+
+        data.read_exact(&mut time)?;
+
+        let dumper = Self::read_str(&mut data)?;
+
+        Ok(Self {
+            sdk_version: u32::from_le_bytes(sdk_version),
+            kernel,
+            model,
+            time: Timespec {
+                sec: i64::from_le_bytes(time[..8].try_into().unwrap()),
+                nsec: u32::from_le_bytes(time[8..].try_into().unwrap()),
+            },
+            dumper,
+        })
+    }
+
+    #[cfg(feature = "read")]
+    fn read_str(data: &mut &[u8]) -> std::io::Result<Vec<u8>> {
+        use std::io::{ErrorKind, Read};
+
+        let mut len = [0u8; 8];
+
+        data.read_exact(&mut len)?;
+
+        let len = usize::try_from(u64::from_le_bytes(len)).unwrap_or(usize::MAX);
+
+        if len > data.len() {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        let (v, rest) = data.split_at(len);
+
+        *data = rest;
+
+        Ok(v.to_vec())
+    }
+}
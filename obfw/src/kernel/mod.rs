@@ -0,0 +1,72 @@
+#[cfg(feature = "read")]
+pub use self::reader::*;
+#[cfg(feature = "write")]
+pub use self::writer::*;
+
+use alloc::vec::Vec;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+#[cfg(feature = "read")]
+mod reader;
+#[cfg(feature = "write")]
+mod writer;
+
+/// Segment is executable. Same as `PF_X` in ELF.
+pub const PF_X: u32 = 1;
+
+/// Segment is writable. Same as `PF_W` in ELF.
+pub const PF_W: u32 = 2;
+
+/// Segment is readable. Same as `PF_R` in ELF.
+pub const PF_R: u32 = 4;
+
+/// Type of item in the kernel dump.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+pub enum KernelItem {
+    End = 0,
+    /// Virtual address, length and protection followed by the data and its CRC-32.
+    Segment = 1,
+}
+
+/// Type of item in the kernel module list.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+pub enum ModuleItem {
+    End = 0,
+    /// Name, load address and size.
+    Module = 1,
+    /// Same as [`ModuleItem::Module`] followed by the memory image and its CRC-32.
+    ModuleImage = 2,
+}
+
+/// Image of the running kernel.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Kernel {
+    /// Virtual address of the ELF header.
+    pub base: u64,
+    pub segments: Vec<Segment>,
+}
+
+/// Mapped segment of the kernel.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Virtual address where the segment was mapped.
+    pub addr: u64,
+    /// Combination of [`PF_X`], [`PF_W`] and [`PF_R`].
+    pub flags: u32,
+    /// Content of the whole segment, including the zero-initialized part.
+    pub data: Vec<u8>,
+}
+
+/// Entry in the kernel linker file list.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Module {
+    /// File name of the module (e.g. `kernel`).
+    pub name: Vec<u8>,
+    /// Virtual address where the module was loaded.
+    pub addr: u64,
+    pub size: u64,
+    /// Memory image of the module. This is [`None`] if the payload did not dump it.
+    pub image: Option<Vec<u8>>,
+}
@@ -0,0 +1,159 @@
+use super::{Kernel, KernelItem, Module, ModuleItem, Segment};
+use crate::crc32::Crc32;
+use std::io::{ErrorKind, Read};
+use std::vec::Vec;
+use thiserror::Error;
+
+impl Kernel {
+    pub(crate) fn read<R: Read>(r: &mut R, ver: u8) -> Result<Self, KernelError> {
+        if ver != 1 {
+            return Err(KernelError::UnknownVersion(ver));
+        }
+
+        // Read base address.
+        let mut base = [0u8; 8];
+
+        r.read_exact(&mut base).map_err(KernelError::Read)?;
+
+        // Read segments.
+        let mut segments = Vec::new();
+
+        loop {
+            let mut ty = 0u8;
+
+            r.read_exact(std::slice::from_mut(&mut ty))
+                .map_err(KernelError::Read)?;
+
+            match KernelItem::try_from(ty) {
+                Ok(KernelItem::End) => break,
+                Ok(KernelItem::Segment) => segments.push(Self::read_segment(r)?),
+                Err(_) => return Err(KernelError::UnknownItem(ty)),
+            }
+        }
+
+        Ok(Self {
+            base: u64::from_le_bytes(base),
+            segments,
+        })
+    }
+
+    fn read_segment<R: Read>(r: &mut R) -> Result<Segment, KernelError> {
+        // Read header.
+        let mut hdr = [0u8; 20];
+
+        r.read_exact(&mut hdr).map_err(KernelError::Read)?;
+
+        let addr = u64::from_le_bytes(hdr[..8].try_into().unwrap());
+        let len = u64::from_le_bytes(hdr[8..16].try_into().unwrap());
+        let flags = u32::from_le_bytes(hdr[16..].try_into().unwrap());
+        let data = read_data(r, len, addr)?;
+
+        Ok(Segment { addr, flags, data })
+    }
+}
+
+impl Module {
+    /// Reads the whole module list.
+    pub(crate) fn read_list<R: Read>(r: &mut R, ver: u8) -> Result<Vec<Self>, KernelError> {
+        if ver != 1 {
+            return Err(KernelError::UnknownVersion(ver));
+        }
+
+        let mut modules = Vec::new();
+
+        loop {
+            // Read item type.
+            let mut ty = 0u8;
+
+            r.read_exact(std::slice::from_mut(&mut ty))
+                .map_err(KernelError::Read)?;
+
+            let image = match ModuleItem::try_from(ty) {
+                Ok(ModuleItem::End) => break,
+                Ok(ModuleItem::Module) => false,
+                Ok(ModuleItem::ModuleImage) => true,
+                Err(_) => return Err(KernelError::UnknownItem(ty)),
+            };
+
+            // Read header.
+            let mut len = [0u8; 8];
+            let mut name = Vec::new();
+            let mut hdr = [0u8; 16];
+
+            r.read_exact(&mut len).map_err(KernelError::Read)?;
+
+            let len = u64::from_le_bytes(len);
+
+            if r.by_ref()
+                .take(len)
+                .read_to_end(&mut name)
+                .map_err(KernelError::Read)? as u64
+                != len
+            {
+                return Err(KernelError::Read(ErrorKind::UnexpectedEof.into()));
+            }
+
+            r.read_exact(&mut hdr).map_err(KernelError::Read)?;
+
+            let addr = u64::from_le_bytes(hdr[..8].try_into().unwrap());
+            let size = u64::from_le_bytes(hdr[8..].try_into().unwrap());
+            let image = if image {
+                Some(read_data(r, size, addr)?)
+            } else {
+                None
+            };
+
+            modules.push(Self {
+                name,
+                addr,
+                size,
+                image,
+            });
+        }
+
+        Ok(modules)
+    }
+}
+
+/// Reads `len` bytes followed by its CRC-32. `addr` is used only for the error.
+fn read_data<R: Read>(r: &mut R, len: u64, addr: u64) -> Result<Vec<u8>, KernelError> {
+    let mut data = Vec::new();
+
+    if r.by_ref()
+        .take(len)
+        .read_to_end(&mut data)
+        .map_err(KernelError::Read)? as u64
+        != len
+    {
+        return Err(KernelError::Read(ErrorKind::UnexpectedEof.into()));
+    }
+
+    // Check digest.
+    let mut digest = [0u8; 4];
+    let mut crc = Crc32::new();
+
+    r.read_exact(&mut digest).map_err(KernelError::Read)?;
+    crc.update(&data);
+
+    if u32::from_le_bytes(digest) != crc.finish() {
+        return Err(KernelError::DigestMismatch(addr));
+    }
+
+    Ok(data)
+}
+
+/// Represents an error when the kernel image or the module list fails to read.
+#[derive(Debug, Error)]
+pub enum KernelError {
+    #[error("unknown version {0}")]
+    UnknownVersion(u8),
+
+    #[error("couldn't read the specified file")]
+    Read(#[source] std::io::Error),
+
+    #[error("unknown item type {0}")]
+    UnknownItem(u8),
+
+    #[error("digest mismatch on data at {0:#x}")]
+    DigestMismatch(u64),
+}
@@ -0,0 +1,94 @@
+use super::{KernelItem, ModuleItem};
+use crate::crc32::Crc32;
+use crate::{DumpSink, DumpWriter};
+
+/// Provides methods to write an image of the running kernel.
+pub struct KernelWriter<'a, S> {
+    dump: &'a mut DumpWriter<S>,
+}
+
+impl<'a, S: DumpSink> KernelWriter<'a, S> {
+    pub(crate) fn new(dump: &'a mut DumpWriter<S>, base: u64) -> Result<Self, S::Error> {
+        dump.write(&base.to_le_bytes())?;
+
+        Ok(Self { dump })
+    }
+
+    /// Write a segment that was mapped at `addr`. `flags` is a combination of [`PF_X`],
+    /// [`PF_W`] and [`PF_R`].
+    ///
+    /// [`PF_X`]: super::PF_X
+    /// [`PF_W`]: super::PF_W
+    /// [`PF_R`]: super::PF_R
+    pub fn segment(&mut self, addr: u64, flags: u32, data: &[u8]) -> Result<(), S::Error> {
+        let len: u64 = data.len().try_into().unwrap();
+        let mut crc = Crc32::new();
+
+        self.dump.write(&[KernelItem::Segment.into()])?;
+        self.dump.write(&addr.to_le_bytes())?;
+        self.dump.write(&len.to_le_bytes())?;
+        self.dump.write(&flags.to_le_bytes())?;
+        self.dump.write(data)?;
+
+        crc.update(data);
+
+        self.dump.write(&crc.finish().to_le_bytes())?;
+        self.dump.add_item();
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), S::Error> {
+        self.dump.write(&[KernelItem::End.into()])
+    }
+}
+
+/// Provides methods to write the kernel module list.
+pub struct ModuleWriter<'a, S> {
+    dump: &'a mut DumpWriter<S>,
+}
+
+impl<'a, S: DumpSink> ModuleWriter<'a, S> {
+    pub(crate) fn new(dump: &'a mut DumpWriter<S>) -> Self {
+        Self { dump }
+    }
+
+    /// Write a module that was loaded at `addr`. `image` is the memory image of the module, which
+    /// is `size` bytes.
+    pub fn module(
+        &mut self,
+        name: &[u8],
+        addr: u64,
+        size: u64,
+        image: Option<&[u8]>,
+    ) -> Result<(), S::Error> {
+        let ty = match image {
+            Some(_) => ModuleItem::ModuleImage,
+            None => ModuleItem::Module,
+        };
+
+        self.dump.write(&[ty.into()])?;
+        self.dump.write_str(name)?;
+        self.dump.write(&addr.to_le_bytes())?;
+        self.dump.write(&size.to_le_bytes())?;
+
+        if let Some(data) = image {
+            let mut crc = Crc32::new();
+
+            assert_eq!(u64::try_from(data.len()).unwrap(), size);
+
+            crc.update(data);
+
+            self.dump.write(data)?;
+            self.dump.write(&crc.finish().to_le_bytes())?;
+        }
+
+        self.dump.add_item();
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), S::Error> {
+        self.dump.write(&[ModuleItem::End.into()])
+    }
+}
@@ -1,27 +1,65 @@
 #![no_std]
 
+#[cfg(feature = "read")]
+pub use self::index::*;
+#[cfg(any(feature = "read", feature = "write"))]
+pub use self::info::*;
 #[cfg(feature = "read")]
 pub use self::reader::*;
+#[cfg(any(feature = "read", feature = "write"))]
+pub use self::syscall::*;
+#[cfg(feature = "write")]
+pub use self::writer::*;
 
 use core::fmt::{Display, Formatter};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 pub mod ps4;
 
+#[cfg(any(feature = "read", feature = "write"))]
+pub mod device;
+#[cfg(any(feature = "read", feature = "write"))]
+pub mod kernel;
+
+#[cfg(any(feature = "read", feature = "write"))]
+mod crc32;
+#[cfg(feature = "read")]
+mod index;
+#[cfg(any(feature = "read", feature = "write"))]
+mod info;
+#[cfg(any(feature = "read", feature = "write"))]
+mod lz4;
 #[cfg(feature = "read")]
 mod reader;
+#[cfg(any(feature = "read", feature = "write"))]
+mod syscall;
+#[cfg(feature = "write")]
+mod writer;
 
-#[cfg(feature = "read")]
+#[cfg(any(feature = "read", feature = "write"))]
+extern crate alloc;
+#[cfg(feature = "std")]
 extern crate std;
 
 pub const MAGIC: &[u8; 4] = b"\x7FOBF";
 
+/// Magic at the end of table of contents footer, which is right before the total number of items.
+///
+/// The footer is the offset of the table of contents followed by this magic. A dump without the
+/// table of contents always has the end entry (zero) where the last byte of this magic would be.
+pub const TOC_MAGIC: &[u8; 4] = b"\x7FOBI";
+
 /// Type of top-level item in the dump file.
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
 pub enum DumpItem {
     End = 0,
     Ps4Part = 1,
+    SystemInfo = 2,
+    Kernel = 3,
+    Syscalls = 4,
+    Modules = 5,
+    RawDevice = 6,
 }
 
 impl Display for DumpItem {
@@ -29,6 +67,11 @@ impl Display for DumpItem {
         let v = match self {
             Self::End => "",
             Self::Ps4Part => "PlayStation 4 partition",
+            Self::SystemInfo => "system information",
+            Self::Kernel => "kernel image",
+            Self::Syscalls => "syscall table",
+            Self::Modules => "kernel modules",
+            Self::RawDevice => "raw device",
         };
 
         f.write_str(v)
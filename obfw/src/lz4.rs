@@ -0,0 +1,188 @@
+//! Minimal implementation of LZ4 block format.
+//!
+//! We don't use an existing crate here because the encoder need to fit in the payload.
+use alloc::vec::Vec;
+
+/// Provides LZ4 compression for an input up to 0xFFFF bytes.
+#[cfg(feature = "write")]
+#[derive(Default)]
+pub(crate) struct Encoder {
+    table: Vec<u16>,
+}
+
+#[cfg(feature = "write")]
+impl Encoder {
+    const HASH_BITS: u32 = 12;
+    const MIN_MATCH: usize = 4;
+
+    /// The last match must start at least 12 bytes before the end of block.
+    const MF_LIMIT: usize = 12;
+
+    /// The last 5 bytes are always literals.
+    const LAST_LITERALS: usize = 5;
+
+    /// Compress `src` and append the result to `dst`.
+    pub fn compress(&mut self, src: &[u8], dst: &mut Vec<u8>) {
+        assert!(src.len() <= 0xFFFF);
+
+        // Reset hash table. Position 0 is a valid candidate so we don't need a special value for
+        // an empty slot since we always compare the data before use.
+        self.table.clear();
+        self.table.resize(1 << Self::HASH_BITS, 0);
+
+        // Find matches.
+        let mut anchor = 0;
+        let mut i = 0;
+
+        if src.len() > Self::MF_LIMIT {
+            let limit = src.len() - Self::MF_LIMIT;
+
+            while i < limit {
+                let seq = Self::read_u32(src, i);
+                let h = Self::hash(seq);
+                let cand = usize::from(self.table[h]);
+
+                self.table[h] = i.try_into().unwrap();
+
+                if cand >= i || Self::read_u32(src, cand) != seq {
+                    i += 1;
+                    continue;
+                }
+
+                // Extend the match.
+                let max = src.len() - Self::LAST_LITERALS - i;
+                let mut len = Self::MIN_MATCH;
+
+                while len < max && src[cand + len] == src[i + len] {
+                    len += 1;
+                }
+
+                Self::write_sequence(dst, &src[anchor..i], Some((i - cand, len)));
+
+                i += len;
+                anchor = i;
+            }
+        }
+
+        // Write last literals.
+        Self::write_sequence(dst, &src[anchor..], None);
+    }
+
+    fn write_sequence(dst: &mut Vec<u8>, lit: &[u8], m: Option<(usize, usize)>) {
+        // Write token.
+        let ml = m.map(|(_, l)| l - Self::MIN_MATCH).unwrap_or(0);
+        let token = (lit.len().min(15) << 4) | ml.min(15);
+
+        dst.push(token as u8);
+
+        // Write literals.
+        if lit.len() >= 15 {
+            Self::write_len(dst, lit.len() - 15);
+        }
+
+        dst.extend_from_slice(lit);
+
+        // Write match.
+        if let Some((off, _)) = m {
+            dst.extend_from_slice(&u16::try_from(off).unwrap().to_le_bytes());
+
+            if ml >= 15 {
+                Self::write_len(dst, ml - 15);
+            }
+        }
+    }
+
+    fn write_len(dst: &mut Vec<u8>, mut len: usize) {
+        while len >= 255 {
+            dst.push(255);
+            len -= 255;
+        }
+
+        dst.push(len as u8);
+    }
+
+    fn read_u32(src: &[u8], i: usize) -> u32 {
+        u32::from_le_bytes(src[i..(i + 4)].try_into().unwrap())
+    }
+
+    fn hash(seq: u32) -> usize {
+        (seq.wrapping_mul(2654435761) >> (32 - Self::HASH_BITS)) as usize
+    }
+}
+
+/// Decompress `src` and append the result to `dst`. Returns [`None`] if `src` is not a valid LZ4
+/// block or the output exceeds `max` bytes.
+#[cfg(feature = "read")]
+pub(crate) fn decompress(mut src: &[u8], dst: &mut Vec<u8>, max: usize) -> Option<()> {
+    let start = dst.len();
+
+    loop {
+        // Read token.
+        let (&token, rest) = src.split_first()?;
+
+        src = rest;
+
+        // Copy literals.
+        let mut len = usize::from(token >> 4);
+
+        if len == 15 {
+            len += read_len(&mut src)?;
+        }
+
+        if len > src.len() || dst.len() - start + len > max {
+            return None;
+        }
+
+        dst.extend_from_slice(&src[..len]);
+        src = &src[len..];
+
+        // The last sequence has only literals.
+        if src.is_empty() {
+            break Some(());
+        }
+
+        // Read match offset.
+        let off = usize::from(u16::from_le_bytes(src.get(..2)?.try_into().unwrap()));
+
+        src = &src[2..];
+
+        if off == 0 || off > dst.len() - start {
+            return None;
+        }
+
+        // Copy match. The source can overlap with the destination.
+        let mut len = usize::from(token & 0xF);
+
+        if len == 15 {
+            len += read_len(&mut src)?;
+        }
+
+        len += 4;
+
+        if dst.len() - start + len > max {
+            return None;
+        }
+
+        let from = dst.len() - off;
+
+        for i in 0..len {
+            dst.push(dst[from + i]);
+        }
+    }
+}
+
+#[cfg(feature = "read")]
+fn read_len(src: &mut &[u8]) -> Option<usize> {
+    let mut len = 0usize;
+
+    loop {
+        let (&v, rest) = src.split_first()?;
+
+        *src = rest;
+        len = len.checked_add(v.into())?;
+
+        if v != 255 {
+            break Some(len);
+        }
+    }
+}
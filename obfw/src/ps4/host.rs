@@ -0,0 +1,271 @@
+use super::{FsSource, Metadata, NodeInfo, NodeType, Timespec};
+use std::ffi::OsStr;
+use std::fs::{read_dir, read_link, symlink_metadata, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+use thiserror::Error;
+
+/// Implementation of [`FsSource`] for a directory on the host.
+///
+/// Children of each directory are sorted by name so the dump does not depend on the host
+/// filesystem. Hard links are detected only on Unix and only within the same device as the root.
+pub struct HostFs {
+    root: PathBuf,
+    dev: Option<u64>,
+}
+
+impl HostFs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            dev: None,
+        }
+    }
+}
+
+impl FsSource for HostFs {
+    type Node = PathBuf;
+    type File = HostFile;
+    type Error = HostError;
+
+    fn root(&mut self) -> Result<Self::Node, Self::Error> {
+        let m = std::fs::metadata(&self.root)
+            .map_err(|e| HostError::ReadMetadata(self.root.clone(), e))?;
+
+        self.dev = device(&m);
+
+        Ok(self.root.clone())
+    }
+
+    fn stat(&mut self, node: &Self::Node) -> Result<NodeInfo, Self::Error> {
+        // Follow the root if it is a symbolic link.
+        let m = if *node == self.root {
+            std::fs::metadata(node)
+        } else {
+            symlink_metadata(node)
+        };
+
+        let m = m.map_err(|e| HostError::ReadMetadata(node.clone(), e))?;
+        let mut info = info(&m);
+
+        if device(&m) != self.dev {
+            info.nlink = 1;
+        }
+
+        Ok(info)
+    }
+
+    fn list(
+        &mut self,
+        dir: &Self::Node,
+        out: &mut Vec<(Vec<u8>, Self::Node)>,
+    ) -> Result<(), Self::Error> {
+        let start = out.len();
+
+        for e in read_dir(dir).map_err(|e| HostError::ReadDirectory(dir.clone(), e))? {
+            let e = e.map_err(|e| HostError::ReadDirectory(dir.clone(), e))?;
+            let name = e.file_name();
+            let name = name_bytes(&name).ok_or_else(|| HostError::InvalidName(e.path()))?;
+
+            out.push((name.to_vec(), e.path()));
+        }
+
+        out[start..].sort_unstable();
+
+        Ok(())
+    }
+
+    fn open(&mut self, node: &Self::Node) -> Result<Self::File, Self::Error> {
+        match File::open(node) {
+            Ok(file) => Ok(HostFile {
+                path: node.clone(),
+                file,
+            }),
+            Err(e) => Err(HostError::Open(node.clone(), e)),
+        }
+    }
+
+    fn read(
+        &mut self,
+        file: &mut Self::File,
+        off: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let path = &file.path;
+        let file = &mut file.file;
+
+        file.seek(SeekFrom::Start(off))
+            .and_then(|_| file.read(buf))
+            .map_err(|e| HostError::Read(path.clone(), e))
+    }
+
+    fn read_link(&mut self, node: &Self::Node) -> Result<Vec<u8>, Self::Error> {
+        let target = read_link(node).map_err(|e| HostError::ReadLink(node.clone(), e))?;
+        let target =
+            name_bytes(target.as_os_str()).ok_or_else(|| HostError::InvalidName(node.clone()))?;
+
+        Ok(target.to_vec())
+    }
+}
+
+/// Opened file of [`HostFs`].
+pub struct HostFile {
+    path: PathBuf,
+    file: File,
+}
+
+#[cfg(unix)]
+fn info(m: &std::fs::Metadata) -> NodeInfo {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let ft = m.file_type();
+    let ty = if ft.is_dir() {
+        NodeType::Directory
+    } else if ft.is_file() {
+        NodeType::File
+    } else if ft.is_symlink() {
+        NodeType::Symlink
+    } else if ft.is_char_device() {
+        NodeType::CharDevice
+    } else if ft.is_block_device() {
+        NodeType::BlockDevice
+    } else if ft.is_fifo() {
+        NodeType::Fifo
+    } else if ft.is_socket() {
+        NodeType::Socket
+    } else {
+        NodeType::Other
+    };
+
+    let time = |sec, nsec: i64| Timespec {
+        sec,
+        nsec: nsec as u32,
+    };
+
+    NodeInfo {
+        ty,
+        meta: Metadata {
+            mode: m.mode() as u16,
+            uid: m.uid(),
+            gid: m.gid(),
+            flags: 0,
+            size: m.size(),
+            atime: time(m.atime(), m.atime_nsec()),
+            mtime: time(m.mtime(), m.mtime_nsec()),
+            ctime: time(m.ctime(), m.ctime_nsec()),
+            birthtime: m.created().map(timespec).unwrap_or_default(),
+        },
+        nlink: m.nlink(),
+        id: m.ino(),
+        rdev: m.rdev(),
+    }
+}
+
+#[cfg(not(unix))]
+fn info(m: &std::fs::Metadata) -> NodeInfo {
+    let ft = m.file_type();
+    let (ty, mode) = if ft.is_dir() {
+        (NodeType::Directory, 0o40755)
+    } else if ft.is_file() {
+        (NodeType::File, 0o100644)
+    } else if ft.is_symlink() {
+        (NodeType::Symlink, 0o120777)
+    } else {
+        (NodeType::Other, 0)
+    };
+
+    let mtime = m.modified().map(timespec).unwrap_or_default();
+
+    NodeInfo {
+        ty,
+        meta: Metadata {
+            mode: if m.permissions().readonly() {
+                mode & !0o222
+            } else {
+                mode
+            },
+            uid: 0,
+            gid: 0,
+            flags: 0,
+            size: m.len(),
+            atime: m.accessed().map(timespec).unwrap_or_default(),
+            mtime,
+            ctime: mtime,
+            birthtime: m.created().map(timespec).unwrap_or_default(),
+        },
+        nlink: 1,
+        id: 0,
+        rdev: 0,
+    }
+}
+
+#[cfg(unix)]
+fn device(m: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(m.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+fn timespec(t: SystemTime) -> Timespec {
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(v) => Timespec {
+            sec: v.as_secs() as i64,
+            nsec: v.subsec_nanos(),
+        },
+        Err(e) => {
+            let v = e.duration();
+
+            match v.subsec_nanos() {
+                0 => Timespec {
+                    sec: -(v.as_secs() as i64),
+                    nsec: 0,
+                },
+                n => Timespec {
+                    sec: -(v.as_secs() as i64) - 1,
+                    nsec: 1_000_000_000 - n,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn name_bytes(name: &OsStr) -> Option<&[u8]> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(name.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn name_bytes(name: &OsStr) -> Option<&[u8]> {
+    name.to_str().map(|v| v.as_bytes())
+}
+
+/// Represents an error when [`HostFs`] fails.
+#[derive(Debug, Error)]
+pub enum HostError {
+    #[error("couldn't get metadata of {}", .0.display())]
+    ReadMetadata(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't read {}", .0.display())]
+    ReadDirectory(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't read {}", .0.display())]
+    ReadLink(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't open {}", .0.display())]
+    Open(PathBuf, #[source] std::io::Error),
+
+    #[error("couldn't read {}", .0.display())]
+    Read(PathBuf, #[source] std::io::Error),
+
+    #[error("{} has a name that is not UTF-8", .0.display())]
+    InvalidName(PathBuf),
+}
@@ -0,0 +1,550 @@
+use super::{read_compressed, FileBlock, Metadata, PartError, PartItem, PartReader, SegmentItem};
+use core::cmp::min;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use std::vec::Vec;
+
+/// Offsets of all items in a PlayStation 4 partition dump.
+#[derive(Debug)]
+pub struct PartIndex {
+    offset: u64,
+    fs: Vec<u8>,
+    dev: Vec<u8>,
+    items: Vec<IndexedItem>,
+    paths: HashMap<Vec<u8>, usize>,
+}
+
+impl PartIndex {
+    /// `offset` is the offset of the partition item in the dump.
+    pub(crate) fn new<F: Read + Seek>(
+        offset: u64,
+        mut part: PartReader<'_, F>,
+    ) -> Result<Self, PartError> {
+        let fs = part.fs().to_vec();
+        let dev = part.dev().to_vec();
+        let mut items = Vec::new();
+        let mut paths = HashMap::new();
+
+        loop {
+            // Read item header.
+            let offset = part.dump().stream_position().map_err(PartError::Seek)?;
+            let (ty, path, meta) = match part.next_header()? {
+                Some(h) => (h.ty, h.path, h.meta),
+                None => break,
+            };
+
+            // Read blocks.
+            let mut block = None;
+            let mut len = 0;
+            let mut blocks = Vec::new();
+            let mut target = None;
+            let mut rdev = None;
+
+            match ty {
+                PartItem::Symlink | PartItem::HardLink => target = Some(part.read_link()?),
+                PartItem::CharDevice | PartItem::BlockDevice => rdev = Some(part.read_rdev()?),
+                _ => {}
+            }
+
+            if ty == PartItem::File {
+                // Only the SELF is indexed for decrypted file.
+                let (ty, decrypted) = match part.read_block_type()? {
+                    FileBlock::Decrypted => (part.read_inner_block_type()?, true),
+                    v => (v, false),
+                };
+
+                Self::index_blocks(part.dump(), ty, &mut blocks, &mut len)?;
+                Self::skip_digest(&mut part)?;
+
+                if decrypted {
+                    Self::skip_segments(&mut part, ty)?;
+                }
+
+                block = Some(ty);
+            }
+
+            paths.insert(path.clone(), items.len());
+            items.push(IndexedItem {
+                ty,
+                path,
+                offset,
+                meta,
+                block,
+                len,
+                blocks,
+                target,
+                rdev,
+            });
+        }
+
+        Ok(Self {
+            offset,
+            fs,
+            dev,
+            items,
+            paths,
+        })
+    }
+
+    /// Load the index from the table of contents. `ver` is the version of the partition item. See
+    /// [`PartWriter`] for the format.
+    ///
+    /// [`PartWriter`]: super::PartWriter
+    pub(crate) fn load<R: Read>(offset: u64, ver: u8, toc: &mut R) -> Result<Self, Error> {
+        let fs = Self::load_str(toc)?;
+        let dev = Self::load_str(toc)?;
+        let count = Self::load_u32(toc)?;
+        let mut items = Vec::new();
+        let mut paths = HashMap::new();
+
+        for _ in 0..count {
+            // Load item.
+            let mut ty = 0;
+
+            toc.read_exact(std::slice::from_mut(&mut ty))?;
+
+            let ty = PartItem::try_from(ty)
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "unknown item type"))?;
+            let path = Self::load_str(toc)?;
+            let offset = Self::load_u64(toc)?;
+            let meta = if ver >= 2 {
+                let mut buf = [0u8; Metadata::LEN];
+
+                toc.read_exact(&mut buf)?;
+
+                Some(Metadata::decode(&buf))
+            } else {
+                None
+            };
+
+            let mut block = 0;
+
+            toc.read_exact(std::slice::from_mut(&mut block))?;
+
+            let block = match ty {
+                PartItem::File => Some(
+                    FileBlock::try_from(block)
+                        .ok()
+                        .filter(|&v| v != FileBlock::Decrypted)
+                        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unknown file block"))?,
+                ),
+                _ => None,
+            };
+            let len = Self::load_u64(toc)?;
+
+            // Load blocks.
+            let count = Self::load_u32(toc)?;
+            let mut blocks = Vec::new();
+            let mut pos = 0;
+
+            for _ in 0..count {
+                let offset = Self::load_u64(toc)?;
+                let len = Self::load_u32(toc)?;
+
+                // Compressed block is always decompressed as a whole so it must fit in 16 bits.
+                if matches!(block, Some(FileBlock::Compressed | FileBlock::Sparse)) && len > 0xFFFF
+                {
+                    return Err(Error::new(ErrorKind::InvalidData, "invalid block length"));
+                }
+
+                blocks.push(IndexedBlock { offset, pos, len });
+                pos += u64::from(len);
+            }
+
+            if pos != len {
+                return Err(Error::new(ErrorKind::InvalidData, "invalid file length"));
+            }
+
+            // Load link target and device number.
+            let target = match ty {
+                PartItem::Symlink | PartItem::HardLink => Some(Self::load_str(toc)?),
+                _ => None,
+            };
+            let rdev = match ty {
+                PartItem::CharDevice | PartItem::BlockDevice => Some(Self::load_u64(toc)?),
+                _ => None,
+            };
+
+            paths.insert(path.clone(), items.len());
+            items.push(IndexedItem {
+                ty,
+                path,
+                offset,
+                meta,
+                block,
+                len,
+                blocks,
+                target,
+                rdev,
+            });
+        }
+
+        Ok(Self {
+            offset,
+            fs,
+            dev,
+            items,
+            paths,
+        })
+    }
+
+    /// Returns the offset of the partition item in the dump.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn fs(&self) -> &[u8] {
+        &self.fs
+    }
+
+    pub fn dev(&self) -> &[u8] {
+        &self.dev
+    }
+
+    /// Returns all items in the same order as they appear in the dump.
+    pub fn items(&self) -> &[IndexedItem] {
+        &self.items
+    }
+
+    pub fn get(&self, path: &[u8]) -> Option<&IndexedItem> {
+        self.paths.get(path).map(|&i| &self.items[i])
+    }
+
+    pub(crate) fn load_str<R: Read>(toc: &mut R) -> Result<Vec<u8>, Error> {
+        let len = Self::load_u64(toc)?;
+        let mut data = Vec::new();
+
+        if toc.take(len).read_to_end(&mut data)? as u64 != len {
+            Err(ErrorKind::UnexpectedEof.into())
+        } else {
+            Ok(data)
+        }
+    }
+
+    pub(crate) fn load_u64<R: Read>(toc: &mut R) -> Result<u64, Error> {
+        let mut v = [0u8; 8];
+
+        toc.read_exact(&mut v)?;
+
+        Ok(u64::from_le_bytes(v))
+    }
+
+    pub(crate) fn load_u32<R: Read>(toc: &mut R) -> Result<u32, Error> {
+        let mut v = [0u8; 4];
+
+        toc.read_exact(&mut v)?;
+
+        Ok(u32::from_le_bytes(v))
+    }
+
+    fn skip_digest<F: Read + Seek>(part: &mut PartReader<'_, F>) -> Result<(), PartError> {
+        if part.has_digest() {
+            part.dump()
+                .seek(SeekFrom::Current(4))
+                .map_err(PartError::Seek)?;
+        }
+
+        Ok(())
+    }
+
+    /// Skip the ELF header and decrypted segments of [`FileBlock::Decrypted`].
+    fn skip_segments<F: Read + Seek>(
+        part: &mut PartReader<'_, F>,
+        ty: FileBlock,
+    ) -> Result<(), PartError> {
+        let hdrs = Self::load_u64(part.dump()).map_err(PartError::Read)?;
+
+        part.dump()
+            .seek(SeekFrom::Current(hdrs.try_into().unwrap_or(i64::MAX)))
+            .map_err(PartError::Seek)?;
+
+        loop {
+            let mut item = [0u8; 1];
+
+            part.dump().read_exact(&mut item).map_err(PartError::Read)?;
+
+            match SegmentItem::try_from(item[0]) {
+                Ok(SegmentItem::End) => break Ok(()),
+                Ok(SegmentItem::Segment) => {}
+                Err(_) => return Err(PartError::UnknownSegmentItem(item[0])),
+            }
+
+            // Skip program header index, blocks and digest.
+            part.dump()
+                .seek(SeekFrom::Current(2))
+                .map_err(PartError::Seek)?;
+
+            Self::index_blocks(part.dump(), ty, &mut Vec::new(), &mut 0)?;
+            Self::skip_digest(part)?;
+        }
+    }
+
+    fn index_blocks<F: Read + Seek>(
+        dump: &mut F,
+        ty: FileBlock,
+        blocks: &mut Vec<IndexedBlock>,
+        pos: &mut u64,
+    ) -> Result<(), PartError> {
+        loop {
+            // Read block length.
+            let offset = dump.stream_position().map_err(PartError::Seek)?;
+            let mut len = [0u8; 2];
+
+            dump.read_exact(&mut len).map_err(PartError::Read)?;
+
+            // Check if EOF.
+            let len = u16::from_le_bytes(len);
+
+            if len == 0 {
+                break Ok(());
+            }
+
+            // Skip data.
+            let stored = match ty {
+                FileBlock::Uncompressed => len,
+                FileBlock::Compressed | FileBlock::Sparse => {
+                    let mut stored = [0u8; 2];
+
+                    dump.read_exact(&mut stored).map_err(PartError::Read)?;
+
+                    u16::from_le_bytes(stored)
+                }
+                FileBlock::Decrypted => unreachable!(),
+            };
+
+            dump.seek(SeekFrom::Current(stored.into()))
+                .map_err(PartError::Seek)?;
+
+            blocks.push(IndexedBlock {
+                offset,
+                pos: *pos,
+                len: len.into(),
+            });
+
+            *pos += u64::from(len);
+        }
+    }
+}
+
+/// Offsets of an item in the partition dump.
+#[derive(Debug)]
+pub struct IndexedItem {
+    ty: PartItem,
+    path: Vec<u8>,
+    offset: u64,
+    meta: Option<Metadata>,
+    block: Option<FileBlock>,
+    len: u64,
+    blocks: Vec<IndexedBlock>,
+    target: Option<Vec<u8>>,
+    rdev: Option<u64>,
+}
+
+impl IndexedItem {
+    /// Create a file item for content that is not in a partition (e.g. raw device). The content
+    /// must be stored as [`FileBlock::Uncompressed`].
+    pub(crate) fn raw(offset: u64, len: u64, blocks: Vec<IndexedBlock>) -> Self {
+        Self {
+            ty: PartItem::File,
+            path: Vec::new(),
+            offset,
+            meta: None,
+            block: Some(FileBlock::Uncompressed),
+            len,
+            blocks,
+            target: None,
+            rdev: None,
+        }
+    }
+
+    pub fn ty(&self) -> PartItem {
+        self.ty
+    }
+
+    pub fn path(&self) -> &[u8] {
+        &self.path
+    }
+
+    /// Returns the offset of the item header in the dump.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns [`None`] if the dump was created by an old version.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.meta.as_ref()
+    }
+
+    /// Returns the type of file blocks. Always [`None`] for non-file.
+    pub fn block(&self) -> Option<FileBlock> {
+        self.block
+    }
+
+    /// Returns the size of file content. Always zero for non-file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn blocks(&self) -> &[IndexedBlock] {
+        &self.blocks
+    }
+
+    /// Returns the target of symbolic link or hard link. Always [`None`] for other items.
+    pub fn target(&self) -> Option<&[u8]> {
+        self.target.as_deref()
+    }
+
+    /// Returns the device number of character or block device. Always [`None`] for other items.
+    pub fn rdev(&self) -> Option<u64> {
+        self.rdev
+    }
+}
+
+/// Location of a file block in the dump.
+#[derive(Debug, Clone, Copy)]
+pub struct IndexedBlock {
+    /// Offset of the block header in the dump.
+    pub offset: u64,
+    /// Offset of the block content in the file.
+    pub pos: u64,
+    /// Size of the block content after decompressed.
+    pub len: u32,
+}
+
+/// Provides [`Read`] and [`Seek`] implementation for a file in the dump.
+pub struct IndexedFile<'a, F> {
+    dump: &'a mut F,
+    item: &'a IndexedItem,
+    pos: u64,
+    next: Option<u64>,
+    cache: BlockCache,
+}
+
+impl<'a, F> IndexedFile<'a, F> {
+    pub(crate) fn new(dump: &'a mut F, item: &'a IndexedItem) -> Self {
+        Self {
+            dump,
+            item,
+            pos: 0,
+            next: None,
+            cache: BlockCache::default(),
+        }
+    }
+
+    /// Reuses the decompressed block from `cache`, which must be obtained from
+    /// [`IndexedFile::into_cache()`] of the same file.
+    pub fn with_cache(mut self, cache: BlockCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Returns the last decompressed block so the file can be reopened without decompressing it
+    /// again.
+    pub fn into_cache(self) -> BlockCache {
+        self.cache
+    }
+}
+
+impl<F: Read + Seek> IndexedFile<'_, F> {
+    fn read_compressed(&mut self, i: usize, off: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Decompress the whole block if it is not the last one we decompressed.
+        if self.cache.block != Some(i) {
+            let b = &self.item.blocks[i];
+            let len = u16::try_from(b.len).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+
+            self.cache.block = None;
+            self.dump.seek(SeekFrom::Start(b.offset + 2))?;
+
+            read_compressed(
+                self.dump,
+                self.item.block.unwrap(),
+                len,
+                &mut self.cache.buf,
+                &mut self.cache.data,
+            )?;
+
+            self.cache.block = Some(i);
+        }
+
+        // Copy data.
+        let src = &self.cache.buf[usize::try_from(off).unwrap()..];
+        let len = min(buf.len(), src.len());
+
+        buf[..len].copy_from_slice(&src[..len]);
+
+        Ok(len)
+    }
+}
+
+impl<F: Read + Seek> Read for IndexedFile<'_, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.item.len {
+            return Ok(0);
+        }
+
+        // Get the block that contains the current position.
+        let blocks = &self.item.blocks;
+        let i = blocks.partition_point(|b| b.pos + u64::from(b.len) <= self.pos);
+        let b = &blocks[i];
+        let off = self.pos - b.pos;
+
+        if self.item.block != Some(FileBlock::Uncompressed) {
+            let len = self.read_compressed(i, off, buf)?;
+
+            self.pos += u64::try_from(len).unwrap();
+
+            return Ok(len);
+        }
+
+        let len = min(buf.len(), usize::try_from(u64::from(b.len) - off).unwrap());
+
+        // Skip the seek if the dump already in the position. The data is right after 2 bytes
+        // block length.
+        let off = b.offset + 2 + off;
+
+        if self.next != Some(off) {
+            self.dump.seek(SeekFrom::Start(off))?;
+        }
+
+        // Read.
+        self.next = None;
+        self.dump.read_exact(&mut buf[..len])?;
+        self.next = Some(off + u64::try_from(len).unwrap());
+        self.pos += u64::try_from(len).unwrap();
+
+        Ok(len)
+    }
+}
+
+impl<F> Seek for IndexedFile<'_, F> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(v) => Some(v),
+            SeekFrom::End(v) => self.item.len.checked_add_signed(v),
+            SeekFrom::Current(v) => self.pos.checked_add_signed(v),
+        };
+
+        match pos {
+            Some(v) => {
+                self.pos = v;
+                Ok(v)
+            }
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Last decompressed block of an [`IndexedFile`].
+#[derive(Default)]
+pub struct BlockCache {
+    block: Option<usize>,
+    buf: Vec<u8>,
+    data: Vec<u8>,
+}
@@ -1,16 +1,168 @@
+#[cfg(all(feature = "write", feature = "std"))]
+pub use self::host::*;
+#[cfg(feature = "read")]
+pub use self::index::*;
 #[cfg(feature = "read")]
 pub use self::part::*;
+#[cfg(any(feature = "read", feature = "write"))]
+pub use self::sce::*;
+#[cfg(feature = "write")]
+pub use self::walk::*;
+#[cfg(feature = "write")]
+pub use self::writer::*;
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
+#[cfg(all(feature = "write", feature = "std"))]
+mod host;
+#[cfg(feature = "read")]
+mod index;
 #[cfg(feature = "read")]
 mod part;
+#[cfg(any(feature = "read", feature = "write"))]
+mod sce;
+#[cfg(feature = "write")]
+mod walk;
+#[cfg(feature = "write")]
+mod writer;
 
 /// Type of item in the partition dump.
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
 pub enum PartItem {
     End = 0,
     Directory = 1,
     File = 2,
+    Symlink = 3,
+    CharDevice = 4,
+    BlockDevice = 5,
+    Fifo = 6,
+    Socket = 7,
+    HardLink = 8,
+}
+
+/// Type of blocks of a file in the partition dump.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+pub enum FileBlock {
+    /// Each block is 16-bit length followed by the data. Zero length indicates the end of file.
+    Uncompressed = 0,
+    /// Each block is 16-bit uncompressed length followed by 16-bit stored length and the data.
+    /// Zero uncompressed length indicates the end of file without the stored length. The data is
+    /// a LZ4 block if stored length is less than uncompressed length otherwise it is stored as-is.
+    Compressed = 1,
+    /// Same as [`FileBlock::Compressed`] except zero stored length indicates the block contains
+    /// only zeros, which is not stored in the dump.
+    Sparse = 2,
+    /// The file is a SELF with its decrypted segments. This is followed by the block type of the
+    /// SELF then the SELF itself. After its digest is the ELF header with program headers, which
+    /// is prefixed with its length, and a list of [`SegmentItem`]. The table of contents contains
+    /// only the SELF with its block type.
+    Decrypted = 3,
+}
+
+/// Type of item after the ELF header of [`FileBlock::Decrypted`].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+pub enum SegmentItem {
+    End = 0,
+    /// 16-bit index of the program header followed by the decrypted data with the same blocks as
+    /// the SELF and its CRC-32.
+    Segment = 1,
+}
+
+/// Metadata of an item in the partition dump.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    /// File type and permission bits, same as `st_mode`.
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    /// File flags (e.g. `SF_IMMUTABLE`).
+    pub flags: u64,
+    /// Size reported by the filesystem.
+    pub size: u64,
+    pub atime: Timespec,
+    pub mtime: Timespec,
+    pub ctime: Timespec,
+    pub birthtime: Timespec,
+}
+
+impl Metadata {
+    /// Size of the encoded metadata.
+    #[cfg(any(feature = "read", feature = "write"))]
+    pub(crate) const LEN: usize = 2 + 4 + 4 + 8 + 8 + Timespec::LEN * 4;
+
+    #[cfg(feature = "write")]
+    pub(crate) fn encode(&self) -> [u8; Self::LEN] {
+        let mut buf = [0; Self::LEN];
+        let times = [self.atime, self.mtime, self.ctime, self.birthtime];
+
+        buf[..2].copy_from_slice(&self.mode.to_le_bytes());
+        buf[2..6].copy_from_slice(&self.uid.to_le_bytes());
+        buf[6..10].copy_from_slice(&self.gid.to_le_bytes());
+        buf[10..18].copy_from_slice(&self.flags.to_le_bytes());
+        buf[18..26].copy_from_slice(&self.size.to_le_bytes());
+
+        for (i, t) in times.into_iter().enumerate() {
+            let off = 26 + i * Timespec::LEN;
+
+            buf[off..(off + 8)].copy_from_slice(&t.sec.to_le_bytes());
+            buf[(off + 8)..(off + 12)].copy_from_slice(&t.nsec.to_le_bytes());
+        }
+
+        buf
+    }
+
+    #[cfg(feature = "read")]
+    pub(crate) fn decode(buf: &[u8; Self::LEN]) -> Self {
+        let time = |i: usize| {
+            let off = 26 + i * Timespec::LEN;
+
+            Timespec {
+                sec: i64::from_le_bytes(buf[off..(off + 8)].try_into().unwrap()),
+                nsec: u32::from_le_bytes(buf[(off + 8)..(off + 12)].try_into().unwrap()),
+            }
+        };
+
+        Self {
+            mode: u16::from_le_bytes(buf[..2].try_into().unwrap()),
+            uid: u32::from_le_bytes(buf[2..6].try_into().unwrap()),
+            gid: u32::from_le_bytes(buf[6..10].try_into().unwrap()),
+            flags: u64::from_le_bytes(buf[10..18].try_into().unwrap()),
+            size: u64::from_le_bytes(buf[18..26].try_into().unwrap()),
+            atime: time(0),
+            mtime: time(1),
+            ctime: time(2),
+            birthtime: time(3),
+        }
+    }
+}
+
+/// Timestamp in [`Metadata`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Timespec {
+    pub sec: i64,
+    pub nsec: u32,
+}
+
+impl Timespec {
+    #[cfg(any(feature = "read", feature = "write"))]
+    const LEN: usize = 8 + 4;
+}
+
+/// Returns the last component of `dev` (e.g. `da0x4.crypt` for `/dev/da0x4.crypt`), which is
+/// suitable for a directory name of the partition. Returns [`None`] if the component is empty,
+/// `.`, `..` or contains NUL.
+pub fn part_name(dev: &[u8]) -> Option<&[u8]> {
+    let name = match dev.iter().rposition(|&b| b == b'/') {
+        Some(i) => &dev[(i + 1)..],
+        None => dev,
+    };
+
+    if name.is_empty() || name == b"." || name == b".." || name.contains(&0) {
+        None
+    } else {
+        Some(name)
+    }
 }
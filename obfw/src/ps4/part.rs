@@ -1,7 +1,9 @@
-use super::PartItem;
+use super::{FileBlock, Metadata, PartItem, SegmentItem};
+use crate::crc32::Crc32;
 use core::cmp::min;
+use core::mem::replace;
 use std::boxed::Box;
-use std::io::{ErrorKind, Read};
+use std::io::{Error, ErrorKind, Read};
 use std::vec::Vec;
 use thiserror::Error;
 
@@ -9,14 +11,16 @@ use thiserror::Error;
 #[derive(Debug)]
 pub struct PartReader<'a, F> {
     dump: &'a mut F,
+    ver: u8,
     fs: Vec<u8>,
     dev: Vec<u8>,
+    elf: bool,
 }
 
 impl<'a, F: Read> PartReader<'a, F> {
     pub(crate) fn new(file: &'a mut F, ver: u8) -> Result<Self, PartError> {
         match ver {
-            0 => Self::read_v0(file),
+            0..=2 => Self::read_v0(file, ver),
             v => Err(PartError::UnknownVersion(v)),
         }
     }
@@ -29,7 +33,39 @@ impl<'a, F: Read> PartReader<'a, F> {
         &self.dev
     }
 
-    pub fn next_item(&mut self) -> Result<Option<PartData>, PartError> {
+    /// Sets whether a SELF file that has decrypted segments will be returned as a reconstructed
+    /// ELF instead of the SELF itself. The default is `false`.
+    ///
+    /// The ELF contains the original ELF header and program headers with each decrypted segment at
+    /// its file offset. Any gap between segments is filled with zeros.
+    pub fn set_elf(&mut self, v: bool) {
+        self.elf = v;
+    }
+
+    pub fn next_item(&mut self) -> Result<Option<PartData<'_>>, PartError> {
+        let h = match self.next_header()? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        // Create item reader.
+        let data = match h.ty {
+            PartItem::End => unreachable!(),
+            PartItem::Directory => PartData::Directory(h.path, h.meta),
+            PartItem::File => self.read_file(h.path, h.meta)?,
+            PartItem::Symlink => PartData::Symlink(h.path, h.meta, self.read_link()?),
+            PartItem::CharDevice => PartData::CharDevice(h.path, h.meta, self.read_rdev()?),
+            PartItem::BlockDevice => PartData::BlockDevice(h.path, h.meta, self.read_rdev()?),
+            PartItem::Fifo => PartData::Fifo(h.path, h.meta),
+            PartItem::Socket => PartData::Socket(h.path, h.meta),
+            PartItem::HardLink => PartData::HardLink(h.path, h.meta, self.read_link()?),
+        };
+
+        Ok(Some(data))
+    }
+
+    /// Reads header of the next item. Returns [`None`] if the next item is an end entry.
+    pub(crate) fn next_header(&mut self) -> Result<Option<ItemHeader>, PartError> {
         // Read item type.
         let mut ty = 0;
 
@@ -37,18 +73,76 @@ impl<'a, F: Read> PartReader<'a, F> {
             .read_exact(std::slice::from_mut(&mut ty))
             .map_err(PartError::Read)?;
 
-        // Create item reader.
+        // Read path.
         let ty = PartItem::try_from(ty).map_err(|_| PartError::UnknownItem(ty))?;
-        let data = match ty {
-            PartItem::End => return Ok(None),
-            PartItem::Directory => Self::read_str(self.dump).map(PartData::Directory)?,
-            PartItem::File => self.read_file()?,
+
+        if ty == PartItem::End {
+            return Ok(None);
+        }
+
+        let path = Self::read_str(self.dump)?;
+
+        // Read metadata.
+        let meta = if self.has_metadata() {
+            let mut buf = [0u8; Metadata::LEN];
+
+            self.dump.read_exact(&mut buf).map_err(PartError::Read)?;
+
+            Some(Metadata::decode(&buf))
+        } else {
+            None
         };
 
-        Ok(Some(data))
+        Ok(Some(ItemHeader { ty, path, meta }))
     }
 
-    fn read_v0(file: &'a mut F) -> Result<Self, PartError> {
+    pub(crate) fn read_block_type(&mut self) -> Result<FileBlock, PartError> {
+        let mut btype = 0;
+
+        self.dump
+            .read_exact(std::slice::from_mut(&mut btype))
+            .map_err(PartError::Read)?;
+
+        FileBlock::try_from(btype).map_err(|_| PartError::UnknownFileBlock(btype))
+    }
+
+    /// Reads block type of the SELF after [`FileBlock::Decrypted`].
+    pub(crate) fn read_inner_block_type(&mut self) -> Result<FileBlock, PartError> {
+        match self.read_block_type()? {
+            FileBlock::Decrypted => Err(PartError::UnknownFileBlock(FileBlock::Decrypted.into())),
+            v => Ok(v),
+        }
+    }
+
+    /// Reads target of a symbolic link or hard link.
+    pub(crate) fn read_link(&mut self) -> Result<Vec<u8>, PartError> {
+        Self::read_str(self.dump)
+    }
+
+    /// Reads device number of a device.
+    pub(crate) fn read_rdev(&mut self) -> Result<u64, PartError> {
+        let mut rdev = [0u8; 8];
+
+        self.dump.read_exact(&mut rdev).map_err(PartError::Read)?;
+
+        Ok(u64::from_le_bytes(rdev))
+    }
+
+    pub(crate) fn dump(&mut self) -> &mut F {
+        self.dump
+    }
+
+    /// Returns `true` if each file has a digest after the end block, which is version 1 or later.
+    pub(crate) fn has_digest(&self) -> bool {
+        self.ver >= 1
+    }
+
+    /// Returns `true` if each item has [`Metadata`], which is version 2 or later.
+    pub(crate) fn has_metadata(&self) -> bool {
+        self.ver >= 2
+    }
+
+    fn read_v0(file: &'a mut F, ver: u8) -> Result<Self, PartError> {
         // Read filesystem type and device name.
         let fs = Self::read_str(file)?;
         let dev = Self::read_str(file)?;
@@ -56,31 +150,38 @@ impl<'a, F: Read> PartReader<'a, F> {
         // Read device name.
         Ok(Self {
             dump: file,
+            ver,
             fs,
             dev,
+            elf: false,
         })
     }
 
-    fn read_file(&mut self) -> Result<PartData, PartError> {
-        // Read name and block type.
-        let name = Self::read_str(self.dump)?;
-        let mut btype = 0;
-
-        self.dump
-            .read_exact(std::slice::from_mut(&mut btype))
-            .map_err(PartError::Read)?;
-
+    fn read_file(
+        &mut self,
+        name: Vec<u8>,
+        meta: Option<Metadata>,
+    ) -> Result<PartData<'_>, PartError> {
         // Create block reader.
-        let r = match btype {
-            0 => Box::new(UncompressedFile {
-                dump: Some(self.dump),
-                buf: Vec::with_capacity(0xFFFF),
-                off: 0,
-            }),
-            v => return Err(PartError::UnknownFileBlock(v)),
+        let digest = self.has_digest();
+        let r: Box<dyn Read + '_> = match self.read_block_type()? {
+            FileBlock::Decrypted => {
+                let ty = self.read_inner_block_type()?;
+
+                Box::new(DecryptedFile {
+                    state: Decrypted::Container(Blocks::new(self.dump, ty, digest)),
+                    ty,
+                    digest,
+                    elf: self.elf,
+                    phdrs: Vec::new(),
+                    pos: 0,
+                    end: 0,
+                })
+            }
+            ty => Box::new(Blocks::new(self.dump, ty, digest)),
         };
 
-        Ok(PartData::File(name, r))
+        Ok(PartData::File(name, meta, r))
     }
 
     fn read_str(file: &mut F) -> Result<Vec<u8>, PartError> {
@@ -106,17 +207,88 @@ impl<'a, F: Read> PartReader<'a, F> {
     }
 }
 
-/// Encapsulates data of a partition item.
+/// Common fields of all partition items.
+pub(crate) struct ItemHeader {
+    pub ty: PartItem,
+    pub path: Vec<u8>,
+    pub meta: Option<Metadata>,
+}
+
+/// Encapsulates data of a partition item. [`Metadata`] is [`None`] if the dump was created by an
+/// old version.
 pub enum PartData<'a> {
-    Directory(Vec<u8>),
-    File(Vec<u8>, Box<dyn Read + 'a>),
+    Directory(Vec<u8>, Option<Metadata>),
+    File(Vec<u8>, Option<Metadata>, Box<dyn Read + 'a>),
+    /// The last field is the target of the link.
+    Symlink(Vec<u8>, Option<Metadata>, Vec<u8>),
+    /// The last field is the device number (`rdev`).
+    CharDevice(Vec<u8>, Option<Metadata>, u64),
+    /// The last field is the device number (`rdev`).
+    BlockDevice(Vec<u8>, Option<Metadata>, u64),
+    Fifo(Vec<u8>, Option<Metadata>),
+    Socket(Vec<u8>, Option<Metadata>),
+    /// The last field is the path of the file in the same partition this item links to, which
+    /// always come before this item.
+    HardLink(Vec<u8>, Option<Metadata>, Vec<u8>),
+}
+
+/// Reader for blocks of a file.
+enum Blocks<'a, F> {
+    Uncompressed(UncompressedFile<'a, F>),
+    Compressed(CompressedFile<'a, F>),
+}
+
+impl<'a, F> Blocks<'a, F> {
+    /// `ty` must not be [`FileBlock::Decrypted`]. The blocks must be followed by a digest if
+    /// `digest` is `true`.
+    fn new(dump: &'a mut F, ty: FileBlock, digest: bool) -> Self {
+        let crc = digest.then(Crc32::new);
+
+        match ty {
+            FileBlock::Uncompressed => Self::Uncompressed(UncompressedFile {
+                dump,
+                done: false,
+                buf: Vec::with_capacity(0xFFFF),
+                off: 0,
+                crc,
+            }),
+            FileBlock::Compressed | FileBlock::Sparse => Self::Compressed(CompressedFile {
+                dump,
+                done: false,
+                ty,
+                buf: Vec::with_capacity(0xFFFF),
+                data: Vec::with_capacity(0xFFFF),
+                off: 0,
+                crc,
+            }),
+            FileBlock::Decrypted => unreachable!(),
+        }
+    }
+
+    fn into_dump(self) -> &'a mut F {
+        match self {
+            Self::Uncompressed(v) => v.dump,
+            Self::Compressed(v) => v.dump,
+        }
+    }
+}
+
+impl<F: Read> Read for Blocks<'_, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Uncompressed(v) => v.read(buf),
+            Self::Compressed(v) => v.read(buf),
+        }
+    }
 }
 
 /// Provides [`Read`] implementation to read uncompressed file.
 struct UncompressedFile<'a, F> {
-    dump: Option<&'a mut F>,
+    dump: &'a mut F,
+    done: bool,
     buf: Vec<u8>,
     off: usize,
+    crc: Option<Crc32>,
 }
 
 impl<F: Read> Read for UncompressedFile<'_, F> {
@@ -129,10 +301,11 @@ impl<F: Read> Read for UncompressedFile<'_, F> {
         if self.off == self.buf.len() {
             // Read block length.
             let mut len = [0u8; 2];
-            let dump = match &mut self.dump {
-                Some(v) => v,
-                None => return Ok(0),
-            };
+            let dump = &mut *self.dump;
+
+            if self.done {
+                return Ok(0);
+            }
 
             dump.read_exact(&mut len)?;
 
@@ -140,8 +313,9 @@ impl<F: Read> Read for UncompressedFile<'_, F> {
             let len = u16::from_le_bytes(len);
 
             if len == 0 {
-                self.dump = None;
-                return Ok(0);
+                self.done = true;
+
+                return check_digest(dump, self.crc).map(|_| 0);
             }
 
             // Read block.
@@ -151,6 +325,69 @@ impl<F: Read> Read for UncompressedFile<'_, F> {
             if dump.take(len.into()).read_to_end(&mut self.buf)? != len.into() {
                 return Err(ErrorKind::UnexpectedEof.into());
             }
+
+            if let Some(crc) = &mut self.crc {
+                crc.update(&self.buf);
+            }
+        }
+
+        // Copy data.
+        let src = &self.buf[self.off..];
+        let len = min(buf.len(), src.len());
+
+        buf[..len].copy_from_slice(&src[..len]);
+        self.off += len;
+
+        Ok(len)
+    }
+}
+
+/// Provides [`Read`] implementation to read compressed file.
+struct CompressedFile<'a, F> {
+    dump: &'a mut F,
+    done: bool,
+    ty: FileBlock,
+    buf: Vec<u8>,
+    data: Vec<u8>,
+    off: usize,
+    crc: Option<Crc32>,
+}
+
+impl<F: Read> Read for CompressedFile<'_, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Check if we need to read next block.
+        if self.off == self.buf.len() {
+            // Read uncompressed length.
+            let mut len = [0u8; 2];
+            let dump = &mut *self.dump;
+
+            if self.done {
+                return Ok(0);
+            }
+
+            dump.read_exact(&mut len)?;
+
+            // Check if EOF.
+            let len = u16::from_le_bytes(len);
+
+            if len == 0 {
+                self.done = true;
+
+                return check_digest(dump, self.crc).map(|_| 0);
+            }
+
+            // Read block.
+            self.off = 0;
+
+            read_compressed(dump, self.ty, len, &mut self.buf, &mut self.data)?;
+
+            if let Some(crc) = &mut self.crc {
+                crc.update(&self.buf);
+            }
         }
 
         // Copy data.
@@ -164,6 +401,309 @@ impl<F: Read> Read for UncompressedFile<'_, F> {
     }
 }
 
+/// Provides [`Read`] implementation to read [`FileBlock::Decrypted`]. The content is either the
+/// SELF or the reconstructed ELF depending on `elf`.
+struct DecryptedFile<'a, F> {
+    state: Decrypted<'a, F>,
+    ty: FileBlock,
+    digest: bool,
+    elf: bool,
+    phdrs: Vec<(u64, u64)>,
+    pos: u64,
+    /// End of the last segment in the ELF.
+    end: u64,
+}
+
+impl<F: Read> DecryptedFile<'_, F> {
+    /// Returns file offset and size of each program header in `hdrs`.
+    fn parse_phdrs(hdrs: &[u8]) -> std::io::Result<Vec<(u64, u64)>> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "invalid ELF header");
+
+        if hdrs.len() < 64 || !hdrs.starts_with(b"\x7FELF") {
+            return Err(invalid());
+        }
+
+        // Get program headers.
+        let off = u64::from_le_bytes(hdrs[32..40].try_into().unwrap());
+        let size = u16::from_le_bytes(hdrs[54..56].try_into().unwrap());
+        let count = u16::from_le_bytes(hdrs[56..58].try_into().unwrap());
+
+        if size < 56 {
+            return Err(invalid());
+        }
+
+        let off = usize::try_from(off).map_err(|_| invalid())?;
+        let len = usize::from(size) * usize::from(count);
+        let phdrs = off
+            .checked_add(len)
+            .and_then(|end| hdrs.get(off..end))
+            .ok_or_else(invalid)?;
+
+        Ok(phdrs
+            .chunks(size.into())
+            .map(|p| {
+                let off = u64::from_le_bytes(p[8..16].try_into().unwrap());
+                let len = u64::from_le_bytes(p[32..40].try_into().unwrap());
+
+                (off, len)
+            })
+            .collect())
+    }
+
+    /// Reads the ELF header and program headers after the SELF.
+    fn read_headers(dump: &mut F) -> std::io::Result<Vec<u8>> {
+        let mut len = [0u8; 8];
+        let mut data = Vec::new();
+
+        dump.read_exact(&mut len)?;
+
+        let len = u64::from_le_bytes(len);
+
+        if dump.take(len).read_to_end(&mut data)? as u64 != len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(data)
+    }
+}
+
+impl<F: Read> Read for DecryptedFile<'_, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match &mut self.state {
+                Decrypted::Container(r) => {
+                    // The SELF still need to be read to the end to verify its digest.
+                    if !self.elf {
+                        let n = r.read(buf)?;
+
+                        if n != 0 {
+                            return Ok(n);
+                        }
+                    } else {
+                        std::io::copy(r, &mut std::io::sink())?;
+                    }
+
+                    // Read headers.
+                    let dump = match replace(&mut self.state, Decrypted::Done) {
+                        Decrypted::Container(r) => r.into_dump(),
+                        _ => unreachable!(),
+                    };
+
+                    let hdrs = Self::read_headers(dump)?;
+
+                    self.state = if self.elf {
+                        self.phdrs = Self::parse_phdrs(&hdrs)?;
+                        Decrypted::Headers(dump, hdrs, 0)
+                    } else {
+                        Decrypted::Next(dump)
+                    };
+                }
+                Decrypted::Headers(_, hdrs, off) => {
+                    if *off < hdrs.len() {
+                        let src = &hdrs[*off..];
+                        let len = min(buf.len(), src.len());
+
+                        buf[..len].copy_from_slice(&src[..len]);
+                        *off += len;
+                        self.pos += u64::try_from(len).unwrap();
+
+                        return Ok(len);
+                    }
+
+                    self.state = match replace(&mut self.state, Decrypted::Done) {
+                        Decrypted::Headers(dump, _, _) => Decrypted::Next(dump),
+                        _ => unreachable!(),
+                    };
+                }
+                Decrypted::Next(dump) => {
+                    // Read item type.
+                    let mut ty = 0;
+
+                    dump.read_exact(std::slice::from_mut(&mut ty))?;
+
+                    match SegmentItem::try_from(ty) {
+                        Ok(SegmentItem::End) => {
+                            self.state = Decrypted::Done;
+                            return Ok(0);
+                        }
+                        Ok(SegmentItem::Segment) => {}
+                        Err(_) => {
+                            return Err(Error::new(ErrorKind::InvalidData, "unknown segment item"))
+                        }
+                    }
+
+                    // Read program header index.
+                    let mut index = [0u8; 2];
+
+                    dump.read_exact(&mut index)?;
+
+                    let index = usize::from(u16::from_le_bytes(index));
+                    let dump = match replace(&mut self.state, Decrypted::Done) {
+                        Decrypted::Next(v) => v,
+                        _ => unreachable!(),
+                    };
+
+                    let mut r = Blocks::new(dump, self.ty, self.digest);
+
+                    if !self.elf {
+                        std::io::copy(&mut r, &mut std::io::sink())?;
+                        self.state = Decrypted::Next(r.into_dump());
+                        continue;
+                    }
+
+                    // Segments must be in the same order as their file offset. A segment can overlap
+                    // the headers (e.g. the first one at offset zero), in which case the overlapped
+                    // part is skipped.
+                    let (off, len) = match self.phdrs.get(index) {
+                        Some(&(off, len)) if off >= self.end => (off, len),
+                        _ => {
+                            return Err(Error::new(ErrorKind::InvalidData, "invalid segment index"))
+                        }
+                    };
+
+                    self.end = off.checked_add(len).ok_or_else(|| {
+                        Error::new(ErrorKind::InvalidData, "invalid segment size")
+                    })?;
+                    self.state = Decrypted::Segment(
+                        r,
+                        off.saturating_sub(self.pos),
+                        self.pos.saturating_sub(off),
+                        len,
+                    );
+                }
+                Decrypted::Segment(r, pad, skip, remaining) => {
+                    // Fill the gap before the segment.
+                    if *pad != 0 {
+                        let len = min(buf.len(), usize::try_from(*pad).unwrap_or(usize::MAX));
+
+                        buf[..len].fill(0);
+                        *pad -= u64::try_from(len).unwrap();
+                        self.pos += u64::try_from(len).unwrap();
+
+                        return Ok(len);
+                    }
+
+                    // Read segment.
+                    let n = r.read(buf)?;
+                    let len = u64::try_from(n).unwrap();
+
+                    if len > *remaining || (n == 0 && *remaining != 0) {
+                        return Err(Error::new(ErrorKind::InvalidData, "segment size mismatch"));
+                    } else if n != 0 {
+                        let skipped = min(len, *skip);
+
+                        *remaining -= len;
+                        *skip -= skipped;
+
+                        if skipped == len {
+                            continue;
+                        }
+
+                        let skipped = usize::try_from(skipped).unwrap();
+
+                        buf.copy_within(skipped..n, 0);
+                        self.pos += u64::try_from(n - skipped).unwrap();
+
+                        return Ok(n - skipped);
+                    }
+
+                    self.state = match replace(&mut self.state, Decrypted::Done) {
+                        Decrypted::Segment(r, _, _, _) => Decrypted::Next(r.into_dump()),
+                        _ => unreachable!(),
+                    };
+                }
+                Decrypted::Done => return Ok(0),
+            }
+        }
+    }
+}
+
+/// State of [`DecryptedFile`].
+enum Decrypted<'a, F> {
+    Container(Blocks<'a, F>),
+    /// ELF header and program headers with the number of bytes that was returned.
+    Headers(&'a mut F, Vec<u8>, usize),
+    /// Before the next segment item.
+    Next(&'a mut F),
+    /// Size of the gap before the segment, size of the part that overlaps the data that was
+    /// already returned and the remaining size of the segment.
+    Segment(Blocks<'a, F>, u64, u64, u64),
+    Done,
+}
+
+/// Reads the remaining of compressed block after its uncompressed length `len` and put the
+/// uncompressed data to `buf`. `ty` must be either [`FileBlock::Compressed`] or
+/// [`FileBlock::Sparse`]. `data` is used as a buffer for the stored data.
+pub(crate) fn read_compressed<R: Read>(
+    dump: &mut R,
+    ty: FileBlock,
+    len: u16,
+    buf: &mut Vec<u8>,
+    data: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    // Read stored length.
+    let mut stored = [0u8; 2];
+
+    dump.read_exact(&mut stored)?;
+
+    let stored = u16::from_le_bytes(stored);
+
+    if stored > len {
+        return Err(Error::new(ErrorKind::InvalidData, "invalid stored length"));
+    }
+
+    // Check if a run of zeros.
+    buf.clear();
+
+    if stored == 0 && ty == FileBlock::Sparse {
+        buf.resize(len.into(), 0);
+        return Ok(());
+    }
+
+    // Read stored data.
+    data.clear();
+
+    if dump.take(stored.into()).read_to_end(data)? != stored.into() {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    // Decompress.
+    if stored == len {
+        buf.extend_from_slice(data);
+    } else if crate::lz4::decompress(data, buf, len.into()).is_none() || buf.len() != len.into() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "invalid compressed block",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reads the file digest after the end block and compare it with `crc`. Do nothing if `crc` is
+/// [`None`].
+fn check_digest<R: Read>(dump: &mut R, crc: Option<Crc32>) -> std::io::Result<()> {
+    let crc = match crc {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    // Read digest.
+    let mut digest = [0u8; 4];
+
+    dump.read_exact(&mut digest)?;
+
+    if u32::from_le_bytes(digest) != crc.finish() {
+        Err(Error::new(ErrorKind::InvalidData, "file digest mismatch"))
+    } else {
+        Ok(())
+    }
+}
+
 /// Represents an error when [`PartReader`] fails to read partition dump.
 #[derive(Debug, Error)]
 pub enum PartError {
@@ -173,9 +713,15 @@ pub enum PartError {
     #[error("couldn't read the specified file")]
     Read(#[source] std::io::Error),
 
+    #[error("couldn't seek the specified file")]
+    Seek(#[source] std::io::Error),
+
     #[error("unknown item type {0}")]
     UnknownItem(u8),
 
     #[error("unknown file block type {0}")]
     UnknownFileBlock(u8),
+
+    #[error("unknown segment item {0}")]
+    UnknownSegmentItem(u8),
 }
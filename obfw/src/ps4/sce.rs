@@ -0,0 +1,364 @@
+use alloc::vec::Vec;
+use thiserror::Error;
+
+#[cfg(feature = "std")]
+use std::io::Read;
+
+/// Magic of the SELF header.
+pub const SELF_MAGIC: [u8; 4] = [0x4F, 0x15, 0x3D, 0x1D];
+
+/// ELF type of the PlayStation 4 executable.
+pub const ET_SCE_EXEC: u16 = 0xFE00;
+
+/// ELF type of the PlayStation 4 position-independent executable.
+pub const ET_SCE_DYNEXEC: u16 = 0xFE10;
+
+/// ELF type of the PlayStation 4 dynamic library (e.g. SPRX).
+pub const ET_SCE_DYNAMIC: u16 = 0xFE18;
+
+/// Parsed headers of a SELF file.
+///
+/// A SELF file is a SCE container of an ELF file. It contains a header, the segment table, the ELF
+/// header with its program headers then the extended information. The segments themselves are
+/// usually encrypted and are not read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfFile {
+    pub header: SelfHeader,
+    pub segments: Vec<SelfSegment>,
+    pub elf: ElfHeader,
+    pub programs: Vec<ProgramHeader>,
+    pub info: SelfInfo,
+}
+
+impl SelfFile {
+    /// Parses the headers from `data`, which must start with the SELF. Returns
+    /// [`SelfError::Truncated`] with the required length if `data` does not contain all headers so
+    /// the caller can read more data and try again.
+    pub fn parse(data: &[u8]) -> Result<Self, SelfError> {
+        // Parse SELF header.
+        let hdr = get(data, 0, SelfHeader::LEN)?;
+
+        if hdr[..4] != SELF_MAGIC {
+            return Err(SelfError::NotSelf);
+        }
+
+        let header = SelfHeader::decode(hdr.try_into().unwrap());
+
+        // Parse segment table.
+        let len = usize::from(header.segments) * SelfSegment::LEN;
+        let segments = get(data, SelfHeader::LEN, len)?
+            .chunks_exact(SelfSegment::LEN)
+            .map(|b| SelfSegment::decode(b.try_into().unwrap()))
+            .collect();
+
+        // Parse ELF header, which is right after the segment table.
+        let elf = SelfHeader::LEN + len;
+        let hdr = get(data, elf, ElfHeader::LEN)?;
+
+        if !hdr.starts_with(b"\x7FELF") {
+            return Err(SelfError::NotElf);
+        }
+
+        let ehdr = ElfHeader::decode(hdr.try_into().unwrap());
+
+        if ehdr.phnum != 0 && usize::from(ehdr.phentsize) < ProgramHeader::LEN {
+            return Err(SelfError::InvalidProgramHeaderSize(ehdr.phentsize));
+        }
+
+        // Parse program headers.
+        let size = usize::from(ehdr.phentsize);
+        let len = size * usize::from(ehdr.phnum);
+        let off = usize::try_from(ehdr.phoff)
+            .ok()
+            .and_then(|v| v.checked_add(elf))
+            .filter(|v| v.checked_add(len + SelfInfo::LEN + 15).is_some())
+            .ok_or(SelfError::InvalidProgramHeaderOffset(ehdr.phoff))?;
+        let programs = match len {
+            0 => Vec::new(),
+            _ => get(data, off, len)?
+                .chunks_exact(size)
+                .map(ProgramHeader::decode)
+                .collect(),
+        };
+
+        // Parse extended information, which is aligned to 16 bytes after the program headers.
+        let off = (off + len).next_multiple_of(16);
+        let info = get(data, off, SelfInfo::LEN)?;
+
+        Ok(Self {
+            header,
+            segments,
+            elf: ehdr,
+            programs,
+            info: SelfInfo::decode(info.try_into().unwrap()),
+        })
+    }
+
+    /// Reads the headers from the current position of `file`, which must be the start of the
+    /// SELF.
+    #[cfg(feature = "std")]
+    pub fn read<F: Read>(file: &mut F) -> Result<Self, SelfError> {
+        let mut data = Vec::new();
+        let mut len = SelfHeader::LEN;
+
+        loop {
+            file.by_ref()
+                .take((len - data.len()) as u64)
+                .read_to_end(&mut data)
+                .map_err(SelfError::Read)?;
+
+            if data.len() < len {
+                return Err(SelfError::Read(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+
+            match Self::parse(&data) {
+                Err(SelfError::Truncated(v)) => len = v,
+                r => return r,
+            }
+        }
+    }
+
+    /// Returns the offset of the ELF header from the start of the SELF.
+    pub fn elf_offset(&self) -> usize {
+        SelfHeader::LEN + self.segments.len() * SelfSegment::LEN
+    }
+
+    /// Returns `true` if the ELF is a dynamic library (e.g. SPRX).
+    pub fn is_library(&self) -> bool {
+        self.elf.ty == ET_SCE_DYNAMIC
+    }
+
+    /// Returns the program header of the data in `seg`.
+    pub fn program(&self, seg: &SelfSegment) -> Option<&ProgramHeader> {
+        if seg.is_blocked() {
+            self.programs.get(usize::from(seg.id()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Header at the beginning of a SELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfHeader {
+    pub version: u8,
+    pub mode: u8,
+    pub endian: u8,
+    pub attrs: u8,
+    pub key_type: u32,
+    /// Size of everything before the metadata.
+    pub header_size: u16,
+    /// Size of the metadata after the headers.
+    pub meta_size: u16,
+    /// Size of the whole SELF.
+    pub file_size: u64,
+    /// Number of entries in the segment table.
+    pub segments: u16,
+    pub flags: u16,
+}
+
+impl SelfHeader {
+    const LEN: usize = 32;
+
+    fn decode(buf: &[u8; Self::LEN]) -> Self {
+        Self {
+            version: buf[4],
+            mode: buf[5],
+            endian: buf[6],
+            attrs: buf[7],
+            key_type: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+            header_size: u16::from_le_bytes(buf[12..14].try_into().unwrap()),
+            meta_size: u16::from_le_bytes(buf[14..16].try_into().unwrap()),
+            file_size: u64::from_le_bytes(buf[16..24].try_into().unwrap()),
+            segments: u16::from_le_bytes(buf[24..26].try_into().unwrap()),
+            flags: u16::from_le_bytes(buf[26..28].try_into().unwrap()),
+        }
+    }
+}
+
+/// Entry in the segment table of a SELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfSegment {
+    pub flags: u64,
+    /// Offset of the data from the start of the SELF.
+    pub offset: u64,
+    /// Size of the data in the SELF.
+    pub stored_size: u64,
+    /// Size of the data after decryption and decompression.
+    pub size: u64,
+}
+
+impl SelfSegment {
+    const LEN: usize = 32;
+
+    pub fn is_ordered(&self) -> bool {
+        self.flags & 0x1 != 0
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & 0x2 != 0
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.flags & 0x4 != 0
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.flags & 0x8 != 0
+    }
+
+    /// Returns `true` if the segment contains data of the program header [`SelfSegment::id()`].
+    pub fn is_blocked(&self) -> bool {
+        self.flags & 0x800 != 0
+    }
+
+    /// Returns size of each block in the segment.
+    pub fn block_size(&self) -> u64 {
+        1 << (12 + ((self.flags >> 12) & 0xF))
+    }
+
+    /// Returns the index of the program header if [`SelfSegment::is_blocked()`] otherwise the
+    /// index of the segment this one contains information for.
+    pub fn id(&self) -> u16 {
+        ((self.flags >> 20) & 0xFFF) as u16
+    }
+
+    fn decode(buf: &[u8; Self::LEN]) -> Self {
+        Self {
+            flags: u64::from_le_bytes(buf[..8].try_into().unwrap()),
+            offset: u64::from_le_bytes(buf[8..16].try_into().unwrap()),
+            stored_size: u64::from_le_bytes(buf[16..24].try_into().unwrap()),
+            size: u64::from_le_bytes(buf[24..32].try_into().unwrap()),
+        }
+    }
+}
+
+/// ELF header embedded in a SELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElfHeader {
+    pub ident: [u8; 16],
+    /// Type of the ELF (e.g. [`ET_SCE_DYNAMIC`]).
+    pub ty: u16,
+    pub machine: u16,
+    pub version: u32,
+    pub entry: u64,
+    /// Offset of the program headers relative to the ELF header.
+    pub phoff: u64,
+    pub shoff: u64,
+    pub flags: u32,
+    pub ehsize: u16,
+    pub phentsize: u16,
+    pub phnum: u16,
+    pub shentsize: u16,
+    pub shnum: u16,
+    pub shstrndx: u16,
+}
+
+impl ElfHeader {
+    const LEN: usize = 64;
+
+    fn decode(buf: &[u8; Self::LEN]) -> Self {
+        Self {
+            ident: buf[..16].try_into().unwrap(),
+            ty: u16::from_le_bytes(buf[16..18].try_into().unwrap()),
+            machine: u16::from_le_bytes(buf[18..20].try_into().unwrap()),
+            version: u32::from_le_bytes(buf[20..24].try_into().unwrap()),
+            entry: u64::from_le_bytes(buf[24..32].try_into().unwrap()),
+            phoff: u64::from_le_bytes(buf[32..40].try_into().unwrap()),
+            shoff: u64::from_le_bytes(buf[40..48].try_into().unwrap()),
+            flags: u32::from_le_bytes(buf[48..52].try_into().unwrap()),
+            ehsize: u16::from_le_bytes(buf[52..54].try_into().unwrap()),
+            phentsize: u16::from_le_bytes(buf[54..56].try_into().unwrap()),
+            phnum: u16::from_le_bytes(buf[56..58].try_into().unwrap()),
+            shentsize: u16::from_le_bytes(buf[58..60].try_into().unwrap()),
+            shnum: u16::from_le_bytes(buf[60..62].try_into().unwrap()),
+            shstrndx: u16::from_le_bytes(buf[62..64].try_into().unwrap()),
+        }
+    }
+}
+
+/// ELF program header embedded in a SELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramHeader {
+    pub ty: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub paddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+    pub align: u64,
+}
+
+impl ProgramHeader {
+    const LEN: usize = 56;
+
+    fn decode(buf: &[u8]) -> Self {
+        Self {
+            ty: u32::from_le_bytes(buf[..4].try_into().unwrap()),
+            flags: u32::from_le_bytes(buf[4..8].try_into().unwrap()),
+            offset: u64::from_le_bytes(buf[8..16].try_into().unwrap()),
+            vaddr: u64::from_le_bytes(buf[16..24].try_into().unwrap()),
+            paddr: u64::from_le_bytes(buf[24..32].try_into().unwrap()),
+            filesz: u64::from_le_bytes(buf[32..40].try_into().unwrap()),
+            memsz: u64::from_le_bytes(buf[40..48].try_into().unwrap()),
+            align: u64::from_le_bytes(buf[48..56].try_into().unwrap()),
+        }
+    }
+}
+
+/// Extended information after the program headers of a SELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfInfo {
+    /// Program authentication ID.
+    pub paid: u64,
+    pub program_type: u64,
+    pub app_version: u64,
+    pub fw_version: u64,
+    /// SHA-256 of the ELF.
+    pub digest: [u8; 32],
+}
+
+impl SelfInfo {
+    const LEN: usize = 64;
+
+    fn decode(buf: &[u8; Self::LEN]) -> Self {
+        Self {
+            paid: u64::from_le_bytes(buf[..8].try_into().unwrap()),
+            program_type: u64::from_le_bytes(buf[8..16].try_into().unwrap()),
+            app_version: u64::from_le_bytes(buf[16..24].try_into().unwrap()),
+            fw_version: u64::from_le_bytes(buf[24..32].try_into().unwrap()),
+            digest: buf[32..].try_into().unwrap(),
+        }
+    }
+}
+
+/// Returns `len` bytes at `off` in `data`.
+fn get(data: &[u8], off: usize, len: usize) -> Result<&[u8], SelfError> {
+    let end = off + len;
+
+    data.get(off..end).ok_or(SelfError::Truncated(end))
+}
+
+/// Represents an error when [`SelfFile::parse()`] or [`SelfFile::read()`] fails.
+#[derive(Debug, Error)]
+pub enum SelfError {
+    #[cfg(feature = "std")]
+    #[error("couldn't read the specified file")]
+    Read(#[source] std::io::Error),
+
+    #[error("the headers need {0} bytes")]
+    Truncated(usize),
+
+    #[error("the specified file is not a SELF")]
+    NotSelf,
+
+    #[error("the SELF does not contain an ELF")]
+    NotElf,
+
+    #[error("invalid size of program header ({0})")]
+    InvalidProgramHeaderSize(u16),
+
+    #[error("invalid offset of program headers ({0:#x})")]
+    InvalidProgramHeaderOffset(u64),
+}
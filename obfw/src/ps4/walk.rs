@@ -0,0 +1,240 @@
+use super::{FileBlock, FileWriter, Metadata, PartWriter};
+use crate::DumpSink;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::collections::vec_deque::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use thiserror::Error;
+
+/// Write all items in `fs` to `part` in the same order as the payload (breadth-first).
+///
+/// Regular files that have more than one link are written only once. The other paths of the same
+/// file are written as a hard link to the first one.
+pub fn walk<S: DumpSink, F: FsSource>(
+    part: &mut PartWriter<'_, S>,
+    fs: &mut F,
+    block: FileBlock,
+) -> Result<(), WalkError<F::Error, S::Error>> {
+    let root = fs.root().map_err(WalkError::Source)?;
+    let mut pending = VecDeque::from([(Vec::new(), root)]);
+    let mut children = Vec::new();
+    let mut links = BTreeMap::<u64, Vec<u8>>::new();
+    let mut buf = vec![0; FileWriter::<S>::BLOCK_SIZE];
+
+    while let Some((path, node)) = pending.pop_front() {
+        let info = fs.stat(&node).map_err(WalkError::Source)?;
+        let meta = &info.meta;
+
+        match info.ty {
+            NodeType::Directory => {
+                part.directory(&path, meta).map_err(WalkError::Write)?;
+
+                // Queue children.
+                fs.list(&node, &mut children).map_err(WalkError::Source)?;
+
+                for (name, child) in children.drain(..) {
+                    let mut path = path.clone();
+
+                    path.push(b'/');
+                    path.extend_from_slice(&name);
+                    pending.push_back((path, child));
+                }
+            }
+            NodeType::File if info.nlink > 1 && links.contains_key(&info.id) => part
+                .hard_link(&path, meta, &links[&info.id])
+                .map_err(WalkError::Write)?,
+            NodeType::File => {
+                if info.nlink > 1 {
+                    links.insert(info.id, path.clone());
+                }
+
+                // Check if SELF.
+                let mut file = fs.open(&node).map_err(WalkError::Source)?;
+                let decrypted = fs.decrypt(&mut file).map_err(WalkError::Source)?;
+                let decrypted = match decrypted {
+                    Some(v) => v,
+                    None => {
+                        let mut w = part.file(&path, meta, block).map_err(WalkError::Write)?;
+
+                        copy(
+                            &mut buf,
+                            |off, buf| fs.read(&mut file, off, buf),
+                            |data| w.write(data),
+                        )?;
+
+                        w.finish().map_err(WalkError::Write)?;
+                        continue;
+                    }
+                };
+
+                // Copy SELF.
+                let mut w = part
+                    .self_file(&path, meta, block)
+                    .map_err(WalkError::Write)?;
+
+                copy(
+                    &mut buf,
+                    |off, buf| fs.read(&mut file, off, buf),
+                    |data| w.write(data),
+                )?;
+
+                // Copy decrypted segments.
+                let mut w = w.decrypted(&decrypted.headers).map_err(WalkError::Write)?;
+
+                for &i in &decrypted.segments {
+                    let mut s = w.segment(i).map_err(WalkError::Write)?;
+
+                    copy(
+                        &mut buf,
+                        |off, buf| fs.read_segment(&mut file, i, off, buf),
+                        |data| s.write(data),
+                    )?;
+
+                    s.finish().map_err(WalkError::Write)?;
+                }
+
+                w.finish().map_err(WalkError::Write)?;
+            }
+            NodeType::Symlink => {
+                let target = fs.read_link(&node).map_err(WalkError::Source)?;
+
+                part.symlink(&path, meta, &target)
+                    .map_err(WalkError::Write)?;
+            }
+            NodeType::CharDevice => part
+                .char_device(&path, meta, info.rdev)
+                .map_err(WalkError::Write)?,
+            NodeType::BlockDevice => part
+                .block_device(&path, meta, info.rdev)
+                .map_err(WalkError::Write)?,
+            NodeType::Fifo => part.fifo(&path, meta).map_err(WalkError::Write)?,
+            NodeType::Socket => part.socket(&path, meta).map_err(WalkError::Write)?,
+            NodeType::Other => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy data from `read` to `write` until `read` returns zero.
+fn copy<F, S>(
+    buf: &mut [u8],
+    mut read: impl FnMut(u64, &mut [u8]) -> Result<usize, F>,
+    mut write: impl FnMut(&[u8]) -> Result<(), S>,
+) -> Result<(), WalkError<F, S>> {
+    let mut off = 0;
+
+    loop {
+        let n = read(off, buf).map_err(WalkError::Source)?;
+
+        if n == 0 {
+            break Ok(());
+        }
+
+        write(&buf[..n]).map_err(WalkError::Write)?;
+        off += u64::try_from(n).unwrap();
+    }
+}
+
+/// Filesystem to write with [`walk()`].
+pub trait FsSource {
+    /// Reference to an item on the filesystem (e.g. a vnode).
+    type Node;
+    /// Opened regular file.
+    type File;
+    type Error;
+
+    fn root(&mut self) -> Result<Self::Node, Self::Error>;
+
+    fn stat(&mut self, node: &Self::Node) -> Result<NodeInfo, Self::Error>;
+
+    /// Append all children of `dir` to `out`, excluding `.` and `..`. Each child is its name and
+    /// the reference to it.
+    fn list(
+        &mut self,
+        dir: &Self::Node,
+        out: &mut Vec<(Vec<u8>, Self::Node)>,
+    ) -> Result<(), Self::Error>;
+
+    fn open(&mut self, node: &Self::Node) -> Result<Self::File, Self::Error>;
+
+    /// Read `file` at `off` into `buf`. Returns zero at the end of file.
+    fn read(
+        &mut self,
+        file: &mut Self::File,
+        off: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Self::Error>;
+
+    /// Returns the target of a symbolic link.
+    fn read_link(&mut self, node: &Self::Node) -> Result<Vec<u8>, Self::Error>;
+
+    /// Returns [`Some`] if `file` is a SELF that can be decrypted with [`Self::read_segment()`].
+    /// The default implementation always returns [`None`].
+    fn decrypt(&mut self, file: &mut Self::File) -> Result<Option<DecryptedSelf>, Self::Error> {
+        let _ = file;
+
+        Ok(None)
+    }
+
+    /// Read decrypted segment of `file` at `off` into `buf`. `index` is the index of the program
+    /// header from [`DecryptedSelf::segments`]. Returns zero at the end of the segment.
+    fn read_segment(
+        &mut self,
+        file: &mut Self::File,
+        index: u16,
+        off: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let _ = (file, index, off, buf);
+
+        Ok(0)
+    }
+}
+
+/// Headers of a SELF file returned from [`FsSource::decrypt()`].
+#[derive(Debug, Clone)]
+pub struct DecryptedSelf {
+    /// ELF header followed by program headers.
+    pub headers: Vec<u8>,
+    /// Index of the program headers that have decrypted data, in ascending order of their file
+    /// offset.
+    pub segments: Vec<u16>,
+}
+
+/// Information of an item returned from [`FsSource::stat()`].
+#[derive(Debug, Clone)]
+pub struct NodeInfo {
+    pub ty: NodeType,
+    pub meta: Metadata,
+    /// Number of hard links.
+    pub nlink: u64,
+    /// Identifier of the file that is unique within the filesystem (e.g. inode number).
+    pub id: u64,
+    /// Device number. Only used for [`NodeType::CharDevice`] and [`NodeType::BlockDevice`].
+    pub rdev: u64,
+}
+
+/// Type of item in [`FsSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeType {
+    Directory,
+    File,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Fifo,
+    Socket,
+    /// The item will be skipped (e.g. `VBAD`).
+    Other,
+}
+
+/// Represents an error when [`walk()`] fails.
+#[derive(Debug, Error)]
+pub enum WalkError<F, S> {
+    #[error("couldn't read the filesystem")]
+    Source(#[source] F),
+
+    #[error("couldn't write the dump")]
+    Write(#[source] S),
+}
@@ -0,0 +1,407 @@
+use super::{FileBlock, Metadata, PartItem, SegmentItem};
+use crate::crc32::Crc32;
+use crate::lz4::Encoder;
+use crate::{DumpItem, DumpSink, DumpWriter};
+use alloc::vec::Vec;
+
+/// Provides methods to write PlayStation 4 partition dump.
+///
+/// The entry in the table of contents for the partition contains filesystem type, device name,
+/// number of items and the items. Each item is its type, path, offset, [`Metadata`],
+/// [`FileBlock`], file size and the list of blocks. Each block is the offset of its header and its
+/// uncompressed size. Symbolic link and hard link also have their target at the end and device has
+/// its `rdev`.
+pub struct PartWriter<'a, S> {
+    dump: &'a mut DumpWriter<S>,
+    toc: usize,
+    count: usize,
+    items: u32,
+    lz4: Encoder,
+    buf: Vec<u8>,
+}
+
+impl<'a, S: DumpSink> PartWriter<'a, S> {
+    pub(crate) fn new(
+        dump: &'a mut DumpWriter<S>,
+        offset: u64,
+        fs: &[u8],
+        dev: &[u8],
+    ) -> Result<Self, S::Error> {
+        // Write filesystem type and device name.
+        dump.write_str(fs)?;
+        dump.write_str(dev)?;
+
+        // Add to table of contents.
+        let toc = dump.toc();
+        let entry = toc.begin(DumpItem::Ps4Part, offset);
+
+        toc.push_str(fs);
+        toc.push_str(dev);
+
+        let count = toc.len();
+
+        toc.push(&0u32.to_le_bytes());
+
+        Ok(Self {
+            dump,
+            toc: entry,
+            count,
+            items: 0,
+            lz4: Encoder::default(),
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn directory(&mut self, path: &[u8], meta: &Metadata) -> Result<(), S::Error> {
+        self.write_item(PartItem::Directory, path, meta)?;
+
+        // Directory has zero length and no blocks.
+        let toc = self.dump.toc();
+
+        toc.push(&[0]);
+        toc.push(&0u64.to_le_bytes());
+        toc.push(&0u32.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Write a symbolic link that points to `target`.
+    pub fn symlink(&mut self, path: &[u8], meta: &Metadata, target: &[u8]) -> Result<(), S::Error> {
+        self.write_link(PartItem::Symlink, path, meta, target)
+    }
+
+    /// Write a hard link to the file that was previously written at `target` in this partition.
+    pub fn hard_link(
+        &mut self,
+        path: &[u8],
+        meta: &Metadata,
+        target: &[u8],
+    ) -> Result<(), S::Error> {
+        self.write_link(PartItem::HardLink, path, meta, target)
+    }
+
+    /// Write a character device. `rdev` is the device number from `VOP_GETATTR`.
+    pub fn char_device(&mut self, path: &[u8], meta: &Metadata, rdev: u64) -> Result<(), S::Error> {
+        self.write_special(PartItem::CharDevice, path, meta, Some(rdev))
+    }
+
+    /// Write a block device. `rdev` is the device number from `VOP_GETATTR`.
+    pub fn block_device(
+        &mut self,
+        path: &[u8],
+        meta: &Metadata,
+        rdev: u64,
+    ) -> Result<(), S::Error> {
+        self.write_special(PartItem::BlockDevice, path, meta, Some(rdev))
+    }
+
+    pub fn fifo(&mut self, path: &[u8], meta: &Metadata) -> Result<(), S::Error> {
+        self.write_special(PartItem::Fifo, path, meta, None)
+    }
+
+    pub fn socket(&mut self, path: &[u8], meta: &Metadata) -> Result<(), S::Error> {
+        self.write_special(PartItem::Socket, path, meta, None)
+    }
+
+    /// Begin a new file. The returned [`FileWriter`] must be finished with
+    /// [`FileWriter::finish()`] before writing the next item.
+    ///
+    /// # Panics
+    /// If `ty` is [`FileBlock::Decrypted`]. Use [`Self::self_file()`] instead.
+    pub fn file(
+        &mut self,
+        path: &[u8],
+        meta: &Metadata,
+        ty: FileBlock,
+    ) -> Result<FileWriter<'_, S>, S::Error> {
+        assert_ne!(ty, FileBlock::Decrypted);
+
+        self.write_item(PartItem::File, path, meta)?;
+        self.dump.write(&[ty.into()])?;
+
+        Ok(self.begin_file(ty))
+    }
+
+    /// Begin a new SELF file that has decrypted segments. `ty` is the type of blocks for both the
+    /// SELF and its segments. The returned [`SelfWriter`] must be finished with
+    /// [`SelfWriter::decrypted()`] then [`SegmentWriter::finish()`] before writing the next item.
+    ///
+    /// # Panics
+    /// If `ty` is [`FileBlock::Decrypted`].
+    pub fn self_file(
+        &mut self,
+        path: &[u8],
+        meta: &Metadata,
+        ty: FileBlock,
+    ) -> Result<SelfWriter<'_, S>, S::Error> {
+        assert_ne!(ty, FileBlock::Decrypted);
+
+        self.write_item(PartItem::File, path, meta)?;
+        self.dump.write(&[FileBlock::Decrypted.into(), ty.into()])?;
+
+        Ok(SelfWriter {
+            file: self.begin_file(ty),
+        })
+    }
+
+    /// Write end entry.
+    pub fn finish(self) -> Result<(), S::Error> {
+        self.dump.write(&[PartItem::End.into()])?;
+
+        // Update table of contents.
+        let toc = self.dump.toc();
+
+        toc.patch(self.count, &self.items.to_le_bytes());
+        toc.end(self.toc);
+
+        Ok(())
+    }
+
+    fn begin_file(&mut self, ty: FileBlock) -> FileWriter<'_, S> {
+        // Reserve file length and number of blocks.
+        let toc = self.dump.toc();
+
+        toc.push(&[ty.into()]);
+
+        let pos = toc.len();
+
+        toc.push(&0u64.to_le_bytes());
+        toc.push(&0u32.to_le_bytes());
+
+        FileWriter {
+            dump: self.dump,
+            ty,
+            lz4: &mut self.lz4,
+            buf: &mut self.buf,
+            crc: Crc32::new(),
+            toc: Some(pos),
+            len: 0,
+            blocks: 0,
+        }
+    }
+
+    fn write_link(
+        &mut self,
+        ty: PartItem,
+        path: &[u8],
+        meta: &Metadata,
+        target: &[u8],
+    ) -> Result<(), S::Error> {
+        self.write_item(ty, path, meta)?;
+        self.dump.write_str(target)?;
+
+        // Link has zero length and no blocks.
+        let toc = self.dump.toc();
+
+        toc.push(&[0]);
+        toc.push(&0u64.to_le_bytes());
+        toc.push(&0u32.to_le_bytes());
+        toc.push_str(target);
+
+        Ok(())
+    }
+
+    fn write_special(
+        &mut self,
+        ty: PartItem,
+        path: &[u8],
+        meta: &Metadata,
+        rdev: Option<u64>,
+    ) -> Result<(), S::Error> {
+        self.write_item(ty, path, meta)?;
+
+        if let Some(v) = rdev {
+            self.dump.write(&v.to_le_bytes())?;
+        }
+
+        // Special file has zero length and no blocks.
+        let toc = self.dump.toc();
+
+        toc.push(&[0]);
+        toc.push(&0u64.to_le_bytes());
+        toc.push(&0u32.to_le_bytes());
+
+        if let Some(v) = rdev {
+            toc.push(&v.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    fn write_item(&mut self, ty: PartItem, path: &[u8], meta: &Metadata) -> Result<(), S::Error> {
+        let offset = self.dump.offset();
+        let meta = meta.encode();
+
+        self.dump.write(&[ty.into()])?;
+        self.dump.write_str(path)?;
+        self.dump.write(&meta)?;
+        self.dump.add_item();
+
+        // Add to table of contents.
+        let toc = self.dump.toc();
+
+        toc.push(&[ty.into()]);
+        toc.push_str(path);
+        toc.push(&offset.to_le_bytes());
+        toc.push(&meta);
+
+        self.items += 1;
+
+        Ok(())
+    }
+}
+
+/// Provides methods to write content of a file.
+#[must_use]
+pub struct FileWriter<'a, S> {
+    dump: &'a mut DumpWriter<S>,
+    ty: FileBlock,
+    lz4: &'a mut Encoder,
+    buf: &'a mut Vec<u8>,
+    crc: Crc32,
+    toc: Option<usize>,
+    len: u64,
+    blocks: u32,
+}
+
+impl<S: DumpSink> FileWriter<'_, S> {
+    /// Maximum size of data in each block.
+    pub const BLOCK_SIZE: usize = 0xFFFF;
+
+    /// Append `data` to the file. Data larger than [`Self::BLOCK_SIZE`] will be split into
+    /// multiple blocks.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), S::Error> {
+        for b in data.chunks(Self::BLOCK_SIZE) {
+            let offset = self.dump.offset();
+            let len: u16 = b.len().try_into().unwrap();
+
+            self.crc.update(b);
+
+            match self.ty {
+                FileBlock::Uncompressed => {
+                    self.dump.write(&len.to_le_bytes())?;
+                    self.dump.write(b)?;
+                }
+                FileBlock::Sparse if b.iter().all(|&v| v == 0) => {
+                    self.dump.write(&len.to_le_bytes())?;
+                    self.dump.write(&0u16.to_le_bytes())?;
+                }
+                FileBlock::Compressed | FileBlock::Sparse => {
+                    // Store as-is if the data is not compressible.
+                    self.buf.clear();
+                    self.lz4.compress(b, self.buf);
+
+                    let data = if self.buf.len() < b.len() {
+                        self.buf.as_slice()
+                    } else {
+                        b
+                    };
+                    let stored: u16 = data.len().try_into().unwrap();
+
+                    self.dump.write(&len.to_le_bytes())?;
+                    self.dump.write(&stored.to_le_bytes())?;
+                    self.dump.write(data)?;
+                }
+                FileBlock::Decrypted => unreachable!(),
+            }
+
+            // Add to table of contents.
+            if self.toc.is_some() {
+                let toc = self.dump.toc();
+
+                toc.push(&offset.to_le_bytes());
+                toc.push(&u32::from(len).to_le_bytes());
+            }
+
+            self.len += u64::from(len);
+            self.blocks += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Write empty block to mark the end of file followed by CRC-32 of the content.
+    pub fn finish(mut self) -> Result<(), S::Error> {
+        self.end()
+    }
+
+    fn end(&mut self) -> Result<(), S::Error> {
+        self.dump.write(&0u16.to_le_bytes())?;
+        self.dump.write(&self.crc.finish().to_le_bytes())?;
+
+        // Update table of contents.
+        if let Some(off) = self.toc {
+            let toc = self.dump.toc();
+
+            toc.patch(off, &self.len.to_le_bytes());
+            toc.patch(off + 8, &self.blocks.to_le_bytes());
+        }
+
+        Ok(())
+    }
+}
+
+/// Provides methods to write content of a SELF file that has decrypted segments.
+#[must_use]
+pub struct SelfWriter<'a, S> {
+    file: FileWriter<'a, S>,
+}
+
+impl<'a, S: DumpSink> SelfWriter<'a, S> {
+    /// Append `data` to the SELF. See [`FileWriter::write()`] for more details.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), S::Error> {
+        self.file.write(data)
+    }
+
+    /// Finish the SELF and begin its decrypted segments. `headers` is the ELF header followed by
+    /// the program headers that were embedded in the SELF.
+    pub fn decrypted(mut self, headers: &[u8]) -> Result<SegmentWriter<'a, S>, S::Error> {
+        self.file.end()?;
+
+        let f = self.file;
+
+        f.dump.write_str(headers)?;
+
+        Ok(SegmentWriter {
+            dump: f.dump,
+            ty: f.ty,
+            lz4: f.lz4,
+            buf: f.buf,
+        })
+    }
+}
+
+/// Provides methods to write decrypted segments of a SELF file.
+#[must_use]
+pub struct SegmentWriter<'a, S> {
+    dump: &'a mut DumpWriter<S>,
+    ty: FileBlock,
+    lz4: &'a mut Encoder,
+    buf: &'a mut Vec<u8>,
+}
+
+impl<S: DumpSink> SegmentWriter<'_, S> {
+    /// Begin a decrypted segment for the program header at `index`. Segments must be written in
+    /// ascending order of their file offset. The returned [`FileWriter`] must be finished with
+    /// [`FileWriter::finish()`] before writing the next segment.
+    pub fn segment(&mut self, index: u16) -> Result<FileWriter<'_, S>, S::Error> {
+        self.dump.write(&[SegmentItem::Segment.into()])?;
+        self.dump.write(&index.to_le_bytes())?;
+
+        Ok(FileWriter {
+            dump: self.dump,
+            ty: self.ty,
+            lz4: self.lz4,
+            buf: self.buf,
+            crc: Crc32::new(),
+            toc: None,
+            len: 0,
+            blocks: 0,
+        })
+    }
+
+    /// Write end entry of the segment list.
+    pub fn finish(self) -> Result<(), S::Error> {
+        self.dump.write(&[SegmentItem::End.into()])
+    }
+}
@@ -1,14 +1,17 @@
-use crate::{DumpItem, MAGIC};
+use crate::{DumpItem, MAGIC, TOC_MAGIC};
 use core::error::Error;
 use core::fmt::{Display, Formatter};
 use std::boxed::Box;
 use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::vec::Vec;
 use thiserror::Error;
 
 /// Provides methods to read a firmware dump.
 pub struct DumpReader<F> {
     file: F,
     items: u32,
+    toc: Option<u64>,
+    next: Option<u64>,
 }
 
 impl<F: Read + Seek> DumpReader<F> {
@@ -27,16 +30,50 @@ impl<F: Read + Seek> DumpReader<F> {
 
         // Read item count.
         let mut items = [0u8; 4];
-
-        file.seek(SeekFrom::End(-4))
+        let len = file
+            .seek(SeekFrom::End(-4))
             .map_err(ReaderError::SeekItemCount)?;
+
         file.read_exact(&mut items).map_err(ReaderError::Read)?;
+
+        // Check if we have table of contents.
+        let toc = if len >= 4 + 1 + 12 {
+            let mut footer = [0u8; 12];
+
+            file.seek(SeekFrom::End(-16))
+                .map_err(ReaderError::SeekFooter)?;
+            file.read_exact(&mut footer).map_err(ReaderError::Read)?;
+
+            if footer[8..] == *TOC_MAGIC {
+                Some(u64::from_le_bytes(footer[..8].try_into().unwrap()))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
         file.seek(SeekFrom::Start(4))
             .map_err(ReaderError::SeekFirstItem)?;
 
         Ok(Self {
             file,
             items: u32::from_le_bytes(items),
+            toc,
+            next: None,
+        })
+    }
+
+    /// Creates a reader that reads the item at `offset` without checking the dump header.
+    pub(crate) fn at(mut file: F, offset: u64) -> Result<Self, ReaderError> {
+        file.seek(SeekFrom::Start(offset))
+            .map_err(ReaderError::SeekNextItem)?;
+
+        Ok(Self {
+            file,
+            items: 0,
+            toc: None,
+            next: None,
         })
     }
 
@@ -45,7 +82,19 @@ impl<F: Read + Seek> DumpReader<F> {
         self.items
     }
 
-    pub fn next_item(&mut self) -> Result<Option<ItemReader<F>>, ReaderError> {
+    /// Returns offset of the table of contents. Returns [`None`] if the dump does not have it.
+    pub fn toc(&self) -> Option<u64> {
+        self.toc
+    }
+
+    pub fn next_item(&mut self) -> Result<Option<ItemReader<'_, F>>, ReaderError> {
+        // Seek to the end of previous item if it can be read in any order.
+        if let Some(v) = self.next.take() {
+            self.file
+                .seek(SeekFrom::Start(v))
+                .map_err(ReaderError::SeekNextItem)?;
+        }
+
         // Read item type.
         let mut ty = 0u8;
 
@@ -68,22 +117,63 @@ impl<F: Read + Seek> DumpReader<F> {
                 Ok(v) => ItemReader::Ps4Part(v),
                 Err(e) => return Err(ReaderError::ItemReader(ty, Box::new(e))),
             },
+            DumpItem::SystemInfo => match crate::SystemInfo::read(&mut self.file, ver) {
+                Ok(v) => ItemReader::SystemInfo(v),
+                Err(e) => return Err(ReaderError::ItemReader(ty, Box::new(e))),
+            },
+            DumpItem::Kernel => match crate::kernel::Kernel::read(&mut self.file, ver) {
+                Ok(v) => ItemReader::Kernel(v),
+                Err(e) => return Err(ReaderError::ItemReader(ty, Box::new(e))),
+            },
+            DumpItem::Syscalls => match crate::SyscallTable::read(&mut self.file, ver) {
+                Ok(v) => ItemReader::Syscalls(v),
+                Err(e) => return Err(ReaderError::ItemReader(ty, Box::new(e))),
+            },
+            DumpItem::Modules => match crate::kernel::Module::read_list(&mut self.file, ver) {
+                Ok(v) => ItemReader::Modules(v),
+                Err(e) => return Err(ReaderError::ItemReader(ty, Box::new(e))),
+            },
+            DumpItem::RawDevice => {
+                let offset = self.file.stream_position().map_err(ReaderError::Read)? - 2;
+
+                match crate::device::RawDeviceReader::new(&mut self.file, offset, ver) {
+                    Ok(v) => {
+                        self.next = Some(v.index().end());
+                        ItemReader::RawDevice(v)
+                    }
+                    Err(e) => return Err(ReaderError::ItemReader(ty, Box::new(e))),
+                }
+            }
         };
 
         Ok(Some(r))
     }
+
+    pub(crate) fn file(&mut self) -> &mut F {
+        &mut self.file
+    }
 }
 
 /// Encapsulates a reader for dump item.
 #[derive(Debug)]
 pub enum ItemReader<'a, F> {
     Ps4Part(crate::ps4::PartReader<'a, F>),
+    SystemInfo(crate::SystemInfo),
+    Kernel(crate::kernel::Kernel),
+    Syscalls(crate::SyscallTable),
+    Modules(Vec<crate::kernel::Module>),
+    RawDevice(crate::device::RawDeviceReader<'a, F>),
 }
 
 impl<F> Display for ItemReader<'_, F> {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         let name = match self {
             Self::Ps4Part(_) => "PlayStation 4 partition",
+            Self::SystemInfo(_) => "system information",
+            Self::Kernel(_) => "kernel image",
+            Self::Syscalls(_) => "syscall table",
+            Self::Modules(_) => "kernel modules",
+            Self::RawDevice(_) => "raw device",
         };
 
         f.write_str(name)
@@ -102,9 +192,15 @@ pub enum ReaderError {
     #[error("couldn't seek to item count")]
     SeekItemCount(#[source] std::io::Error),
 
+    #[error("couldn't seek to table of contents footer")]
+    SeekFooter(#[source] std::io::Error),
+
     #[error("couldn't seek to first item")]
     SeekFirstItem(#[source] std::io::Error),
 
+    #[error("couldn't seek to next item")]
+    SeekNextItem(#[source] std::io::Error),
+
     #[error("unknown item type {0}")]
     UnknownItem(u8),
 
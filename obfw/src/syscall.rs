@@ -0,0 +1,122 @@
+use alloc::vec::Vec;
+
+/// Content of the kernel `sysent` table.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyscallTable {
+    pub entries: Vec<Syscall>,
+}
+
+impl SyscallTable {
+    #[cfg(feature = "write")]
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let count: u32 = self.entries.len().try_into().unwrap();
+
+        data.extend_from_slice(&count.to_le_bytes());
+
+        for e in &self.entries {
+            let len: u64 = e.name.len().try_into().unwrap();
+
+            data.extend_from_slice(&e.number.to_le_bytes());
+            data.extend_from_slice(&e.narg.to_le_bytes());
+            data.extend_from_slice(&e.handler.to_le_bytes());
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(&e.name);
+        }
+
+        data
+    }
+
+    #[cfg(feature = "read")]
+    pub(crate) fn read<R: std::io::Read>(r: &mut R, ver: u8) -> std::io::Result<Self> {
+        use std::io::{Error, ErrorKind, Read};
+
+        if ver != 1 {
+            return Err(Error::new(ErrorKind::InvalidData, "unknown item version"));
+        }
+
+        // Read entries.
+        let mut count = [0u8; 4];
+
+        r.read_exact(&mut count)?;
+
+        let count = u32::from_le_bytes(count);
+        let mut entries = Vec::new();
+
+        for _ in 0..count {
+            let mut hdr = [0u8; 24];
+            let mut name = Vec::new();
+
+            r.read_exact(&mut hdr)?;
+
+            let len = u64::from_le_bytes(hdr[16..].try_into().unwrap());
+
+            if r.by_ref().take(len).read_to_end(&mut name)? as u64 != len {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+
+            entries.push(Syscall {
+                number: u32::from_le_bytes(hdr[..4].try_into().unwrap()),
+                narg: u32::from_le_bytes(hdr[4..8].try_into().unwrap()),
+                handler: u64::from_le_bytes(hdr[8..16].try_into().unwrap()),
+                name,
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Write the table as a JSON array. Each entry is an object with `number`, `name`, `narg` and
+    /// `handler`. The handler is a hexadecimal string since JSON number cannot represent all kernel
+    /// addresses. The name is `null` if it is not available.
+    #[cfg(feature = "read")]
+    pub fn write_json<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_all(b"[")?;
+
+        for (i, e) in self.entries.iter().enumerate() {
+            if i != 0 {
+                w.write_all(b",")?;
+            }
+
+            write!(w, "\n  {{\"number\": {}, \"name\": ", e.number)?;
+
+            if e.name.is_empty() {
+                w.write_all(b"null")?;
+            } else {
+                w.write_all(b"\"")?;
+
+                // Names are not guaranteed to be UTF-8 so each non-ASCII byte is one character.
+                for &b in &e.name {
+                    match b {
+                        b'"' => w.write_all(b"\\\"")?,
+                        b'\\' => w.write_all(b"\\\\")?,
+                        0x20..0x7F => w.write_all(&[b])?,
+                        b => write!(w, "\\u{b:04x}")?,
+                    }
+                }
+
+                w.write_all(b"\"")?;
+            }
+
+            write!(
+                w,
+                ", \"narg\": {}, \"handler\": \"{:#x}\"}}",
+                e.narg, e.handler
+            )?;
+        }
+
+        w.write_all(b"\n]\n")
+    }
+}
+
+/// An entry in [`SyscallTable`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Syscall {
+    pub number: u32,
+    /// Number of arguments.
+    pub narg: u32,
+    /// Address of the handler.
+    pub handler: u64,
+    /// Name from the syscall name table. Empty if the kernel does not have it.
+    pub name: Vec<u8>,
+}
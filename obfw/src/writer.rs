@@ -0,0 +1,231 @@
+use crate::{DumpItem, MAGIC, TOC_MAGIC};
+use alloc::vec::Vec;
+
+/// Destination of the data produced by [`DumpWriter`].
+pub trait DumpSink {
+    type Error;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "std")]
+impl<T: std::io::Write> DumpSink for T {
+    type Error = std::io::Error;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.write_all(data)
+    }
+}
+
+/// Provides methods to write a firmware dump.
+///
+/// The table of contents is kept in memory until [`DumpWriter::finish()`] so memory usage will
+/// grow with the number of items.
+pub struct DumpWriter<S> {
+    sink: S,
+    items: u32,
+    offset: u64,
+    toc: Toc,
+}
+
+impl<S: DumpSink> DumpWriter<S> {
+    pub fn new(mut sink: S) -> Result<Self, S::Error> {
+        sink.write(MAGIC)?;
+
+        Ok(Self {
+            sink,
+            items: 0,
+            offset: MAGIC.len().try_into().unwrap(),
+            toc: Toc(Vec::new()),
+        })
+    }
+
+    /// Returns total items that was written so far, including nested items.
+    pub fn items(&self) -> u32 {
+        self.items
+    }
+
+    /// Write information of the system that produced the dump. This should be the first item.
+    pub fn system_info(&mut self, info: &crate::SystemInfo) -> Result<(), S::Error> {
+        let offset = self.offset;
+        let data = info.encode();
+
+        self.write_item(DumpItem::SystemInfo, 1)?;
+        self.write(&data)?;
+
+        // The entry data is the same as the item data.
+        let entry = self.toc.begin(DumpItem::SystemInfo, offset);
+
+        self.toc.push(&data);
+        self.toc.end(entry);
+
+        Ok(())
+    }
+
+    /// Write the kernel `sysent` table.
+    pub fn syscalls(&mut self, table: &crate::SyscallTable) -> Result<(), S::Error> {
+        self.begin_item(DumpItem::Syscalls, 1)?;
+        self.write(&table.encode())
+    }
+
+    /// Begin an image of the running kernel. `base` is the virtual address of its ELF header. The
+    /// returned [`KernelWriter`] must be finished with [`KernelWriter::finish()`] before writing
+    /// the next item.
+    ///
+    /// [`KernelWriter`]: crate::kernel::KernelWriter
+    /// [`KernelWriter::finish()`]: crate::kernel::KernelWriter::finish()
+    pub fn kernel(&mut self, base: u64) -> Result<crate::kernel::KernelWriter<'_, S>, S::Error> {
+        self.begin_item(DumpItem::Kernel, 1)?;
+
+        crate::kernel::KernelWriter::new(self, base)
+    }
+
+    /// Begin the list of loaded kernel modules. The returned [`ModuleWriter`] must be finished with
+    /// [`ModuleWriter::finish()`] before writing the next item.
+    ///
+    /// [`ModuleWriter`]: crate::kernel::ModuleWriter
+    /// [`ModuleWriter::finish()`]: crate::kernel::ModuleWriter::finish()
+    pub fn modules(&mut self) -> Result<crate::kernel::ModuleWriter<'_, S>, S::Error> {
+        self.begin_item(DumpItem::Modules, 1)?;
+
+        Ok(crate::kernel::ModuleWriter::new(self))
+    }
+
+    /// Begin content of a raw device. `name` is the path of the device (e.g. `/dev/sflash0`) and
+    /// `size` is its size reported by the kernel. The returned [`RawDeviceWriter`] must be
+    /// finished with [`RawDeviceWriter::finish()`] before writing the next item.
+    ///
+    /// [`RawDeviceWriter`]: crate::device::RawDeviceWriter
+    /// [`RawDeviceWriter::finish()`]: crate::device::RawDeviceWriter::finish()
+    pub fn raw_device(
+        &mut self,
+        name: &[u8],
+        size: u64,
+    ) -> Result<crate::device::RawDeviceWriter<'_, S>, S::Error> {
+        let offset = self.offset;
+
+        self.write_item(DumpItem::RawDevice, 1)?;
+
+        crate::device::RawDeviceWriter::new(self, offset, name, size)
+    }
+
+    /// Begin a new PlayStation 4 partition. The returned [`PartWriter`] must be finished with
+    /// [`PartWriter::finish()`] before writing the next item.
+    ///
+    /// [`PartWriter`]: crate::ps4::PartWriter
+    /// [`PartWriter::finish()`]: crate::ps4::PartWriter::finish()
+    pub fn ps4_part(
+        &mut self,
+        fs: &[u8],
+        dev: &[u8],
+    ) -> Result<crate::ps4::PartWriter<'_, S>, S::Error> {
+        let offset = self.offset;
+
+        self.write_item(DumpItem::Ps4Part, 2)?;
+
+        crate::ps4::PartWriter::new(self, offset, fs, dev)
+    }
+
+    /// Write end entry, table of contents and the total number of items then return the
+    /// underlying sink.
+    pub fn finish(mut self) -> Result<S, S::Error> {
+        self.write(&[DumpItem::End.into()])?;
+
+        // Write table of contents.
+        let offset = self.offset;
+        let mut toc = core::mem::take(&mut self.toc.0);
+
+        toc.push(DumpItem::End.into());
+        toc.extend_from_slice(&offset.to_le_bytes());
+        toc.extend_from_slice(TOC_MAGIC);
+        toc.extend_from_slice(&self.items.to_le_bytes());
+
+        self.write(&toc)?;
+
+        Ok(self.sink)
+    }
+
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8]) -> Result<(), S::Error> {
+        self.sink.write(data)?;
+        self.offset += u64::try_from(data.len()).unwrap();
+
+        Ok(())
+    }
+
+    pub(crate) fn write_str(&mut self, data: &[u8]) -> Result<(), S::Error> {
+        let len: u64 = data.len().try_into().unwrap();
+
+        self.write(&len.to_le_bytes())?;
+        self.write(data)
+    }
+
+    pub(crate) fn add_item(&mut self) {
+        self.items += 1;
+    }
+
+    pub(crate) fn toc(&mut self) -> &mut Toc {
+        &mut self.toc
+    }
+
+    /// Same as [`Self::write_item()`] but also add an entry without data to the table of
+    /// contents.
+    fn begin_item(&mut self, ty: DumpItem, ver: u8) -> Result<(), S::Error> {
+        let entry = self.toc.begin(ty, self.offset);
+
+        self.toc.end(entry);
+        self.write_item(ty, ver)
+    }
+
+    fn write_item(&mut self, ty: DumpItem, ver: u8) -> Result<(), S::Error> {
+        self.write(&[ty.into(), ver])?;
+        self.add_item();
+
+        Ok(())
+    }
+}
+
+/// Table of contents that is being built by [`DumpWriter`].
+///
+/// Each entry is a type of top-level item followed by its offset in the dump, the length of the
+/// entry data and the data.
+pub(crate) struct Toc(Vec<u8>);
+
+impl Toc {
+    /// Returns the current length, which can be used with [`Self::patch()`].
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Begin a new entry. The returned value must be passed to [`Self::end()`].
+    pub fn begin(&mut self, ty: DumpItem, offset: u64) -> usize {
+        self.0.push(ty.into());
+        self.0.extend_from_slice(&offset.to_le_bytes());
+        self.0.extend_from_slice(&0u64.to_le_bytes());
+        self.0.len()
+    }
+
+    pub fn end(&mut self, entry: usize) {
+        let len: u64 = (self.0.len() - entry).try_into().unwrap();
+
+        self.patch(entry - 8, &len.to_le_bytes());
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.0.extend_from_slice(data);
+    }
+
+    pub fn push_str(&mut self, data: &[u8]) {
+        let len: u64 = data.len().try_into().unwrap();
+
+        self.0.extend_from_slice(&len.to_le_bytes());
+        self.0.extend_from_slice(data);
+    }
+
+    pub fn patch(&mut self, off: usize, data: &[u8]) {
+        self.0[off..(off + data.len())].copy_from_slice(data);
+    }
+}
@@ -0,0 +1,232 @@
+use obfw::ps4::{FileBlock, Metadata, PartItem, Timespec};
+use obfw::DumpWriter;
+use proptest::collection::{btree_map, vec};
+use proptest::prelude::*;
+use std::collections::{BTreeMap, VecDeque};
+
+pub fn write(dump: &[Part], chunk: usize, block: FileBlock) -> Vec<u8> {
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+
+    for p in dump {
+        let mut pw = w.ps4_part(&p.fs, &p.dev).unwrap();
+
+        for i in &p.items {
+            match i {
+                Item::Directory(path, meta) => pw.directory(path, meta).unwrap(),
+                Item::Symlink(path, meta, target) => pw.symlink(path, meta, target).unwrap(),
+                Item::HardLink(path, meta, target) => pw.hard_link(path, meta, target).unwrap(),
+                Item::Special(path, meta, ty, rdev) => match ty {
+                    PartItem::CharDevice => pw.char_device(path, meta, *rdev).unwrap(),
+                    PartItem::BlockDevice => pw.block_device(path, meta, *rdev).unwrap(),
+                    PartItem::Fifo => pw.fifo(path, meta).unwrap(),
+                    PartItem::Socket => pw.socket(path, meta).unwrap(),
+                    _ => unreachable!(),
+                },
+                Item::File(path, meta, data) => {
+                    let mut f = pw.file(path, meta, block).unwrap();
+
+                    for c in data.chunks(chunk) {
+                        f.write(c).unwrap();
+                    }
+
+                    f.finish().unwrap();
+                }
+            }
+        }
+
+        pw.finish().unwrap();
+    }
+
+    w.finish().unwrap()
+}
+
+pub fn block() -> impl Strategy<Value = FileBlock> {
+    prop_oneof![
+        Just(FileBlock::Uncompressed),
+        Just(FileBlock::Compressed),
+        Just(FileBlock::Sparse)
+    ]
+}
+
+pub fn dump() -> impl Strategy<Value = Vec<Part>> {
+    vec(part(), 0..4)
+}
+
+pub fn part() -> impl Strategy<Value = Part> {
+    let name = vec(any::<u8>(), 0..24);
+
+    (name.clone(), name, tree()).prop_map(|(fs, dev, root)| {
+        // Flatten the same way the payload does.
+        let mut items = Vec::new();
+        let mut files = Vec::new();
+        let mut pending = VecDeque::from([(Vec::new(), root)]);
+
+        while let Some((path, node)) = pending.pop_front() {
+            match node {
+                Node::Directory(meta, children) => {
+                    for (name, node) in children {
+                        let mut path = path.clone();
+
+                        path.push(b'/');
+                        path.extend_from_slice(&name);
+                        pending.push_back((path, node));
+                    }
+
+                    items.push(Item::Directory(path, meta));
+                }
+                Node::File(meta, data) => {
+                    files.push(path.clone());
+                    items.push(Item::File(path, meta, data));
+                }
+                Node::Symlink(meta, target) => items.push(Item::Symlink(path, meta, target)),
+                Node::Special(meta, ty, rdev) => items.push(Item::Special(path, meta, ty, rdev)),
+                Node::HardLink(meta, target) => {
+                    // Hard link can only point to the file that come before it.
+                    if !files.is_empty() {
+                        let target = target.get(&files).clone();
+
+                        items.push(Item::HardLink(path, meta, target));
+                    }
+                }
+            }
+        }
+
+        Part { fs, dev, items }
+    })
+}
+
+pub fn tree() -> impl Strategy<Value = Node> {
+    let name = vec(
+        any::<u8>().prop_filter("not a separator", |&b| b != b'/'),
+        1..16,
+    );
+    let len = prop_oneof![
+        Just(0),
+        Just(0xFFFF),
+        Just(0x10000),
+        Just(0xFFFF * 3),
+        0..0x100usize,
+        0..0x30000usize,
+    ];
+    let file = (meta(), len, any::<u64>(), 0..3).prop_map(|(meta, len, seed, kind)| {
+        let data = match kind {
+            0 => fill(len, seed),
+            1 => text(len, seed),
+            _ => sparse(len, seed),
+        };
+
+        Node::File(meta, data)
+    });
+    let symlink =
+        (meta(), vec(any::<u8>(), 0..64)).prop_map(|(meta, target)| Node::Symlink(meta, target));
+    let special = (meta(), special()).prop_map(|(meta, (ty, rdev))| Node::Special(meta, ty, rdev));
+    let hard_link =
+        (meta(), any::<prop::sample::Index>()).prop_map(|(meta, i)| Node::HardLink(meta, i));
+    let leaf = prop_oneof![8 => file, 2 => symlink, 1 => special, 1 => hard_link];
+
+    leaf.prop_recursive(4, 32, 8, move |inner| {
+        (meta(), btree_map(name.clone(), inner, 0..8))
+            .prop_map(|(meta, children)| Node::Directory(meta, children))
+    })
+    .prop_map(|n| match n {
+        Node::Directory(..) => n,
+        Node::File(..) | Node::Symlink(..) | Node::Special(..) | Node::HardLink(..) => {
+            Node::Directory(Metadata::default(), BTreeMap::from([(b"f".to_vec(), n)]))
+        }
+    })
+}
+
+/// Returns the type of special file and its device number, which is zero for non-device.
+pub fn special() -> impl Strategy<Value = (PartItem, u64)> {
+    prop_oneof![
+        any::<u64>().prop_map(|v| (PartItem::CharDevice, v)),
+        any::<u64>().prop_map(|v| (PartItem::BlockDevice, v)),
+        Just((PartItem::Fifo, 0)),
+        Just((PartItem::Socket, 0)),
+    ]
+}
+
+pub fn meta() -> impl Strategy<Value = Metadata> {
+    let time = (any::<i64>(), 0..1_000_000_000u32).prop_map(|(sec, nsec)| Timespec { sec, nsec });
+    let ids = (
+        any::<u16>(),
+        any::<u32>(),
+        any::<u32>(),
+        any::<u64>(),
+        any::<u64>(),
+    );
+    let times = (time.clone(), time.clone(), time.clone(), time);
+
+    (ids, times).prop_map(
+        |((mode, uid, gid, flags, size), (atime, mtime, ctime, birthtime))| Metadata {
+            mode,
+            uid,
+            gid,
+            flags,
+            size,
+            atime,
+            mtime,
+            ctime,
+            birthtime,
+        },
+    )
+}
+
+pub fn fill(len: usize, seed: u64) -> Vec<u8> {
+    // Use xorshift instead of proptest for speed.
+    let mut s = seed | 1;
+
+    (0..len)
+        .map(|_| {
+            s ^= s << 13;
+            s ^= s >> 7;
+            s ^= s << 17;
+            s as u8
+        })
+        .collect()
+}
+
+/// Same as [`fill()`] but the result can be compressed.
+pub fn text(len: usize, seed: u64) -> Vec<u8> {
+    fill(len, seed)
+        .into_iter()
+        .map(|b| b"\0\0\0\0abc\n"[usize::from(b & 7)])
+        .collect()
+}
+
+/// Same as [`fill()`] but most of the result is zeros.
+pub fn sparse(len: usize, seed: u64) -> Vec<u8> {
+    let mut data = vec![0; len];
+
+    for (i, b) in fill(len / 0x8000, seed).into_iter().enumerate() {
+        data[i * 0x8000 + usize::from(b)] = b | 1;
+    }
+
+    data
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Part {
+    pub fs: Vec<u8>,
+    pub dev: Vec<u8>,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Directory(Vec<u8>, Metadata),
+    File(Vec<u8>, Metadata, Vec<u8>),
+    Symlink(Vec<u8>, Metadata, Vec<u8>),
+    Special(Vec<u8>, Metadata, PartItem, u64),
+    HardLink(Vec<u8>, Metadata, Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Directory(Metadata, BTreeMap<Vec<u8>, Node>),
+    File(Metadata, Vec<u8>),
+    Symlink(Metadata, Vec<u8>),
+    Special(Metadata, PartItem, u64),
+    /// The target is selected from the files that come before this node when flattening.
+    HardLink(Metadata, prop::sample::Index),
+}
@@ -0,0 +1,381 @@
+use self::common::{block, dump, fill, write, Item, Part};
+use obfw::kernel::{Kernel, Module, Segment, PF_R, PF_X};
+use obfw::ps4::{BlockCache, FileBlock, Metadata, PartItem, Timespec};
+use obfw::{
+    DumpIndex, DumpItem, DumpReader, DumpWriter, IndexError, Syscall, SyscallTable, SystemInfo,
+};
+use proptest::prelude::*;
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+mod common;
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn random_access(mut dump in dump(), block in block(), reads in prop::collection::vec((any::<u64>(), 1usize..0x30000), 1..16)) {
+        // Make device names unique.
+        for (i, p) in dump.iter_mut().enumerate() {
+            p.dev.insert(0, i as u8);
+        }
+
+        let data = write(&dump, 0x8000, block);
+        let mut index = DumpIndex::new(Cursor::new(data.clone())).unwrap();
+        let scan = DumpIndex::scan(Cursor::new(data)).unwrap();
+
+        prop_assert_eq!(index.parts().len(), dump.len());
+
+        for (a, b) in index.parts().iter().zip(scan.parts()) {
+            prop_assert_eq!(a.offset(), b.offset());
+            prop_assert_eq!(format!("{:?}", a.items()), format!("{:?}", b.items()));
+        }
+
+        for p in &dump {
+            let indexed = index.part(&p.dev).unwrap();
+
+            prop_assert_eq!(indexed.fs(), p.fs.as_slice());
+            prop_assert_eq!(indexed.items().len(), p.items.len());
+
+            for i in &p.items {
+                let (path, meta, data) = match i {
+                    Item::Directory(path, meta) => (path, meta, None),
+                    Item::File(path, meta, data) => (path, meta, Some(data)),
+                    Item::Symlink(path, meta, target) | Item::HardLink(path, meta, target) => {
+                        let item = index.part(&p.dev).unwrap().get(path).unwrap();
+                        let ty = match i {
+                            Item::Symlink(..) => PartItem::Symlink,
+                            _ => PartItem::HardLink,
+                        };
+
+                        prop_assert_eq!(item.ty(), ty);
+                        prop_assert_eq!(item.metadata(), Some(meta));
+                        prop_assert_eq!(item.target(), Some(target.as_slice()));
+                        continue;
+                    }
+                    Item::Special(path, meta, ty, rdev) => {
+                        let item = index.part(&p.dev).unwrap().get(path).unwrap();
+                        let expected = match ty {
+                            PartItem::CharDevice | PartItem::BlockDevice => Some(*rdev),
+                            _ => None,
+                        };
+
+                        prop_assert_eq!(item.ty(), *ty);
+                        prop_assert_eq!(item.metadata(), Some(meta));
+                        prop_assert_eq!(item.rdev(), expected);
+                        continue;
+                    }
+                };
+                let item = index.part(&p.dev).unwrap().get(path).unwrap();
+
+                prop_assert_eq!(item.metadata(), Some(meta));
+
+                let data = match data {
+                    Some(v) => v,
+                    None => {
+                        prop_assert_eq!(item.ty(), PartItem::Directory);
+                        continue;
+                    }
+                };
+
+                prop_assert_eq!(item.len(), data.len() as u64);
+
+                let mut f = index.open_file(&p.dev, path).unwrap();
+
+                for &(off, len) in &reads {
+                    let off = if data.is_empty() { 0 } else { off % data.len() as u64 };
+                    let end = std::cmp::min(data.len(), off as usize + len);
+                    let mut buf = Vec::new();
+
+                    prop_assert_eq!(f.seek(SeekFrom::Start(off)).unwrap(), off);
+                    f.by_ref().take(len as u64).read_to_end(&mut buf).unwrap();
+                    prop_assert_eq!(buf.as_slice(), &data[off as usize..end]);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn without_toc() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::Directory(b"".to_vec(), Metadata::default()),
+            Item::File(b"/a".to_vec(), Metadata::default(), fill(0x20000, 1)),
+        ],
+    }];
+    let mut data = write(&dump, usize::MAX, FileBlock::Uncompressed);
+    let toc = DumpReader::new(Cursor::new(&data)).unwrap().toc().unwrap();
+    let items = data.split_off(data.len() - 4);
+
+    data.truncate(toc as usize);
+    data.extend_from_slice(&items);
+
+    let mut index = DumpIndex::new(Cursor::new(data)).unwrap();
+    let mut buf = Vec::new();
+
+    index
+        .open_file(b"/dev/da0x4.crypt", b"/a")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+
+    assert_eq!(buf, fill(0x20000, 1));
+}
+
+#[test]
+fn offsets() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::Directory(b"".to_vec(), Metadata::default()),
+            Item::File(b"/a".to_vec(), Metadata::default(), fill(0x10000, 0)),
+            Item::File(b"/b".to_vec(), Metadata::default(), Vec::new()),
+        ],
+    }];
+    let index = DumpIndex::new(Cursor::new(write(
+        &dump,
+        usize::MAX,
+        FileBlock::Uncompressed,
+    )))
+    .unwrap();
+    let part = &index.parts()[0];
+    let items = part.items();
+
+    assert_eq!(index.items(), 4);
+    assert_eq!(part.offset(), 4);
+    assert_eq!(items[0].offset(), 4 + 2 + 8 + 3 + 8 + 16);
+    assert_eq!(items[1].offset(), items[0].offset() + 1 + 8 + 74);
+
+    // Check blocks.
+    let blocks = items[1].blocks();
+    let data = items[1].offset() + 1 + 8 + 2 + 74 + 1;
+
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[0].offset, data);
+    assert_eq!(blocks[0].pos, 0);
+    assert_eq!(blocks[0].len, 0xFFFF);
+    assert_eq!(blocks[1].offset, data + 2 + 0xFFFF);
+    assert_eq!(blocks[1].pos, 0xFFFF);
+    assert_eq!(blocks[1].len, 1);
+    assert_eq!(items[2].offset(), blocks[1].offset + 2 + 1 + 2 + 4);
+    assert!(items[2].blocks().is_empty());
+}
+
+#[test]
+fn other_items() {
+    let info = SystemInfo {
+        sdk_version: 0x11008001,
+        kernel: b"FreeBSD 9.0-RELEASE".to_vec(),
+        model: b"CUH-2215B".to_vec(),
+        time: Timespec::default(),
+        dumper: b"firmware-dumper 0.1.0".to_vec(),
+    };
+    let kernel = Kernel {
+        base: 0xffffffff8a200000,
+        segments: vec![Segment {
+            addr: 0xffffffff8a200000,
+            flags: PF_R | PF_X,
+            data: fill(0x1234, 0),
+        }],
+    };
+    let table = SyscallTable {
+        entries: vec![Syscall {
+            number: 0,
+            narg: 0,
+            handler: 0xffffffff8a2d1230,
+            name: b"syscall".to_vec(),
+        }],
+    };
+    let modules = vec![Module {
+        name: b"kernel".to_vec(),
+        addr: 0xffffffff8a200000,
+        size: 0x2000000,
+        image: None,
+    }];
+
+    // Write.
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+
+    w.system_info(&info).unwrap();
+
+    let mut kw = w.kernel(kernel.base).unwrap();
+
+    for s in &kernel.segments {
+        kw.segment(s.addr, s.flags, &s.data).unwrap();
+    }
+
+    kw.finish().unwrap();
+    w.syscalls(&table).unwrap();
+
+    let mut mw = w.modules().unwrap();
+
+    for m in &modules {
+        mw.module(&m.name, m.addr, m.size, m.image.as_deref())
+            .unwrap();
+    }
+
+    mw.finish().unwrap();
+    w.ps4_part(b"ufs", b"/dev/da0x4.crypt")
+        .unwrap()
+        .finish()
+        .unwrap();
+
+    let data = w.finish().unwrap();
+
+    // Both table of contents and scanning must give the same result.
+    for mut index in [
+        DumpIndex::new(Cursor::new(&data)).unwrap(),
+        DumpIndex::scan(Cursor::new(&data)).unwrap(),
+    ] {
+        let types: Vec<DumpItem> = index.others().iter().map(|i| i.0).collect();
+
+        assert_eq!(
+            types,
+            [
+                DumpItem::SystemInfo,
+                DumpItem::Kernel,
+                DumpItem::Syscalls,
+                DumpItem::Modules
+            ]
+        );
+
+        assert_eq!(index.others()[0].1, 4);
+        assert_eq!(index.system_info(), Some(&info));
+        assert_eq!(index.kernel().unwrap(), Some(kernel.clone()));
+        assert_eq!(index.syscalls().unwrap(), Some(table.clone()));
+        assert_eq!(index.modules().unwrap(), Some(modules.clone()));
+        assert_eq!(index.parts().len(), 1);
+    }
+
+    // Items that do not exist.
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+
+    w.syscalls(&table).unwrap();
+
+    let mut index = DumpIndex::new(Cursor::new(w.finish().unwrap())).unwrap();
+
+    assert_eq!(index.kernel().unwrap(), None);
+    assert_eq!(index.modules().unwrap(), None);
+    assert_eq!(index.syscalls().unwrap(), Some(table));
+}
+
+#[test]
+fn hard_link() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::Directory(b"".to_vec(), Metadata::default()),
+            Item::File(b"/a".to_vec(), Metadata::default(), fill(0x12345, 3)),
+            Item::HardLink(b"/b".to_vec(), Metadata::default(), b"/a".to_vec()),
+        ],
+    }];
+    let data = write(&dump, usize::MAX, FileBlock::Compressed);
+
+    for mut index in [
+        DumpIndex::new(Cursor::new(&data)).unwrap(),
+        DumpIndex::scan(Cursor::new(&data)).unwrap(),
+    ] {
+        let mut buf = Vec::new();
+
+        index
+            .open_file(b"/dev/da0x4.crypt", b"/b")
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+
+        assert_eq!(buf, fill(0x12345, 3));
+    }
+}
+
+#[test]
+fn reopen() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::Directory(b"".to_vec(), Metadata::default()),
+            Item::File(b"/a".to_vec(), Metadata::default(), fill(0x23456, 5)),
+        ],
+    }];
+    let data = write(&dump, usize::MAX, FileBlock::Compressed);
+    let mut index = DumpIndex::new(Cursor::new(data)).unwrap();
+    let mut cache = BlockCache::default();
+    let mut buf = Vec::new();
+
+    // Read each chunk from a new handle like FUSE does.
+    while buf.len() < 0x23456 {
+        let mut f = index
+            .open_file(b"/dev/da0x4.crypt", b"/a")
+            .unwrap()
+            .with_cache(cache);
+
+        f.seek(SeekFrom::Start(buf.len() as u64)).unwrap();
+        f.by_ref().take(0x3000).read_to_end(&mut buf).unwrap();
+
+        cache = f.into_cache();
+    }
+
+    assert_eq!(buf, fill(0x23456, 5));
+}
+
+#[test]
+fn invalid_block() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::Directory(b"".to_vec(), Metadata::default()),
+            Item::File(b"/a".to_vec(), Metadata::default(), fill(0xFFFF * 2, 1)),
+        ],
+    }];
+    let mut data = write(&dump, usize::MAX, FileBlock::Compressed);
+    let toc = DumpReader::new(Cursor::new(&data)).unwrap().toc().unwrap() as usize;
+    let lens: Vec<usize> = (toc..(data.len() - 4))
+        .filter(|&i| data[i..(i + 4)] == 0xFFFFu32.to_le_bytes())
+        .collect();
+
+    assert_eq!(lens.len(), 2);
+
+    // Merge both blocks into the first one so the file length is still the same.
+    data[lens[0]..(lens[0] + 4)].copy_from_slice(&(0xFFFFu32 * 2).to_le_bytes());
+    data[lens[1]..(lens[1] + 4)].copy_from_slice(&0u32.to_le_bytes());
+
+    match DumpIndex::new(Cursor::new(data)) {
+        Err(IndexError::ReadToc(e)) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+        _ => panic!("unexpected result"),
+    }
+}
+
+#[test]
+fn open_error() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![Item::Directory(b"".to_vec(), Metadata::default())],
+    }];
+    let mut index = DumpIndex::new(Cursor::new(write(
+        &dump,
+        usize::MAX,
+        FileBlock::Uncompressed,
+    )))
+    .unwrap();
+
+    assert!(matches!(
+        index.open_file(b"/dev/da0x5.crypt", b""),
+        Err(IndexError::PartitionNotFound)
+    ));
+
+    assert!(matches!(
+        index.open_file(b"/dev/da0x4.crypt", b"/a"),
+        Err(IndexError::ItemNotFound)
+    ));
+
+    assert!(matches!(
+        index.open_file(b"/dev/da0x4.crypt", b""),
+        Err(IndexError::NotFile)
+    ));
+}
@@ -0,0 +1,1005 @@
+use self::common::{block, dump, fill, sparse, text, write, Item, Part};
+use obfw::device::RawDeviceError;
+use obfw::kernel::{Kernel, KernelError, Module, Segment, PF_R, PF_W, PF_X};
+use obfw::ps4::{FileBlock, Metadata, PartData, PartError, PartItem, Timespec};
+use obfw::{
+    DumpIndex, DumpReader, DumpWriter, ItemReader, ReaderError, Syscall, SyscallTable, SystemInfo,
+};
+use proptest::prelude::*;
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+mod common;
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn roundtrip(dump in dump(), block in block(), wchunk in 1usize..0x20000, rchunk in 1usize..0x20000) {
+        let data = write(&dump, wchunk, block);
+        let (items, actual) = read(data, rchunk);
+
+        prop_assert_eq!(items, dump.iter().map(|p| 1 + p.items.len() as u32).sum::<u32>());
+        prop_assert_eq!(actual, dump);
+    }
+}
+
+#[test]
+fn encoding() {
+    let meta = Metadata {
+        mode: 0o40755,
+        uid: 1,
+        gid: 2,
+        flags: 0x20000,
+        size: 512,
+        atime: Timespec { sec: 3, nsec: 4 },
+        mtime: Timespec { sec: 5, nsec: 6 },
+        ctime: Timespec { sec: 7, nsec: 8 },
+        birthtime: Timespec { sec: -9, nsec: 10 },
+    };
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::Directory(b"".to_vec(), meta),
+            Item::File(b"/a".to_vec(), Metadata::default(), b"abc".to_vec()),
+        ],
+    }];
+    let mut encoded = Vec::new();
+
+    encoded.extend_from_slice(&0o40755u16.to_le_bytes());
+    encoded.extend_from_slice(&1u32.to_le_bytes());
+    encoded.extend_from_slice(&2u32.to_le_bytes());
+    encoded.extend_from_slice(&0x20000u64.to_le_bytes());
+    encoded.extend_from_slice(&512u64.to_le_bytes());
+
+    for (sec, nsec) in [(3i64, 4u32), (5, 6), (7, 8), (-9, 10)] {
+        encoded.extend_from_slice(&sec.to_le_bytes());
+        encoded.extend_from_slice(&nsec.to_le_bytes());
+    }
+
+    assert_eq!(encoded.len(), 74);
+
+    let mut expected = Vec::new();
+
+    expected.extend_from_slice(b"\x7FOBF");
+    expected.extend_from_slice(&[1, 2]);
+    expected.extend_from_slice(&3u64.to_le_bytes());
+    expected.extend_from_slice(b"ufs");
+    expected.extend_from_slice(&16u64.to_le_bytes());
+    expected.extend_from_slice(b"/dev/da0x4.crypt");
+    expected.push(1);
+    expected.extend_from_slice(&0u64.to_le_bytes());
+    expected.extend_from_slice(&encoded);
+    expected.push(2);
+    expected.extend_from_slice(&2u64.to_le_bytes());
+    expected.extend_from_slice(b"/a");
+    expected.extend_from_slice(&[0; 74]);
+    expected.push(0);
+    expected.extend_from_slice(&3u16.to_le_bytes());
+    expected.extend_from_slice(b"abc");
+    expected.extend_from_slice(&0u16.to_le_bytes());
+    expected.extend_from_slice(&0x352441C2u32.to_le_bytes());
+    expected.push(0);
+    expected.push(0);
+
+    // Table of contents.
+    let toc = expected.len() as u64;
+
+    expected.push(1);
+    expected.extend_from_slice(&4u64.to_le_bytes());
+    expected.extend_from_slice(&261u64.to_le_bytes());
+    expected.extend_from_slice(&3u64.to_le_bytes());
+    expected.extend_from_slice(b"ufs");
+    expected.extend_from_slice(&16u64.to_le_bytes());
+    expected.extend_from_slice(b"/dev/da0x4.crypt");
+    expected.extend_from_slice(&2u32.to_le_bytes());
+    expected.push(1);
+    expected.extend_from_slice(&0u64.to_le_bytes());
+    expected.extend_from_slice(&41u64.to_le_bytes());
+    expected.extend_from_slice(&encoded);
+    expected.push(0);
+    expected.extend_from_slice(&0u64.to_le_bytes());
+    expected.extend_from_slice(&0u32.to_le_bytes());
+    expected.push(2);
+    expected.extend_from_slice(&2u64.to_le_bytes());
+    expected.extend_from_slice(b"/a");
+    expected.extend_from_slice(&124u64.to_le_bytes());
+    expected.extend_from_slice(&[0; 74]);
+    expected.push(0);
+    expected.extend_from_slice(&3u64.to_le_bytes());
+    expected.extend_from_slice(&1u32.to_le_bytes());
+    expected.extend_from_slice(&210u64.to_le_bytes());
+    expected.extend_from_slice(&3u32.to_le_bytes());
+    expected.push(0);
+    expected.extend_from_slice(&toc.to_le_bytes());
+    expected.extend_from_slice(b"\x7FOBI");
+    expected.extend_from_slice(&3u32.to_le_bytes());
+
+    assert_eq!(write(&dump, usize::MAX, FileBlock::Uncompressed), expected);
+}
+
+#[test]
+fn block_boundaries() {
+    for len in [0, 1, 0xFFFE, 0xFFFF, 0x10000, 0x1FFFE, 0x1FFFF, 0x20000] {
+        let dump = [Part {
+            fs: b"exfatfs".to_vec(),
+            dev: b"/dev/da0x0.crypt".to_vec(),
+            items: vec![Item::File(
+                b"/f".to_vec(),
+                Metadata::default(),
+                fill(len, len as u64),
+            )],
+        }];
+        let data = write(&dump, usize::MAX, FileBlock::Uncompressed);
+        let toc = DumpReader::new(Cursor::new(&data)).unwrap().toc().unwrap();
+
+        // Each block has 2 bytes header plus the end block and the digest.
+        let blocks = len.div_ceil(0xFFFF);
+        let header = 4 + 2 + 8 + 7 + 8 + 16 + 1 + 8 + 2 + 74 + 1;
+
+        assert_eq!(toc, (header + blocks * 2 + len + 2 + 4 + 1 + 1) as u64);
+        assert_eq!(read(data, 0x1000).1, dump);
+    }
+}
+
+#[test]
+fn compressed_blocks() {
+    let text = text(0x20000, 1);
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::File(b"/a".to_vec(), Metadata::default(), text.clone()),
+            Item::File(b"/b".to_vec(), Metadata::default(), fill(0x10000, 1)),
+            Item::File(b"/c".to_vec(), Metadata::default(), vec![0; 0x100]),
+        ],
+    }];
+    let data = write(&dump, 0x3000, FileBlock::Compressed);
+    let index = DumpIndex::new(Cursor::new(&data)).unwrap();
+
+    assert_eq!(read(data.clone(), 0x1000).1, dump);
+
+    // Decompress each block with a reference implementation.
+    for (i, item) in index.parts()[0].items().iter().enumerate() {
+        let mut content = Vec::new();
+
+        assert_eq!(item.block(), Some(FileBlock::Compressed));
+
+        for b in item.blocks() {
+            let off = b.offset as usize;
+            let len = u16::from_le_bytes(data[off..(off + 2)].try_into().unwrap());
+            let stored = u16::from_le_bytes(data[(off + 2)..(off + 4)].try_into().unwrap());
+            let block = &data[(off + 4)..(off + 4 + usize::from(stored))];
+
+            assert_eq!(u32::from(len), b.len);
+
+            if i == 1 {
+                // Random data is not compressible so it should be stored as-is.
+                assert_eq!(stored, len);
+                content.extend_from_slice(block);
+            } else {
+                assert!(stored < len);
+                content.extend(lz4_flex::block::decompress(block, len.into()).unwrap());
+            }
+        }
+
+        let Item::File(_, _, expected) = &dump[0].items[i] else {
+            unreachable!();
+        };
+
+        assert_eq!(&content, expected);
+    }
+}
+
+#[test]
+fn sparse_blocks() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::File(b"/a".to_vec(), Metadata::default(), vec![0; 0x30000]),
+            Item::File(b"/b".to_vec(), Metadata::default(), sparse(0x30000, 1)),
+        ],
+    }];
+    let data = write(&dump, 0x10000, FileBlock::Sparse);
+    let index = DumpIndex::new(Cursor::new(&data)).unwrap();
+    let items = index.parts()[0].items();
+
+    assert_eq!(read(data.clone(), 0x1000).1, dump);
+
+    // Zero blocks should have only the header.
+    for b in items[0].blocks() {
+        let off = b.offset as usize;
+
+        assert_eq!(items[0].block(), Some(FileBlock::Sparse));
+        assert_eq!(data[(off + 2)..(off + 4)], [0, 0]);
+    }
+
+    // The same data with compressed blocks must be larger.
+    let compressed = write(&dump, 0x10000, FileBlock::Compressed);
+
+    assert!(data.len() < compressed.len());
+}
+
+#[test]
+fn invalid_compressed_block() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![Item::File(
+            b"/a".to_vec(),
+            Metadata::default(),
+            vec![0; 0x100],
+        )],
+    }];
+    let mut data = write(&dump, usize::MAX, FileBlock::Compressed);
+    let index = DumpIndex::new(Cursor::new(&data)).unwrap();
+    let off = index.parts()[0].items()[0].blocks()[0].offset as usize;
+
+    // The block is a single literal followed by a match. Make the match offset points before the
+    // beginning of the block.
+    data[off + 6] = 0xFF;
+    data[off + 7] = 0xFF;
+
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+    let mut p = match r.next_item().unwrap().unwrap() {
+        ItemReader::Ps4Part(v) => v,
+        _ => panic!("unexpected item"),
+    };
+    let mut f = match p.next_item().unwrap() {
+        Some(PartData::File(_, _, f)) => f,
+        _ => panic!("unexpected item"),
+    };
+
+    let e = f.read_to_end(&mut Vec::new()).unwrap_err();
+
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn old_versions() {
+    for ver in [0, 1] {
+        let mut data = Vec::new();
+
+        data.extend_from_slice(b"\x7FOBF");
+        data.extend_from_slice(&[1, ver]);
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(b"ufs");
+        data.extend_from_slice(&16u64.to_le_bytes());
+        data.extend_from_slice(b"/dev/da0x4.crypt");
+        data.push(1);
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(2);
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(b"/a");
+        data.push(0);
+        data.extend_from_slice(&3u16.to_le_bytes());
+        data.extend_from_slice(b"abc");
+        data.extend_from_slice(&0u16.to_le_bytes());
+
+        // Version 1 has a digest after each file.
+        if ver == 1 {
+            data.extend_from_slice(&0x352441C2u32.to_le_bytes());
+        }
+
+        data.push(0);
+        data.push(0);
+        data.extend_from_slice(&3u32.to_le_bytes());
+
+        // Old versions don't have metadata.
+        let mut index = DumpIndex::new(Cursor::new(data.clone())).unwrap();
+        let mut buf = Vec::new();
+
+        assert!(index.parts()[0].items()[1].metadata().is_none());
+
+        index
+            .open_file(b"/dev/da0x4.crypt", b"/a")
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+
+        assert_eq!(buf, b"abc");
+
+        let dump = vec![Part {
+            fs: b"ufs".to_vec(),
+            dev: b"/dev/da0x4.crypt".to_vec(),
+            items: vec![
+                Item::Directory(b"".to_vec(), Metadata::default()),
+                Item::File(b"/a".to_vec(), Metadata::default(), b"abc".to_vec()),
+            ],
+        }];
+
+        assert_eq!(read(data, 0x1000), (3, dump));
+    }
+}
+
+#[test]
+fn corrupted_file() {
+    for block in [FileBlock::Uncompressed, FileBlock::Compressed] {
+        let dump = [Part {
+            fs: b"ufs".to_vec(),
+            dev: b"/dev/da0x4.crypt".to_vec(),
+            items: vec![Item::File(
+                b"/a".to_vec(),
+                Metadata::default(),
+                fill(0x20000, 0),
+            )],
+        }];
+        let mut data = write(&dump, usize::MAX, block);
+        let index = DumpIndex::new(Cursor::new(&data)).unwrap();
+        let off = index.parts()[0].items()[0].blocks()[1].offset as usize;
+
+        // Flip a bit in the middle of the second block.
+        data[off + 0x100] ^= 1;
+
+        let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+        let mut p = match r.next_item().unwrap().unwrap() {
+            ItemReader::Ps4Part(v) => v,
+            _ => panic!("unexpected item"),
+        };
+        let mut f = match p.next_item().unwrap() {
+            Some(PartData::File(_, _, f)) => f,
+            _ => panic!("unexpected item"),
+        };
+
+        let e = f.read_to_end(&mut Vec::new()).unwrap_err();
+
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "file digest mismatch");
+    }
+}
+
+#[test]
+fn without_toc() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x4.crypt".to_vec(),
+        items: vec![
+            Item::Directory(b"".to_vec(), Metadata::default()),
+            Item::File(b"/a".to_vec(), Metadata::default(), fill(0x10000, 0)),
+        ],
+    }];
+    let mut data = write(&dump, usize::MAX, FileBlock::Uncompressed);
+
+    // Strip table of contents to get the same dump as the old version.
+    let toc = DumpReader::new(Cursor::new(&data)).unwrap().toc().unwrap();
+    let items = data.split_off(data.len() - 4);
+
+    data.truncate(toc as usize);
+    data.extend_from_slice(&items);
+
+    assert!(DumpReader::new(Cursor::new(&data)).unwrap().toc().is_none());
+    assert_eq!(read(data, 0x1000), (3, dump.into()));
+}
+
+#[test]
+fn system_info() {
+    let info = SystemInfo {
+        sdk_version: 0x11008001,
+        kernel: b"FreeBSD 9.0-RELEASE #0: Tue Mar 12 17:02:44 PDT 2024\n".to_vec(),
+        model: b"CUH-2215B".to_vec(),
+        time: Timespec {
+            sec: 1729123456,
+            nsec: 789,
+        },
+        dumper: b"firmware-dumper 0.1.0".to_vec(),
+    };
+
+    assert_eq!(info.firmware(), (11, 0));
+
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+
+    w.system_info(&info).unwrap();
+    w.ps4_part(b"ufs", b"/dev/da0x4.crypt")
+        .unwrap()
+        .finish()
+        .unwrap();
+
+    let data = w.finish().unwrap();
+
+    // Read back.
+    let mut r = DumpReader::new(Cursor::new(&data)).unwrap();
+
+    assert_eq!(r.items(), 2);
+
+    match r.next_item().unwrap() {
+        Some(ItemReader::SystemInfo(v)) => assert_eq!(v, info),
+        _ => panic!("unexpected item"),
+    }
+
+    assert!(matches!(r.next_item(), Ok(Some(ItemReader::Ps4Part(_)))));
+
+    let index = DumpIndex::new(Cursor::new(&data)).unwrap();
+    let scan = DumpIndex::scan(Cursor::new(&data)).unwrap();
+
+    assert_eq!(index.system_info(), Some(&info));
+    assert_eq!(scan.system_info(), Some(&info));
+    assert_eq!(index.parts().len(), 1);
+
+    // Fields from the newer version must be skipped.
+    let len = u64::from_le_bytes(data[6..14].try_into().unwrap());
+    let mut data = data[..(14 + len as usize)].to_vec();
+
+    data[6..14].copy_from_slice(&(len + 3).to_le_bytes());
+    data.extend_from_slice(&[1, 2, 3, 0]);
+    data.extend_from_slice(&1u32.to_le_bytes());
+
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+
+    match r.next_item().unwrap() {
+        Some(ItemReader::SystemInfo(v)) => assert_eq!(v, info),
+        _ => panic!("unexpected item"),
+    }
+
+    assert!(r.next_item().unwrap().is_none());
+}
+
+#[test]
+fn kernel() {
+    let kernel = Kernel {
+        base: 0xffffffff8a200000,
+        segments: vec![
+            Segment {
+                addr: 0xffffffff8a200000,
+                flags: PF_R | PF_X,
+                data: fill(0x12345, 1),
+            },
+            Segment {
+                addr: 0xffffffff8b400000,
+                flags: PF_R | PF_W,
+                data: Vec::new(),
+            },
+        ],
+    };
+
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+    let mut kw = w.kernel(kernel.base).unwrap();
+
+    for s in &kernel.segments {
+        kw.segment(s.addr, s.flags, &s.data).unwrap();
+    }
+
+    kw.finish().unwrap();
+
+    let mut data = w.finish().unwrap();
+    let mut r = DumpReader::new(Cursor::new(&data)).unwrap();
+
+    assert_eq!(r.items(), 3);
+
+    match r.next_item().unwrap() {
+        Some(ItemReader::Kernel(v)) => assert_eq!(v, kernel),
+        _ => panic!("unexpected item"),
+    }
+
+    assert!(r.next_item().unwrap().is_none());
+    assert!(DumpIndex::new(Cursor::new(&data))
+        .unwrap()
+        .parts()
+        .is_empty());
+
+    // Corrupt the first segment.
+    data[4 + 2 + 8 + 1 + 20 + 0x100] ^= 1;
+
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+    let e = match r.next_item() {
+        Err(ReaderError::ItemReader(_, e)) => e,
+        _ => panic!("unexpected result"),
+    };
+
+    assert!(matches!(
+        e.downcast_ref::<KernelError>(),
+        Some(KernelError::DigestMismatch(0xffffffff8a200000))
+    ));
+}
+
+#[test]
+fn modules() {
+    let modules = vec![
+        Module {
+            name: b"kernel".to_vec(),
+            addr: 0xffffffff8a200000,
+            size: 0x2000000,
+            image: None,
+        },
+        Module {
+            name: b"/system/priv/lib/libSceSomething.sprx".to_vec(),
+            addr: 0xffffffff8e000000,
+            size: 0x4321,
+            image: Some(fill(0x4321, 2)),
+        },
+    ];
+
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+    let mut mw = w.modules().unwrap();
+
+    for m in &modules {
+        mw.module(&m.name, m.addr, m.size, m.image.as_deref())
+            .unwrap();
+    }
+
+    mw.finish().unwrap();
+
+    let mut data = w.finish().unwrap();
+    let mut r = DumpReader::new(Cursor::new(&data)).unwrap();
+
+    assert_eq!(r.items(), 3);
+
+    match r.next_item().unwrap() {
+        Some(ItemReader::Modules(v)) => assert_eq!(v, modules),
+        _ => panic!("unexpected item"),
+    }
+
+    assert!(r.next_item().unwrap().is_none());
+
+    // Corrupt the image.
+    let off = 4 + 2 + (1 + 8 + 6 + 16) + (1 + 8 + modules[1].name.len() + 16);
+
+    data[off + 0x1000] ^= 1;
+
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+    let e = match r.next_item() {
+        Err(ReaderError::ItemReader(_, e)) => e,
+        _ => panic!("unexpected result"),
+    };
+
+    assert!(matches!(
+        e.downcast_ref::<KernelError>(),
+        Some(KernelError::DigestMismatch(0xffffffff8e000000))
+    ));
+}
+
+#[test]
+fn syscalls() {
+    let table = SyscallTable {
+        entries: vec![
+            Syscall {
+                number: 0,
+                narg: 0,
+                handler: 0xffffffff8a2d1230,
+                name: b"syscall".to_vec(),
+            },
+            Syscall {
+                number: 1,
+                narg: 1,
+                handler: 0xffffffff8a2d4560,
+                name: b"\"ex\\it\x01\xff".to_vec(),
+            },
+            Syscall {
+                number: 2,
+                narg: 6,
+                handler: 0xffffffff8a2d7890,
+                name: Vec::new(),
+            },
+        ],
+    };
+
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+
+    w.syscalls(&table).unwrap();
+
+    let data = w.finish().unwrap();
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+
+    assert_eq!(r.items(), 1);
+
+    let actual = match r.next_item().unwrap() {
+        Some(ItemReader::Syscalls(v)) => v,
+        _ => panic!("unexpected item"),
+    };
+
+    assert_eq!(actual, table);
+    assert!(r.next_item().unwrap().is_none());
+
+    // Export.
+    let mut json = Vec::new();
+
+    table.write_json(&mut json).unwrap();
+
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        concat!(
+            "[\n",
+            "  {\"number\": 0, \"name\": \"syscall\", \"narg\": 0, \"handler\": \"0xffffffff8a2d1230\"},\n",
+            "  {\"number\": 1, \"name\": \"\\\"ex\\\\it\\u0001\\u00ff\", \"narg\": 1, \"handler\": \"0xffffffff8a2d4560\"},\n",
+            "  {\"number\": 2, \"name\": null, \"narg\": 6, \"handler\": \"0xffffffff8a2d7890\"}\n",
+            "]\n"
+        )
+    );
+}
+
+#[test]
+fn decrypted_self() {
+    // ELF header with two program headers.
+    let mut hdrs = vec![0; 64 + 56 * 2];
+
+    hdrs[..4].copy_from_slice(b"\x7FELF");
+    hdrs[32..40].copy_from_slice(&64u64.to_le_bytes());
+    hdrs[54..56].copy_from_slice(&56u16.to_le_bytes());
+    hdrs[56..58].copy_from_slice(&2u16.to_le_bytes());
+
+    for (i, (off, len)) in [(0x4000u64, 0x12345u64), (0x1c000, 0x100)]
+        .into_iter()
+        .enumerate()
+    {
+        let p = 64 + 56 * i;
+
+        hdrs[(p + 8)..(p + 16)].copy_from_slice(&off.to_le_bytes());
+        hdrs[(p + 32)..(p + 40)].copy_from_slice(&len.to_le_bytes());
+    }
+
+    // Build the expected ELF.
+    let container = fill(0x8000, 1);
+    let segments = [fill(0x12345, 2), text(0x100, 3)];
+    let mut elf = hdrs.clone();
+
+    elf.resize(0x4000, 0);
+    elf.extend_from_slice(&segments[0]);
+    elf.resize(0x1c000, 0);
+    elf.extend_from_slice(&segments[1]);
+
+    for block in [FileBlock::Uncompressed, FileBlock::Sparse] {
+        let data = write_self(&hdrs, &container, &segments, &[0, 1], block);
+
+        // Read the SELF then the ELF. The next file must still be readable.
+        for (elf, expected) in [(false, &container), (true, &elf)] {
+            let mut r = DumpReader::new(Cursor::new(&data)).unwrap();
+            let mut part = match r.next_item().unwrap() {
+                Some(ItemReader::Ps4Part(v)) => v,
+                _ => panic!("unexpected item"),
+            };
+
+            part.set_elf(elf);
+
+            for (path, expected) in [(b"/a", expected.as_slice()), (b"/b", b"b".as_slice())] {
+                let mut content = Vec::new();
+
+                match part.next_item().unwrap() {
+                    Some(PartData::File(p, _, mut r)) => {
+                        assert_eq!(p, path);
+                        r.read_to_end(&mut content).unwrap();
+                    }
+                    _ => panic!("unexpected item"),
+                }
+
+                assert_eq!(content, expected);
+            }
+
+            assert!(part.next_item().unwrap().is_none());
+        }
+
+        // Only the SELF is indexed.
+        for mut index in [
+            DumpIndex::new(Cursor::new(&data)).unwrap(),
+            DumpIndex::scan(Cursor::new(&data)).unwrap(),
+        ] {
+            let items = index.parts()[0].items();
+            let mut content = Vec::new();
+
+            assert_eq!(items.len(), 2);
+            assert_eq!(items[0].block(), Some(block));
+            assert_eq!(items[0].len(), 0x8000);
+
+            index
+                .open_file(b"/dev/da0x4.crypt", b"/a")
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+
+            assert_eq!(content, container);
+        }
+    }
+
+    // Segments must be in the same order as their offset.
+    let data = write_self(
+        &hdrs,
+        &container,
+        &segments,
+        &[1, 0],
+        FileBlock::Uncompressed,
+    );
+    let mut r = DumpReader::new(Cursor::new(&data)).unwrap();
+    let mut part = match r.next_item().unwrap() {
+        Some(ItemReader::Ps4Part(v)) => v,
+        _ => panic!("unexpected item"),
+    };
+
+    part.set_elf(true);
+
+    let e = match part.next_item().unwrap() {
+        Some(PartData::File(_, _, mut r)) => r.read_to_end(&mut Vec::new()).unwrap_err(),
+        _ => panic!("unexpected item"),
+    };
+
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+
+    // The first segment at offset zero overlaps the headers.
+    let segments = [fill(0x5000, 4), text(0x100, 5)];
+
+    for (i, off) in [0u64, 0x8000].into_iter().enumerate() {
+        let p = 64 + 56 * i;
+        let len = segments[i].len() as u64;
+
+        hdrs[(p + 8)..(p + 16)].copy_from_slice(&off.to_le_bytes());
+        hdrs[(p + 32)..(p + 40)].copy_from_slice(&len.to_le_bytes());
+    }
+
+    let mut elf = hdrs.clone();
+
+    elf.extend_from_slice(&segments[0][hdrs.len()..]);
+    elf.resize(0x8000, 0);
+    elf.extend_from_slice(&segments[1]);
+
+    for block in [FileBlock::Uncompressed, FileBlock::Sparse] {
+        let data = write_self(&hdrs, &container, &segments, &[0, 1], block);
+        let mut r = DumpReader::new(Cursor::new(&data)).unwrap();
+        let mut part = match r.next_item().unwrap() {
+            Some(ItemReader::Ps4Part(v)) => v,
+            _ => panic!("unexpected item"),
+        };
+        let mut content = Vec::new();
+
+        part.set_elf(true);
+
+        match part.next_item().unwrap() {
+            Some(PartData::File(_, _, mut r)) => r.read_to_end(&mut content).unwrap(),
+            _ => panic!("unexpected item"),
+        };
+
+        assert_eq!(content, elf);
+    }
+}
+
+#[test]
+fn raw_device() {
+    let content = fill(0x24000, 3);
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+    let mut dw = w.raw_device(b"/dev/sflash0", 0x28000).unwrap();
+
+    for c in content.chunks(0x10000) {
+        dw.write(c).unwrap();
+    }
+
+    dw.finish().unwrap();
+    w.syscalls(&SyscallTable::default()).unwrap();
+
+    let mut data = w.finish().unwrap();
+
+    // Read only some part of the content then the next item must still be readable.
+    let mut r = DumpReader::new(Cursor::new(&data)).unwrap();
+
+    assert_eq!(r.items(), 2);
+
+    match r.next_item().unwrap() {
+        Some(ItemReader::RawDevice(mut v)) => {
+            let mut buf = vec![0; 0x100];
+
+            assert_eq!(v.name(), b"/dev/sflash0");
+            assert_eq!(v.size(), 0x28000);
+            assert_eq!(v.len(), 0x24000);
+            assert_eq!(v.index().blocks().len(), 5);
+
+            let mut f = v.open();
+
+            f.seek(SeekFrom::Start(0xFFF0)).unwrap();
+            f.read_exact(&mut buf).unwrap();
+
+            assert_eq!(buf, content[0xFFF0..0x100F0]);
+        }
+        _ => panic!("unexpected item"),
+    }
+
+    match r.next_item().unwrap() {
+        Some(ItemReader::Syscalls(v)) => assert_eq!(v, SyscallTable::default()),
+        _ => panic!("unexpected item"),
+    }
+
+    assert!(r.next_item().unwrap().is_none());
+
+    // Check index.
+    for mut index in [
+        DumpIndex::new(Cursor::new(&data)).unwrap(),
+        DumpIndex::scan(Cursor::new(&data)).unwrap(),
+    ] {
+        let mut actual = Vec::new();
+
+        assert_eq!(index.raw_devices().len(), 1);
+        assert_eq!(index.raw_device(b"/dev/sflash0").unwrap().offset(), 4);
+
+        index.check_raw_device(b"/dev/sflash0").unwrap();
+        index
+            .open_raw_device(b"/dev/sflash0")
+            .unwrap()
+            .read_to_end(&mut actual)
+            .unwrap();
+
+        assert_eq!(actual, content);
+    }
+
+    // Corrupt the content.
+    data[4 + 2 + 8 + 12 + 8 + 2 + 0x1000] ^= 1;
+
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+
+    match r.next_item().unwrap() {
+        Some(ItemReader::RawDevice(mut v)) => assert!(matches!(
+            v.check_digest(),
+            Err(RawDeviceError::DigestMismatch)
+        )),
+        _ => panic!("unexpected item"),
+    }
+}
+
+#[test]
+fn not_dump() {
+    let r = DumpReader::new(Cursor::new(b"\x7FELF\0\0\0\0".to_vec()));
+
+    assert!(matches!(r, Err(ReaderError::NotFirmwareDump)));
+
+    let r = DumpReader::new(Cursor::new(b"\x7FO".to_vec()));
+
+    assert!(matches!(r, Err(ReaderError::NotFirmwareDump)));
+}
+
+#[test]
+fn unknown_item() {
+    let mut data = write(&[], usize::MAX, FileBlock::Uncompressed);
+
+    data[4] = 0xFF;
+
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+
+    assert!(matches!(r.next_item(), Err(ReaderError::UnknownItem(0xFF))));
+}
+
+#[test]
+fn unknown_version() {
+    let dump = [Part {
+        fs: Vec::new(),
+        dev: Vec::new(),
+        items: Vec::new(),
+    }];
+    let mut data = write(&dump, usize::MAX, FileBlock::Uncompressed);
+
+    data[5] = 0xFF;
+
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+    let e = match r.next_item() {
+        Err(ReaderError::ItemReader(_, e)) => e,
+        _ => panic!("unexpected result"),
+    };
+
+    assert!(matches!(
+        e.downcast_ref::<PartError>(),
+        Some(PartError::UnknownVersion(0xFF))
+    ));
+}
+
+#[test]
+fn truncated_file() {
+    let dump = [Part {
+        fs: b"ufs".to_vec(),
+        dev: b"/dev/da0x5.crypt".to_vec(),
+        items: vec![Item::File(
+            b"/f".to_vec(),
+            Metadata::default(),
+            fill(0x20000, 0),
+        )],
+    }];
+    let mut data = write(&dump, usize::MAX, FileBlock::Uncompressed);
+
+    // Cut in the middle of the second block but keep the item count readable.
+    data.truncate(0x10100);
+    data.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+    let mut p = match r.next_item().unwrap().unwrap() {
+        ItemReader::Ps4Part(v) => v,
+        _ => panic!("unexpected item"),
+    };
+    let mut f = match p.next_item().unwrap() {
+        Some(PartData::File(_, _, f)) => f,
+        _ => panic!("unexpected item"),
+    };
+
+    let e = f.read_to_end(&mut Vec::new()).unwrap_err();
+
+    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+}
+
+fn read(data: Vec<u8>, chunk: usize) -> (u32, Vec<Part>) {
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+    let items = r.items();
+    let mut parts = Vec::new();
+
+    while let Some(i) = r.next_item().unwrap() {
+        let mut p = match i {
+            ItemReader::Ps4Part(v) => v,
+            _ => panic!("unexpected item"),
+        };
+        let mut part = Part {
+            fs: p.fs().to_vec(),
+            dev: p.dev().to_vec(),
+            items: Vec::new(),
+        };
+
+        while let Some(i) = p.next_item().unwrap() {
+            let i = match i {
+                PartData::Directory(path, meta) => Item::Directory(path, meta.unwrap_or_default()),
+                PartData::Symlink(path, meta, target) => {
+                    Item::Symlink(path, meta.unwrap_or_default(), target)
+                }
+                PartData::HardLink(path, meta, target) => {
+                    Item::HardLink(path, meta.unwrap_or_default(), target)
+                }
+                PartData::CharDevice(path, meta, rdev) => {
+                    Item::Special(path, meta.unwrap_or_default(), PartItem::CharDevice, rdev)
+                }
+                PartData::BlockDevice(path, meta, rdev) => {
+                    Item::Special(path, meta.unwrap_or_default(), PartItem::BlockDevice, rdev)
+                }
+                PartData::Fifo(path, meta) => {
+                    Item::Special(path, meta.unwrap_or_default(), PartItem::Fifo, 0)
+                }
+                PartData::Socket(path, meta) => {
+                    Item::Special(path, meta.unwrap_or_default(), PartItem::Socket, 0)
+                }
+                PartData::File(path, meta, mut f) => {
+                    let mut data = Vec::new();
+                    let mut buf = vec![0; chunk];
+
+                    loop {
+                        let n = f.read(&mut buf).unwrap();
+
+                        if n == 0 {
+                            break;
+                        }
+
+                        data.extend_from_slice(&buf[..n]);
+                    }
+
+                    Item::File(path, meta.unwrap_or_default(), data)
+                }
+            };
+
+            part.items.push(i);
+        }
+
+        parts.push(part);
+    }
+
+    (items, parts)
+}
+
+/// Write a partition with a SELF at `/a` that has `segments` in the order of `order` followed by
+/// a file at `/b`.
+fn write_self(
+    hdrs: &[u8],
+    container: &[u8],
+    segments: &[Vec<u8>],
+    order: &[u16],
+    block: FileBlock,
+) -> Vec<u8> {
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+    let mut p = w.ps4_part(b"ufs", b"/dev/da0x4.crypt").unwrap();
+    let mut f = p.self_file(b"/a", &Metadata::default(), block).unwrap();
+
+    f.write(container).unwrap();
+
+    let mut s = f.decrypted(hdrs).unwrap();
+
+    for &i in order {
+        let mut w = s.segment(i).unwrap();
+
+        w.write(&segments[usize::from(i)]).unwrap();
+        w.finish().unwrap();
+    }
+
+    s.finish().unwrap();
+
+    let mut f = p.file(b"/b", &Metadata::default(), block).unwrap();
+
+    f.write(b"b").unwrap();
+    f.finish().unwrap();
+    p.finish().unwrap();
+    w.finish().unwrap()
+}
@@ -0,0 +1,285 @@
+use obfw::ps4::{
+    ProgramHeader, SelfError, SelfFile, SelfSegment, ET_SCE_DYNAMIC, ET_SCE_EXEC, SELF_MAGIC,
+};
+use proptest::prelude::*;
+use std::io::{Cursor, Seek, SeekFrom};
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn headers(segments in prop::collection::vec(segment(), 0..8), programs in prop::collection::vec(program(), 0..8), ty in prop_oneof![Just(ET_SCE_EXEC), Just(ET_SCE_DYNAMIC)], paid in any::<u64>(), prefix in 0usize..64) {
+        let mut data = vec![0xCC; prefix];
+
+        data.extend(fixture(&segments, &programs, ty, paid));
+
+        let mut file = Cursor::new(data);
+
+        file.seek(SeekFrom::Start(prefix as u64)).unwrap();
+
+        let parsed = SelfFile::read(&mut file).unwrap();
+
+        prop_assert_eq!(parsed.header.segments as usize, segments.len());
+        prop_assert_eq!(&parsed.segments, &segments);
+        prop_assert_eq!(parsed.elf.ty, ty);
+        prop_assert_eq!(parsed.elf.phnum as usize, programs.len());
+        prop_assert_eq!(&parsed.programs, &programs);
+        prop_assert_eq!(parsed.info.paid, paid);
+        prop_assert_eq!(parsed.info.digest, [0xAB; 32]);
+        prop_assert_eq!(parsed.is_library(), ty == ET_SCE_DYNAMIC);
+        prop_assert_eq!(parsed.elf_offset(), 32 + segments.len() * 32);
+        prop_assert_eq!(SelfFile::parse(&file.get_ref()[prefix..]).unwrap(), parsed);
+    }
+}
+
+#[test]
+fn library() {
+    let segments = [
+        SelfSegment {
+            flags: 0x800 | 0x2 | 0x4 | 0x8 | (2 << 12) | (1 << 20),
+            offset: 0x4000,
+            stored_size: 0x1000,
+            size: 0x3000,
+        },
+        SelfSegment {
+            flags: 0x1 | 0x2 | 0x4 | (1 << 20),
+            offset: 0x3000,
+            stored_size: 0x20,
+            size: 0x20,
+        },
+    ];
+    let programs = [
+        ProgramHeader {
+            ty: 0x61000000,
+            flags: 4,
+            offset: 0,
+            vaddr: 0,
+            paddr: 0,
+            filesz: 0,
+            memsz: 0,
+            align: 0x10,
+        },
+        ProgramHeader {
+            ty: 1,
+            flags: 5,
+            offset: 0x4000,
+            vaddr: 0,
+            paddr: 0,
+            filesz: 0x3000,
+            memsz: 0x3000,
+            align: 0x4000,
+        },
+    ];
+    let data = fixture(&segments, &programs, ET_SCE_DYNAMIC, 0x3800000000000011);
+    let parsed = SelfFile::read(&mut Cursor::new(data)).unwrap();
+
+    assert_eq!(parsed.header.version, 0);
+    assert_eq!(parsed.header.mode, 1);
+    assert_eq!(parsed.header.endian, 1);
+    assert_eq!(parsed.header.attrs, 0x12);
+    assert_eq!(parsed.header.key_type, 0x101);
+    assert_eq!(parsed.header.flags, 0x22);
+    assert_eq!(parsed.header.file_size, 0x8000);
+    assert!(parsed.is_library());
+
+    let seg = &parsed.segments[0];
+
+    assert!(seg.is_blocked());
+    assert!(seg.is_encrypted());
+    assert!(seg.is_signed());
+    assert!(seg.is_compressed());
+    assert!(!seg.is_ordered());
+    assert_eq!(seg.block_size(), 0x4000);
+    assert_eq!(seg.id(), 1);
+    assert_eq!(parsed.program(seg), Some(&programs[1]));
+
+    let seg = &parsed.segments[1];
+
+    assert!(!seg.is_blocked());
+    assert!(seg.is_ordered());
+    assert_eq!(parsed.program(seg), None);
+
+    assert_eq!(parsed.info.paid, 0x3800000000000011);
+    assert_eq!(parsed.info.program_type, 0x9);
+    assert_eq!(parsed.info.app_version, 0);
+    assert_eq!(parsed.info.fw_version, 0x0505000000000000);
+}
+
+#[test]
+fn invalid() {
+    let programs = [ProgramHeader {
+        ty: 1,
+        flags: 5,
+        offset: 0x4000,
+        vaddr: 0,
+        paddr: 0,
+        filesz: 0x3000,
+        memsz: 0x3000,
+        align: 0x4000,
+    }];
+    let data = fixture(&[], &programs, ET_SCE_EXEC, 0);
+
+    // Not SELF.
+    let mut elf = data.clone();
+
+    elf[..4].copy_from_slice(b"\x7FELF");
+
+    assert!(matches!(
+        SelfFile::read(&mut Cursor::new(elf)),
+        Err(SelfError::NotSelf)
+    ));
+
+    // Not ELF.
+    let mut bad = data.clone();
+
+    bad[32] = 0;
+
+    assert!(matches!(
+        SelfFile::read(&mut Cursor::new(bad)),
+        Err(SelfError::NotElf)
+    ));
+
+    // Invalid size of program header.
+    let mut bad = data.clone();
+
+    bad[(32 + 54)..(32 + 56)].copy_from_slice(&32u16.to_le_bytes());
+
+    assert!(matches!(
+        SelfFile::read(&mut Cursor::new(bad)),
+        Err(SelfError::InvalidProgramHeaderSize(32))
+    ));
+
+    // Truncated.
+    for len in [16, 32 + 60, 32 + 64 + 40, data.len() - 1] {
+        assert!(matches!(
+            SelfFile::read(&mut Cursor::new(&data[..len])),
+            Err(SelfError::Read(_))
+        ));
+    }
+
+    // Parse incrementally.
+    let mut len = 0;
+    let mut steps = Vec::new();
+
+    let parsed = loop {
+        match SelfFile::parse(&data[..len]) {
+            Ok(v) => break v,
+            Err(SelfError::Truncated(v)) => {
+                assert!(v > len && v <= data.len());
+
+                len = v;
+                steps.push(v);
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    };
+
+    assert_eq!(steps, [32, 32 + 64, 32 + 64 + 56, data.len()]);
+    assert_eq!(parsed.programs, programs);
+
+    // Offset of program headers that overflows.
+    let mut bad = data.clone();
+
+    bad[(32 + 32)..(32 + 40)].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    assert!(matches!(
+        SelfFile::parse(&bad),
+        Err(SelfError::InvalidProgramHeaderOffset(u64::MAX))
+    ));
+}
+
+fn segment() -> impl Strategy<Value = SelfSegment> {
+    (any::<u64>(), any::<u64>(), any::<u64>(), any::<u64>()).prop_map(
+        |(flags, offset, stored_size, size)| SelfSegment {
+            flags,
+            offset,
+            stored_size,
+            size,
+        },
+    )
+}
+
+fn program() -> impl Strategy<Value = ProgramHeader> {
+    (
+        any::<u32>(),
+        any::<u32>(),
+        any::<u64>(),
+        any::<u64>(),
+        any::<u64>(),
+        any::<u64>(),
+    )
+        .prop_map(|(ty, flags, offset, vaddr, filesz, memsz)| ProgramHeader {
+            ty,
+            flags,
+            offset,
+            vaddr,
+            paddr: vaddr,
+            filesz,
+            memsz,
+            align: 0x4000,
+        })
+}
+
+/// Builds the headers of a synthetic SELF file.
+fn fixture(segments: &[SelfSegment], programs: &[ProgramHeader], ty: u16, paid: u64) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    // SELF header.
+    data.extend_from_slice(&SELF_MAGIC);
+    data.extend_from_slice(&[0, 1, 1, 0x12]);
+    data.extend_from_slice(&0x101u32.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes()); // Header size.
+    data.extend_from_slice(&0x100u16.to_le_bytes());
+    data.extend_from_slice(&0x8000u64.to_le_bytes());
+    data.extend_from_slice(&(segments.len() as u16).to_le_bytes());
+    data.extend_from_slice(&0x22u16.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+
+    // Segment table.
+    for s in segments {
+        data.extend_from_slice(&s.flags.to_le_bytes());
+        data.extend_from_slice(&s.offset.to_le_bytes());
+        data.extend_from_slice(&s.stored_size.to_le_bytes());
+        data.extend_from_slice(&s.size.to_le_bytes());
+    }
+
+    // ELF header.
+    data.extend_from_slice(b"\x7FELF\x02\x01\x01\x09");
+    data.extend_from_slice(&[0; 8]);
+    data.extend_from_slice(&ty.to_le_bytes());
+    data.extend_from_slice(&0x3Eu16.to_le_bytes());
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes()); // Entry.
+    data.extend_from_slice(&64u64.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes()); // Section headers.
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&64u16.to_le_bytes());
+    data.extend_from_slice(&56u16.to_le_bytes());
+    data.extend_from_slice(&(programs.len() as u16).to_le_bytes());
+    data.extend_from_slice(&[0; 6]);
+
+    // Program headers.
+    for p in programs {
+        data.extend_from_slice(&p.ty.to_le_bytes());
+        data.extend_from_slice(&p.flags.to_le_bytes());
+        data.extend_from_slice(&p.offset.to_le_bytes());
+        data.extend_from_slice(&p.vaddr.to_le_bytes());
+        data.extend_from_slice(&p.paddr.to_le_bytes());
+        data.extend_from_slice(&p.filesz.to_le_bytes());
+        data.extend_from_slice(&p.memsz.to_le_bytes());
+        data.extend_from_slice(&p.align.to_le_bytes());
+    }
+
+    // Extended information.
+    data.resize(data.len().next_multiple_of(16), 0);
+    data.extend_from_slice(&paid.to_le_bytes());
+    data.extend_from_slice(&0x9u64.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&0x0505000000000000u64.to_le_bytes());
+    data.extend_from_slice(&[0xAB; 32]);
+
+    let len = data.len() as u16;
+
+    data[12..14].copy_from_slice(&len.to_le_bytes());
+    data
+}
@@ -0,0 +1,442 @@
+use obfw::ps4::{
+    walk, DecryptedSelf, FileBlock, FsSource, HostFs, Metadata, NodeInfo, NodeType, PartData,
+    WalkError,
+};
+use obfw::{DumpReader, DumpWriter, ItemReader};
+use std::io::{Cursor, Read};
+
+#[test]
+fn traversal_order() {
+    let mut fs = MockFs::default();
+    let root = fs.dir(None, b"");
+    let b = fs.dir(Some(root), b"b");
+
+    fs.file(Some(b), b"x", b"nested", 1, 1);
+    fs.file(Some(root), b"a", &vec![7; 0x20000], 2, 1);
+    fs.link(Some(root), b"c", b"b/x");
+
+    let (items, actual) = write(&mut fs).unwrap();
+
+    // Items must be breadth-first in the order returned by the source.
+    assert_eq!(items, 6);
+    assert_eq!(
+        actual,
+        [
+            Item::Directory(b"".to_vec()),
+            Item::Directory(b"/b".to_vec()),
+            Item::File(b"/a".to_vec(), vec![7; 0x20000]),
+            Item::Symlink(b"/c".to_vec(), b"b/x".to_vec()),
+            Item::File(b"/b/x".to_vec(), b"nested".to_vec()),
+        ]
+    );
+
+    // The source should be read until it returns zero.
+    let mut reads: Vec<(u64, u64)> = (0..0x20000).step_by(100).map(|off| (2, off)).collect();
+
+    reads.extend([(2, 0x20000), (1, 0), (1, 6)]);
+
+    assert_eq!(fs.reads, reads);
+}
+
+#[test]
+fn hard_links() {
+    let mut fs = MockFs::default();
+    let root = fs.dir(None, b"");
+
+    fs.file(Some(root), b"a", b"abc", 1, 2);
+    fs.file(Some(root), b"b", b"abc", 1, 2);
+    fs.file(Some(root), b"c", b"def", 2, 1);
+    fs.file(Some(root), b"d", b"def", 2, 1);
+
+    let (items, actual) = write(&mut fs).unwrap();
+
+    // Files with a single link must not be deduplicated even if they have the same ID.
+    assert_eq!(items, 6);
+    assert_eq!(
+        actual,
+        [
+            Item::Directory(b"".to_vec()),
+            Item::File(b"/a".to_vec(), b"abc".to_vec()),
+            Item::HardLink(b"/b".to_vec(), b"/a".to_vec()),
+            Item::File(b"/c".to_vec(), b"def".to_vec()),
+            Item::File(b"/d".to_vec(), b"def".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn decrypted_self() {
+    let mut fs = MockFs::default();
+    let root = fs.dir(None, b"");
+    let a = fs.file(Some(root), b"a.sprx", b"SELF", 1, 1);
+
+    // ELF header with a single program header at 0x100.
+    let mut hdrs = vec![0; 64 + 56];
+
+    hdrs[..4].copy_from_slice(b"\x7FELF");
+    hdrs[32..40].copy_from_slice(&64u64.to_le_bytes());
+    hdrs[54..56].copy_from_slice(&56u16.to_le_bytes());
+    hdrs[56..58].copy_from_slice(&1u16.to_le_bytes());
+    hdrs[(64 + 8)..(64 + 16)].copy_from_slice(&0x100u64.to_le_bytes());
+    hdrs[(64 + 32)..(64 + 40)].copy_from_slice(&0x150u64.to_le_bytes());
+
+    fs.nodes[a].headers = hdrs.clone();
+    fs.nodes[a].segment = vec![9; 0x150];
+
+    // The SELF must be returned by default.
+    let (_, actual) = write(&mut fs).unwrap();
+
+    assert_eq!(
+        actual,
+        [
+            Item::Directory(b"".to_vec()),
+            Item::File(b"/a.sprx".to_vec(), b"SELF".to_vec()),
+        ]
+    );
+
+    // Read as ELF.
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+    let mut part = w.ps4_part(b"ufs", b"/dev/da0x4.crypt").unwrap();
+
+    walk(&mut part, &mut fs, FileBlock::Sparse).unwrap();
+    part.finish().unwrap();
+
+    let data = w.finish().unwrap();
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+    let mut p = match r.next_item().unwrap().unwrap() {
+        ItemReader::Ps4Part(v) => v,
+        _ => panic!("unexpected item"),
+    };
+    let mut elf = Vec::new();
+
+    p.set_elf(true);
+    p.next_item().unwrap();
+
+    match p.next_item().unwrap() {
+        Some(PartData::File(_, _, mut f)) => f.read_to_end(&mut elf).unwrap(),
+        _ => panic!("unexpected item"),
+    };
+
+    hdrs.resize(0x100, 0);
+    hdrs.extend_from_slice(&[9; 0x150]);
+
+    assert_eq!(elf, hdrs);
+}
+
+#[test]
+fn special_files() {
+    let mut fs = MockFs::default();
+    let root = fs.dir(None, b"");
+
+    fs.special(Some(root), b"null", NodeType::CharDevice, 0x102);
+    fs.special(Some(root), b"disk", NodeType::BlockDevice, 0x304);
+    fs.special(Some(root), b"fifo", NodeType::Fifo, 0);
+    fs.special(Some(root), b"sock", NodeType::Socket, 0);
+    fs.special(Some(root), b"bad", NodeType::Other, 0);
+
+    let (items, actual) = write(&mut fs).unwrap();
+
+    // Unknown item must be skipped.
+    assert_eq!(items, 6);
+    assert_eq!(
+        actual,
+        [
+            Item::Directory(b"".to_vec()),
+            Item::Special(b"/null".to_vec(), 0x102),
+            Item::Special(b"/disk".to_vec(), 0x304),
+            Item::Special(b"/fifo".to_vec(), 0),
+            Item::Special(b"/sock".to_vec(), 0),
+        ]
+    );
+}
+
+#[test]
+fn source_error() {
+    let mut fs = MockFs::default();
+    let root = fs.dir(None, b"");
+    let a = fs.dir(Some(root), b"a");
+    let b = fs.file(Some(a), b"b", b"abc", 1, 1);
+
+    fs.file(Some(root), b"c", b"def", 2, 1);
+    fs.fail = Some(b);
+
+    // The walk must stop at the first error.
+    match write(&mut fs) {
+        Err(WalkError::Source(v)) => assert_eq!(v, b),
+        _ => panic!("unexpected result"),
+    }
+
+    assert_eq!(fs.reads, [(2, 0), (2, 3)]);
+}
+
+#[test]
+fn host() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+
+    std::fs::create_dir(root.join("sys")).unwrap();
+    std::fs::write(root.join("b.txt"), b"hello").unwrap();
+    std::fs::write(root.join("sys").join("a.bin"), vec![0; 0x20000]).unwrap();
+    std::fs::write(root.join("a.txt"), b"").unwrap();
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink("sys/a.bin", root.join("link")).unwrap();
+        std::fs::hard_link(root.join("b.txt"), root.join("sys").join("hard")).unwrap();
+    }
+
+    let (_, actual) = write(&mut HostFs::new(root)).unwrap();
+    let mut expected = vec![
+        Item::Directory(b"".to_vec()),
+        Item::File(b"/a.txt".to_vec(), Vec::new()),
+        Item::File(b"/b.txt".to_vec(), b"hello".to_vec()),
+    ];
+
+    #[cfg(unix)]
+    expected.push(Item::Symlink(b"/link".to_vec(), b"sys/a.bin".to_vec()));
+
+    expected.push(Item::Directory(b"/sys".to_vec()));
+    expected.push(Item::File(b"/sys/a.bin".to_vec(), vec![0; 0x20000]));
+
+    #[cfg(unix)]
+    expected.push(Item::HardLink(b"/sys/hard".to_vec(), b"/b.txt".to_vec()));
+
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn host_not_found() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut fs = HostFs::new(tmp.path().join("missing"));
+
+    assert!(matches!(write(&mut fs), Err(WalkError::Source(_))));
+}
+
+/// Write `fs` as a single partition and read it back. Returns the number of items in the dump and
+/// the items in the partition.
+fn write<F: FsSource>(fs: &mut F) -> Result<(u32, Vec<Item>), WalkError<F::Error, std::io::Error>> {
+    let mut w = DumpWriter::new(Vec::new()).unwrap();
+    let mut part = w.ps4_part(b"ufs", b"/dev/da0x4.crypt").unwrap();
+
+    walk(&mut part, fs, FileBlock::Sparse)?;
+
+    part.finish().unwrap();
+
+    // Read back.
+    let data = w.finish().unwrap();
+    let mut r = DumpReader::new(Cursor::new(data)).unwrap();
+    let items = r.items();
+    let mut p = match r.next_item().unwrap().unwrap() {
+        ItemReader::Ps4Part(v) => v,
+        _ => panic!("unexpected item"),
+    };
+    let mut actual = Vec::new();
+
+    while let Some(v) = p.next_item().unwrap() {
+        let item = match v {
+            PartData::Directory(path, _) => Item::Directory(path),
+            PartData::File(path, _, mut f) => {
+                let mut data = Vec::new();
+
+                f.read_to_end(&mut data).unwrap();
+
+                Item::File(path, data)
+            }
+            PartData::Symlink(path, _, target) => Item::Symlink(path, target),
+            PartData::HardLink(path, _, target) => Item::HardLink(path, target),
+            PartData::CharDevice(path, _, rdev) | PartData::BlockDevice(path, _, rdev) => {
+                Item::Special(path, rdev)
+            }
+            PartData::Fifo(path, _) | PartData::Socket(path, _) => Item::Special(path, 0),
+        };
+
+        actual.push(item);
+    }
+
+    Ok((items, actual))
+}
+
+#[derive(Debug, PartialEq)]
+enum Item {
+    Directory(Vec<u8>),
+    File(Vec<u8>, Vec<u8>),
+    Symlink(Vec<u8>, Vec<u8>),
+    HardLink(Vec<u8>, Vec<u8>),
+    Special(Vec<u8>, u64),
+}
+
+/// Implementation of [`FsSource`] in memory. Each node is an index into `nodes`.
+#[derive(Default)]
+struct MockFs {
+    nodes: Vec<MockNode>,
+    fail: Option<usize>,
+    reads: Vec<(u64, u64)>,
+}
+
+impl MockFs {
+    /// Maximum number of bytes to return on each read.
+    const CHUNK: usize = 100;
+
+    fn dir(&mut self, parent: Option<usize>, name: &[u8]) -> usize {
+        self.add(parent, name, NodeType::Directory)
+    }
+
+    fn file(
+        &mut self,
+        parent: Option<usize>,
+        name: &[u8],
+        data: &[u8],
+        id: u64,
+        nlink: u64,
+    ) -> usize {
+        let i = self.add(parent, name, NodeType::File);
+        let n = &mut self.nodes[i];
+
+        n.data = data.to_vec();
+        n.id = id;
+        n.nlink = nlink;
+
+        i
+    }
+
+    fn link(&mut self, parent: Option<usize>, name: &[u8], target: &[u8]) -> usize {
+        let i = self.add(parent, name, NodeType::Symlink);
+
+        self.nodes[i].data = target.to_vec();
+
+        i
+    }
+
+    fn special(&mut self, parent: Option<usize>, name: &[u8], ty: NodeType, rdev: u64) -> usize {
+        let i = self.add(parent, name, ty);
+
+        self.nodes[i].rdev = rdev;
+
+        i
+    }
+
+    fn add(&mut self, parent: Option<usize>, name: &[u8], ty: NodeType) -> usize {
+        let i = self.nodes.len();
+
+        self.nodes.push(MockNode {
+            ty,
+            children: Vec::new(),
+            data: Vec::new(),
+            id: 0,
+            nlink: 1,
+            rdev: 0,
+            headers: Vec::new(),
+            segment: Vec::new(),
+        });
+
+        if let Some(p) = parent {
+            self.nodes[p].children.push((name.to_vec(), i));
+        }
+
+        i
+    }
+}
+
+impl FsSource for MockFs {
+    type Node = usize;
+    type File = usize;
+    type Error = usize;
+
+    fn root(&mut self) -> Result<Self::Node, Self::Error> {
+        Ok(0)
+    }
+
+    fn stat(&mut self, node: &Self::Node) -> Result<NodeInfo, Self::Error> {
+        if self.fail == Some(*node) {
+            return Err(*node);
+        }
+
+        let n = &self.nodes[*node];
+
+        Ok(NodeInfo {
+            ty: n.ty,
+            meta: Metadata::default(),
+            nlink: n.nlink,
+            id: n.id,
+            rdev: n.rdev,
+        })
+    }
+
+    fn list(
+        &mut self,
+        dir: &Self::Node,
+        out: &mut Vec<(Vec<u8>, Self::Node)>,
+    ) -> Result<(), Self::Error> {
+        out.extend(self.nodes[*dir].children.iter().cloned());
+        Ok(())
+    }
+
+    fn open(&mut self, node: &Self::Node) -> Result<Self::File, Self::Error> {
+        Ok(*node)
+    }
+
+    fn read(
+        &mut self,
+        file: &mut Self::File,
+        off: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let data = &self.nodes[*file].data;
+        let off = std::cmp::min(off as usize, data.len());
+        let len = std::cmp::min(buf.len(), std::cmp::min(data.len() - off, Self::CHUNK));
+
+        self.reads.push((self.nodes[*file].id, off as u64));
+        buf[..len].copy_from_slice(&data[off..(off + len)]);
+
+        Ok(len)
+    }
+
+    fn read_link(&mut self, node: &Self::Node) -> Result<Vec<u8>, Self::Error> {
+        Ok(self.nodes[*node].data.clone())
+    }
+
+    fn decrypt(&mut self, file: &mut Self::File) -> Result<Option<DecryptedSelf>, Self::Error> {
+        let n = &self.nodes[*file];
+
+        if n.headers.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(DecryptedSelf {
+            headers: n.headers.clone(),
+            segments: vec![0],
+        }))
+    }
+
+    fn read_segment(
+        &mut self,
+        file: &mut Self::File,
+        index: u16,
+        off: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        assert_eq!(index, 0);
+
+        let data = &self.nodes[*file].segment;
+        let off = std::cmp::min(off as usize, data.len());
+        let len = std::cmp::min(buf.len(), std::cmp::min(data.len() - off, Self::CHUNK));
+
+        buf[..len].copy_from_slice(&data[off..(off + len)]);
+
+        Ok(len)
+    }
+}
+
+struct MockNode {
+    ty: NodeType,
+    children: Vec<(Vec<u8>, usize)>,
+    /// Content of the file or the target of the link.
+    data: Vec<u8>,
+    id: u64,
+    nlink: u64,
+    rdev: u64,
+    /// ELF header and program headers if this is a SELF.
+    headers: Vec<u8>,
+    /// Decrypted data of the first segment.
+    segment: Vec<u8>,
+}